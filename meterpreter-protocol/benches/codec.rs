@@ -0,0 +1,75 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use meterpreter_protocol::packet::Packet;
+use meterpreter_protocol::tlv::{BinaryWriter, Tlv};
+
+mod shapes;
+
+const SESSION_GUID: [u8; 16] = [0; 16];
+const XOR_KEY: [u8; 4] = [1, 2, 3, 4];
+
+fn encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Packet::to_raw");
+    for (name, packet) in shapes::all() {
+        let raw = packet.to_raw_with_xor_key(&SESSION_GUID, XOR_KEY);
+        group.throughput(Throughput::Bytes(raw.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &packet, |b, packet| {
+            b.iter(|| packet.to_raw_with_xor_key(black_box(&SESSION_GUID), XOR_KEY))
+        });
+    }
+    group.finish();
+}
+
+fn encode_tlvs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Tlv::to_raw");
+    for (name, packet) in shapes::all() {
+        let tlvs: Vec<&Tlv> = packet.tlvs.iter().collect();
+        group.bench_with_input(BenchmarkId::from_parameter(name), &tlvs, |b, tlvs| {
+            b.iter(|| {
+                let mut storage = vec![];
+                for tlv in tlvs {
+                    tlv.to_raw(&mut storage);
+                }
+                storage
+            })
+        });
+    }
+    group.finish();
+}
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Packet::from_raw");
+    for (name, packet) in shapes::all() {
+        let raw = packet.to_raw_with_xor_key(&SESSION_GUID, XOR_KEY);
+        group.throughput(Throughput::Bytes(raw.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &raw, |b, raw| {
+            b.iter(|| Packet::from_raw(black_box(raw), &mut 0).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn binary_writer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("BinaryWriter");
+    group.bench_function("write_dword", |b| {
+        b.iter(|| {
+            let mut storage = Vec::with_capacity(4 * 1024);
+            for value in 0..1024 {
+                BinaryWriter::write_dword(&mut storage, black_box(value));
+            }
+            storage
+        })
+    });
+    group.bench_function("write_string", |b| {
+        b.iter(|| {
+            let mut storage = Vec::with_capacity(16 * 1024);
+            for _ in 0..1024 {
+                BinaryWriter::write_string(&mut storage, black_box("stdapi_fs_ls"));
+            }
+            storage
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, encode, encode_tlvs, decode, binary_writer);
+criterion_main!(benches);
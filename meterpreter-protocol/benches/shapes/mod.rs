@@ -0,0 +1,59 @@
+// Packet shapes the codec is measured with, shared by the benchmarks and the allocation test
+use meterpreter_protocol::packet::Packet;
+use meterpreter_protocol::tlv::{Add, Tlv, TlvType};
+
+// The stdapi types the shapes are made of, defined the way the extension does
+mod stdapi {
+    meterpreter_protocol::tlv_keys! {
+        StdapiFileName = String | 1201,
+        StdapiInterfaceMtu = Uint | 1402,
+        StdapiMacName = String | 1432,
+        StdapiNetworkInterface = Group | 1433,
+    }
+}
+
+// Lots of tiny values, like a big directory listing
+pub fn many_small_tlvs() -> Packet {
+    let mut packet = Packet::new(String::from("stdapi_fs_ls"));
+    packet.set_request_id(String::from("1"));
+    for index in 0..1000 {
+        packet.add(stdapi::StdapiFileName, format!("file-{}", index));
+        packet.add(TlvType::Length, index);
+    }
+    packet
+}
+
+// Groups nested as deep as the default decode limits allow, a few values on every level
+pub fn deep_groups() -> Packet {
+    let mut group = Tlv::group(stdapi::StdapiNetworkInterface);
+    group.add(stdapi::StdapiMacName, String::from("eth0"));
+    for depth in 0..30 {
+        let mut parent = Tlv::group(stdapi::StdapiNetworkInterface);
+        parent.add(stdapi::StdapiMacName, format!("level-{}", depth));
+        parent.add(stdapi::StdapiInterfaceMtu, 1500);
+        parent.add_tlv(group);
+        group = parent;
+    }
+
+    let mut packet = Packet::new(String::from("stdapi_net_config_get_interfaces"));
+    packet.set_request_id(String::from("1"));
+    packet.add_tlv(group);
+    packet
+}
+
+// A single big chunk of channel data
+pub fn large_payload() -> Packet {
+    let mut packet = Packet::new(String::from("core_channel_write"));
+    packet.set_request_id(String::from("1"));
+    packet.add(TlvType::ChannelId, 1);
+    packet.add(TlvType::ChannelData, vec![0x5a; 1024 * 1024]);
+    packet
+}
+
+pub fn all() -> [(&'static str, Packet); 3] {
+    [
+        ("many_small_tlvs", many_small_tlvs()),
+        ("deep_groups", deep_groups()),
+        ("large_payload", large_payload()),
+    ]
+}
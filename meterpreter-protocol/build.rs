@@ -0,0 +1,45 @@
+// Generates the TlvType constants and the reverse lookup table from the upstream TLV
+// definitions checked in under tlv-definitions/. Extensions generate their own types with
+// the same code, see src/definitions.rs
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+#[path = "src/definitions.rs"]
+mod definitions;
+
+const DEFINITIONS: &str = "tlv-definitions/packet.rb";
+const RUST_NAMES: &str = "tlv-definitions/rust_names.txt";
+const CORE_TLV_TYPES: &str = "core_tlv_types.rs";
+const TLV_NAMES: &str = "tlv_names.rs";
+
+fn main() {
+    println!("cargo:rerun-if-changed=tlv-definitions");
+    println!("cargo:rerun-if-changed=src/definitions.rs");
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let rust_names = definitions::read_rust_names(RUST_NAMES);
+
+    let mut known = definitions::parse(DEFINITIONS, "", &rust_names, &mut HashMap::new());
+    fs::write(
+        Path::new(&out_dir).join(CORE_TLV_TYPES),
+        definitions::generate(DEFINITIONS, "tlv_types", &known),
+    )
+    .unwrap();
+
+    known.sort_by_key(|definition| definition.meta_bits | definition.number);
+    let mut generated = format!(
+        "// Generated by build.rs from {}, sorted by value\n\
+         const KNOWN_TLV_TYPES: &[(u32, &str)] = &[\n",
+        DEFINITIONS
+    );
+    for definition in &known {
+        generated += &format!(
+            "    ({:#010x}, \"{}\"),\n",
+            definition.meta_bits | definition.number,
+            definition.name
+        );
+    }
+    generated += "];\n";
+    fs::write(Path::new(&out_dir).join(TLV_NAMES), generated).unwrap();
+}
@@ -0,0 +1,79 @@
+# -*- coding: binary -*-
+#
+# Encodes every fixture described by a .json in this directory with
+# Rex::Post::Meterpreter::Packet and writes the bytes to the .hex next to it.
+# The .json gets the XOR key Rex picked, the test encodes the packet again with it.
+#
+# Run it from a metasploit-framework checkout:
+#
+#   cd metasploit-framework
+#   bundle exec ruby -Ilib path/to/fixtures/packets/generate.rb
+#
+require 'json'
+require 'rex/post/meterpreter/packet'
+
+# JSON type name => TLV type. The values come from the definitions this crate is
+# generated from, newer frameworks dropped some of them (TLV_TYPE_METHOD)
+TLV_TYPES = {
+  'Method'                 => 0x00010001, # TLV_TYPE_METHOD
+  'RequestId'              => 0x00010002, # TLV_TYPE_REQUEST_ID
+  'Result'                 => 0x00020004, # TLV_TYPE_RESULT
+  'String'                 => 0x0001000a, # TLV_TYPE_STRING
+  'Bool'                   => 0x0008000c, # TLV_TYPE_BOOL
+  'Length'                 => 0x00020019, # TLV_TYPE_LENGTH
+  'ChannelId'              => 0x00020032, # TLV_TYPE_CHANNEL_ID
+  'ChannelType'            => 0x00010033, # TLV_TYPE_CHANNEL_TYPE
+  'ChannelData'            => 0x00040034, # TLV_TYPE_CHANNEL_DATA
+  'StdapiProcessHandle'    => 0x00100276, # TLV_TYPE_PROCESS_HANDLE
+  'StdapiFilePath'         => 0x000104b2, # TLV_TYPE_FILE_PATH
+  'StdapiFileMode'         => 0x000104b3, # TLV_TYPE_FILE_MODE
+  'StdapiInterfaceMtu'     => 0x0002057a, # TLV_TYPE_INTERFACE_MTU
+  'StdapiInterfaceFlags'   => 0x0001057b, # TLV_TYPE_INTERFACE_FLAGS
+  'StdapiInterfaceIndex'   => 0x0002057c, # TLV_TYPE_INTERFACE_INDEX
+  'StdapiNetmask'          => 0x0004058d, # TLV_TYPE_NETMASK
+  'StdapiIp'               => 0x00040596, # TLV_TYPE_IP
+  'StdapiMacAddr'          => 0x00040597, # TLV_TYPE_MAC_ADDRESS
+  'StdapiMacName'          => 0x00010598, # TLV_TYPE_MAC_NAME
+  'StdapiNetworkInterface' => 0x40000599, # TLV_TYPE_NETWORK_INTERFACE
+  'StdapiProcessId'        => 0x000208fc, # TLV_TYPE_PID
+}
+
+PACKET_TYPES = {
+  'Request'  => Rex::Post::Meterpreter::PACKET_TYPE_REQUEST,
+  'Response' => Rex::Post::Meterpreter::PACKET_TYPE_RESPONSE,
+}
+
+def add_tlvs(parent, tlvs)
+  tlvs.each do |tlv|
+    type = TLV_TYPES.fetch(tlv['type'])
+    if tlv.key?('tlvs')
+      add_tlvs(parent.add_tlv(type), tlv['tlvs'])
+    elsif type & Rex::Post::Meterpreter::TLV_META_TYPE_RAW != 0
+      parent.add_tlv(type, [tlv['value']].pack('H*'))
+    else
+      parent.add_tlv(type, tlv['value'])
+    end
+  end
+end
+
+def hex_lines(raw)
+  raw.unpack1('H*').scan(/.{1,32}/).map { |line| line.scan(/../).join(' ') }
+end
+
+version = defined?(Metasploit::Framework::VERSION) ? Metasploit::Framework::VERSION : 'unknown version'
+
+Dir.glob(File.join(__dir__, '*.json')).sort.each do |json_path|
+  fixture = JSON.parse(File.read(json_path))
+  packet = Rex::Post::Meterpreter::Packet.new(PACKET_TYPES.fetch(fixture['packet_type']))
+  add_tlvs(packet, fixture['tlvs'])
+  raw = packet.to_r([fixture['session_guid']].pack('H*'))
+
+  File.write(json_path.sub(/\.json\z/, '.hex'), ([
+    "# #{fixture['description']}",
+    "# Source: Rex::Post::Meterpreter::Packet#to_r, metasploit-framework #{version},",
+    '# written by generate.rb',
+  ] + hex_lines(raw)).join("\n") + "\n")
+
+  fixture['xor_key'] = raw[0, 4].unpack1('H*')
+  File.write(json_path, JSON.pretty_generate(fixture) + "\n")
+end
@@ -0,0 +1,265 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::tlv::BinaryWriter;
+
+// Start of every configuration block, followed by the version of the layout
+pub const CONFIG_MAGIC: [u8; 4] = *b"MCFG";
+pub const CONFIG_VERSION: u32 = 1;
+
+// Settings the agent starts a session with. Serialized big endian like the packets:
+//
+// magic, version, session guid (16), uuid (16), expiry, transport count, transports,
+// extension count, extensions
+//
+// a transport is its url, comm timeout, retry total and retry wait, strings are a length
+// followed by that many bytes of UTF-8
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Config {
+    pub session_guid: [u8; 16],
+    pub uuid: [u8; 16],
+    // seconds the session lives before the agent exits on its own
+    pub expiry: u32,
+    // tried in order, the first one is used to connect
+    pub transports: Vec<TransportConfig>,
+    // extensions loaded at startup, besides the ones that are always there
+    pub extensions: Vec<String>,
+}
+
+// Timeouts are in seconds, like TransCommTimeout, TransRetryTotal and TransRetryWait
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransportConfig {
+    pub url: String,
+    pub comm_timeout: u32,
+    pub retry_total: u32,
+    pub retry_wait: u32,
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    #[error("Not a configuration block")]
+    BadMagic,
+
+    #[error("Configuration version {0} is not supported")]
+    UnsupportedVersion(u32),
+
+    #[error("Configuration block ends too early")]
+    Truncated,
+
+    #[error("Configuration block holds a string that isn't valid UTF-8")]
+    InvalidString,
+
+    #[error("{0} bytes left over after the configuration block")]
+    TrailingBytes(usize),
+}
+
+impl Config {
+    pub fn from_bytes(data: &[u8]) -> Result<Config, ConfigError> {
+        let mut reader = Reader { data, position: 0 };
+        if reader.take(CONFIG_MAGIC.len())? != CONFIG_MAGIC {
+            return Err(ConfigError::BadMagic);
+        }
+        let version = reader.dword()?;
+        if version != CONFIG_VERSION {
+            return Err(ConfigError::UnsupportedVersion(version));
+        }
+
+        let session_guid = reader.guid()?;
+        let uuid = reader.guid()?;
+        let expiry = reader.dword()?;
+
+        // counts aren't trusted for preallocation, every entry is read before it's kept
+        let mut transports = vec![];
+        for _ in 0..reader.dword()? {
+            transports.push(TransportConfig {
+                url: reader.string()?,
+                comm_timeout: reader.dword()?,
+                retry_total: reader.dword()?,
+                retry_wait: reader.dword()?,
+            });
+        }
+        let mut extensions = vec![];
+        for _ in 0..reader.dword()? {
+            extensions.push(reader.string()?);
+        }
+
+        let left = data.len() - reader.position;
+        if left != 0 {
+            return Err(ConfigError::TrailingBytes(left));
+        }
+        Ok(Config {
+            session_guid,
+            uuid,
+            expiry,
+            transports,
+            extensions,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut storage = CONFIG_MAGIC.to_vec();
+        BinaryWriter::write_dword(&mut storage, CONFIG_VERSION);
+        storage.extend(self.session_guid);
+        storage.extend(self.uuid);
+        BinaryWriter::write_dword(&mut storage, self.expiry);
+
+        BinaryWriter::write_dword(&mut storage, self.transports.len() as u32);
+        for transport in &self.transports {
+            write_string(&mut storage, &transport.url);
+            BinaryWriter::write_dword(&mut storage, transport.comm_timeout);
+            BinaryWriter::write_dword(&mut storage, transport.retry_total);
+            BinaryWriter::write_dword(&mut storage, transport.retry_wait);
+        }
+        BinaryWriter::write_dword(&mut storage, self.extensions.len() as u32);
+        for extension in &self.extensions {
+            write_string(&mut storage, extension);
+        }
+        storage
+    }
+}
+
+fn write_string(storage: &mut Vec<u8>, value: &str) {
+    BinaryWriter::write_dword(storage, value.len() as u32);
+    storage.extend(value.as_bytes());
+}
+
+// Bounds checked reads, the block may come from a file that was cut short
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], ConfigError> {
+        let end = self
+            .position
+            .checked_add(length)
+            .filter(|end| *end <= self.data.len())
+            .ok_or(ConfigError::Truncated)?;
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn dword(&mut self) -> Result<u32, ConfigError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn guid(&mut self) -> Result<[u8; 16], ConfigError> {
+        let mut guid = [0; 16];
+        guid.copy_from_slice(self.take(16)?);
+        Ok(guid)
+    }
+
+    fn string(&mut self) -> Result<String, ConfigError> {
+        let length = self.dword()? as usize;
+        let bytes = self.take(length)?;
+        core::str::from_utf8(bytes)
+            .map(String::from)
+            .map_err(|_| ConfigError::InvalidString)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    use super::{Config, ConfigError, TransportConfig, CONFIG_VERSION};
+
+    fn config() -> Config {
+        Config {
+            session_guid: [1; 16],
+            uuid: [2; 16],
+            expiry: 604800,
+            transports: vec![
+                TransportConfig {
+                    url: String::from("tcp://127.0.0.1:4444"),
+                    comm_timeout: 300,
+                    retry_total: 3600,
+                    retry_wait: 10,
+                },
+                TransportConfig {
+                    url: String::from("tcp://10.0.0.1:443"),
+                    comm_timeout: 60,
+                    retry_total: 0,
+                    retry_wait: 5,
+                },
+            ],
+            extensions: vec![String::from("stdapi"), String::from("priv")],
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let config = config();
+        assert_eq!(Config::from_bytes(&config.to_bytes()), Ok(config));
+        assert_eq!(
+            Config::from_bytes(&Config::default().to_bytes()),
+            Ok(Config::default())
+        );
+    }
+
+    #[test]
+    fn test_layout() {
+        let bytes = Config {
+            expiry: 3,
+            extensions: vec![String::from("ab")],
+            ..Config::default()
+        }
+        .to_bytes();
+
+        let mut expected: Vec<u8> = b"MCFG".to_vec();
+        expected.extend([0, 0, 0, 1]);
+        expected.extend([0; 32]);
+        expected.extend([0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 2, b'a', b'b']);
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn test_rejects_other_blocks() {
+        let mut bytes = config().to_bytes();
+        bytes[0] = b'X';
+        assert_eq!(Config::from_bytes(&bytes), Err(ConfigError::BadMagic));
+
+        let mut bytes = config().to_bytes();
+        bytes[7] = CONFIG_VERSION as u8 + 1;
+        assert_eq!(
+            Config::from_bytes(&bytes),
+            Err(ConfigError::UnsupportedVersion(CONFIG_VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn test_rejects_damaged_blocks() {
+        let bytes = config().to_bytes();
+        for length in 0..bytes.len() {
+            assert!(Config::from_bytes(&bytes[..length]).is_err());
+        }
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            Config::from_bytes(&trailing),
+            Err(ConfigError::TrailingBytes(1))
+        );
+
+        // a count far beyond the data
+        let mut huge_count = Config::default().to_bytes();
+        let count_offset = huge_count.len() - 8;
+        huge_count[count_offset..count_offset + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert_eq!(Config::from_bytes(&huge_count), Err(ConfigError::Truncated));
+
+        let mut invalid = Config {
+            extensions: vec![String::from("x")],
+            ..Config::default()
+        }
+        .to_bytes();
+        *invalid.last_mut().unwrap() = 0xff;
+        assert_eq!(
+            Config::from_bytes(&invalid),
+            Err(ConfigError::InvalidString)
+        );
+    }
+}
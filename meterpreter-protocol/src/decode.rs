@@ -0,0 +1,261 @@
+use crate::tlv::TlvType;
+
+// Bounds on what a packet from the other side may make the decoder do
+#[derive(Debug, Clone)]
+pub struct DecodeLimits {
+    pub max_body_bytes: usize,
+    // groups inside groups, 0 allows no groups at all
+    pub max_group_depth: usize,
+    // counted over the whole packet, TLVs inside groups included
+    pub max_tlvs: usize,
+    pub max_string_length: usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: 16 * 1024 * 1024,
+            max_group_depth: 32,
+            max_tlvs: 100_000,
+            max_string_length: 1024 * 1024,
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    #[error("Packet body of {length} bytes is over the limit of {max} bytes")]
+    BodyTooLarge { length: usize, max: usize },
+
+    #[error("Groups are nested deeper than {0} levels")]
+    GroupTooDeep(usize),
+
+    #[error("Packet has more than {0} TLVs")]
+    TooManyTlvs(usize),
+
+    #[error("{tlv_type:?} holds a string of {length} bytes, over the limit of {max} bytes")]
+    StringTooLong {
+        tlv_type: TlvType,
+        length: usize,
+        max: usize,
+    },
+
+    #[error("Data ends in the middle of a packet or TLV")]
+    Truncated,
+
+    #[error("Packet length {0} is shorter than the packet header")]
+    InvalidPacketLength(u32),
+
+    #[error("Length {length} is invalid for {tlv_type:?}")]
+    InvalidLength { tlv_type: TlvType, length: u32 },
+
+    #[error("Unknown packet type {0}")]
+    InvalidPacketType(u32),
+
+    #[error("Unknown result code {0}")]
+    UnknownResult(u32),
+
+    #[error("Meta type of {0:?} is not supported")]
+    UnsupportedMetaType(TlvType),
+
+    #[error("{0:?} is not valid UTF-8")]
+    InvalidString(TlvType),
+
+    #[error("Encrypted packets are not supported")]
+    Encrypted,
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::vec::Vec;
+
+    use super::{DecodeError, DecodeLimits};
+    use crate::packet::Packet;
+    use crate::tlv::{MetaType, Tlv, TlvType};
+
+    fn tlv(tlv_type: impl Into<TlvType>, value: &[u8]) -> Vec<u8> {
+        let mut raw = (value.len() as u32 + 8).to_be_bytes().to_vec();
+        raw.extend(u32::from(tlv_type.into()).to_be_bytes());
+        raw.extend(value);
+        raw
+    }
+
+    // A request with an all zero XOR key, so the body can be written in clear
+    fn packet(body: &[u8]) -> Vec<u8> {
+        let mut raw = vec![0; 24];
+        raw.extend((body.len() as u32 + 8).to_be_bytes());
+        raw.extend(0u32.to_be_bytes());
+        raw.extend(body);
+        raw
+    }
+
+    fn decode(raw: &[u8], limits: &DecodeLimits) -> Result<Packet, DecodeError> {
+        Packet::from_raw_with_limits(raw, &mut 0, limits)
+    }
+
+    // Only headers, each group holds the next one and nothing else
+    fn nested_groups(depth: usize) -> Vec<u8> {
+        let mut raw = vec![];
+        for level in 0..depth {
+            raw.extend((((depth - level) * 8) as u32).to_be_bytes());
+            raw.extend(u32::from(TlvType::TransGroup.tlv_type()).to_be_bytes());
+        }
+        raw
+    }
+
+    #[test]
+    fn test_body_too_large() {
+        // only the header is there, nothing may be allocated for the claimed body
+        let mut raw = packet(&[]);
+        raw[24..28].copy_from_slice(&u32::MAX.to_be_bytes());
+
+        assert_eq!(
+            decode(&raw, &DecodeLimits::default()).unwrap_err(),
+            DecodeError::BodyTooLarge {
+                length: u32::MAX as usize - 8,
+                max: 16 * 1024 * 1024
+            }
+        );
+    }
+
+    #[test]
+    fn test_group_too_deep() {
+        let limits = DecodeLimits {
+            max_group_depth: 3,
+            ..DecodeLimits::default()
+        };
+        assert!(decode(&packet(&nested_groups(3)), &limits).is_ok());
+        assert_eq!(
+            decode(&packet(&nested_groups(4)), &limits).unwrap_err(),
+            DecodeError::GroupTooDeep(3)
+        );
+
+        // deep enough to overflow the stack if the recursion wasn't bounded
+        assert_eq!(
+            decode(&packet(&nested_groups(200_000)), &DecodeLimits::default()).unwrap_err(),
+            DecodeError::GroupTooDeep(32)
+        );
+    }
+
+    #[test]
+    fn test_too_many_tlvs() {
+        let limits = DecodeLimits {
+            max_tlvs: 5,
+            ..DecodeLimits::default()
+        };
+        let uint = tlv(TlvType::ChannelId, &[0, 0, 0, 1]);
+        assert!(decode(&packet(&uint.repeat(5)), &limits).is_ok());
+        assert_eq!(
+            decode(&packet(&uint.repeat(6)), &limits).unwrap_err(),
+            DecodeError::TooManyTlvs(5)
+        );
+
+        // TLVs inside groups count as well
+        let group = tlv(TlvType::TransGroup, &uint.repeat(5));
+        assert_eq!(
+            decode(&packet(&group), &limits).unwrap_err(),
+            DecodeError::TooManyTlvs(5)
+        );
+    }
+
+    #[test]
+    fn test_string_too_long() {
+        let limits = DecodeLimits {
+            max_string_length: 10,
+            ..DecodeLimits::default()
+        };
+        assert!(decode(&packet(&tlv(TlvType::Method, b"0123456789\0")), &limits).is_ok());
+        assert_eq!(
+            decode(&packet(&tlv(TlvType::Method, b"0123456789a\0")), &limits).unwrap_err(),
+            DecodeError::StringTooLong {
+                tlv_type: TlvType::Method.into(),
+                length: 11,
+                max: 10
+            }
+        );
+    }
+
+    #[test]
+    fn test_truncated() {
+        let limits = DecodeLimits::default();
+        let raw = packet(&tlv(TlvType::Method, b"core_channel_open\0"));
+        assert_eq!(
+            decode(&raw[..raw.len() - 1], &limits).unwrap_err(),
+            DecodeError::Truncated
+        );
+        assert_eq!(
+            decode(&raw[..20], &limits).unwrap_err(),
+            DecodeError::Truncated
+        );
+
+        // a TLV running past the end of its group
+        let mut group = tlv(TlvType::TransGroup, &tlv(TlvType::TransType, &[0, 0, 0, 1]));
+        group[3] -= 1;
+        group.push(0);
+        assert_eq!(
+            decode(&packet(&group), &limits).unwrap_err(),
+            DecodeError::Truncated
+        );
+    }
+
+    #[test]
+    fn test_malformed_tlvs() {
+        let limits = DecodeLimits::default();
+
+        let mut short = tlv(TlvType::ChannelId, &[]);
+        short[3] = 4;
+        assert_eq!(
+            decode(&packet(&short), &limits).unwrap_err(),
+            DecodeError::InvalidLength {
+                tlv_type: TlvType::ChannelId.into(),
+                length: 4
+            }
+        );
+        assert_eq!(
+            decode(&packet(&tlv(TlvType::ChannelId, &[1])), &limits).unwrap_err(),
+            DecodeError::InvalidLength {
+                tlv_type: TlvType::ChannelId.into(),
+                length: 9
+            }
+        );
+        assert_eq!(
+            decode(&packet(&tlv(TlvType::Method, &[0xff, 0xfe, 0])), &limits).unwrap_err(),
+            DecodeError::InvalidString(TlvType::Method.into())
+        );
+
+        let two_meta_types = TlvType::from(MetaType::String as u32 | MetaType::Uint as u32 | 1);
+        assert_eq!(
+            decode(&packet(&tlv(two_meta_types, &[0; 4])), &limits).unwrap_err(),
+            DecodeError::UnsupportedMetaType(two_meta_types)
+        );
+
+        let storage = tlv(TlvType::Any, &[]);
+        assert_eq!(
+            Tlv::from_raw(&storage, &mut 0).unwrap_err(),
+            DecodeError::UnsupportedMetaType(TlvType::Any.into())
+        );
+    }
+
+    #[test]
+    fn test_malformed_headers() {
+        let limits = DecodeLimits::default();
+
+        let mut raw = packet(&[]);
+        raw[31] = 7;
+        assert_eq!(
+            decode(&raw, &limits).unwrap_err(),
+            DecodeError::InvalidPacketType(7)
+        );
+
+        let mut raw = packet(&[]);
+        raw[27] = 4;
+        assert_eq!(
+            decode(&raw, &limits).unwrap_err(),
+            DecodeError::InvalidPacketLength(4)
+        );
+
+        let mut raw = packet(&[]);
+        raw[23] = 1;
+        assert_eq!(decode(&raw, &limits).unwrap_err(), DecodeError::Encrypted);
+    }
+}
@@ -0,0 +1,138 @@
+// Reads Metasploit's TLV definition files (packet.rb, the tlv.rb of each extension) and turns
+// them into Rust source. Used by the build script of this crate for the core types and by
+// the build scripts of extensions for theirs
+use std::collections::HashMap;
+use std::format;
+use std::fs;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+const META_TYPES: &[(&str, &str, u32)] = &[
+    ("TLV_META_TYPE_NONE", "None", 0),
+    ("TLV_META_TYPE_STRING", "String", 1 << 16),
+    ("TLV_META_TYPE_UINT", "Uint", 1 << 17),
+    ("TLV_META_TYPE_RAW", "Raw", 1 << 18),
+    ("TLV_META_TYPE_BOOL", "Bool", 1 << 19),
+    ("TLV_META_TYPE_QWORD", "Qword", 1 << 20),
+    ("TLV_META_TYPE_COMPRESSED", "Compressed", 1 << 29),
+    ("TLV_META_TYPE_GROUP", "Group", 1 << 30),
+    ("TLV_META_TYPE_COMPLEX", "Complex", 1 << 31),
+];
+
+pub struct Definition {
+    pub name: String,
+    pub meta_type: &'static str,
+    pub meta_bits: u32,
+    pub number: u32,
+}
+
+// Invocation of the macro declaring the types, tlv_types! for the core ones and
+// meterpreter_protocol::tlv_keys! for those of an extension
+pub fn generate(path: &str, macro_name: &str, definitions: &[Definition]) -> String {
+    let mut generated = format!(
+        "// Generated by build.rs from {}\n{}! {{\n",
+        path, macro_name
+    );
+    for definition in definitions {
+        generated += &format!(
+            "    {} = {} | {},\n",
+            definition.name, definition.meta_type, definition.number
+        );
+    }
+    generated += "}\n";
+    generated
+}
+
+// upstream constant name -> TlvType constant name, for names that can't be derived
+pub fn read_rust_names(path: &str) -> HashMap<String, String> {
+    let content = fs::read_to_string(path).unwrap();
+    content
+        .lines()
+        .map(|line| line.split('#').next().unwrap().trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let mut parts = line.split_whitespace();
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(upstream), Some(rust), None) => (upstream.to_string(), rust.to_string()),
+                _ => panic!("{}: malformed line '{}'", path, line),
+            }
+        })
+        .collect()
+}
+
+// Plain numeric constants such as TLV_EXTENSIONS end up in constants, definitions can build
+// on them
+pub fn parse(
+    path: &str,
+    prefix: &str,
+    rust_names: &HashMap<String, String>,
+    constants: &mut HashMap<String, u32>,
+) -> Vec<Definition> {
+    let content = fs::read_to_string(path).unwrap();
+    let mut definitions = vec![];
+
+    for (index, line) in content.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim();
+        let Some((constant, value)) = line.split_once('=') else {
+            continue;
+        };
+        let constant = constant.trim();
+        let location = format!("{}:{}", path, index + 1);
+
+        if let Some(upstream_name) = constant.strip_prefix("TLV_TYPE_") {
+            let (meta_type, number) = value
+                .split_once('|')
+                .unwrap_or_else(|| panic!("{}: expected META_TYPE | number", location));
+            let (_, meta_type, meta_bits) = META_TYPES
+                .iter()
+                .find(|(upstream, _, _)| *upstream == meta_type.trim())
+                .unwrap_or_else(|| panic!("{}: unknown meta type '{}'", location, meta_type));
+            let name = match rust_names.get(constant) {
+                Some(name) => name.clone(),
+                None => format!("{}{}", prefix, camel_case(upstream_name)),
+            };
+
+            definitions.push(Definition {
+                name,
+                meta_type,
+                meta_bits: *meta_bits,
+                number: evaluate(number, constants)
+                    .unwrap_or_else(|| panic!("{}: can't evaluate '{}'", location, number)),
+            });
+        } else if let Some(number) = evaluate(value, constants) {
+            constants.insert(constant.to_string(), number);
+        }
+    }
+
+    definitions
+}
+
+// numbers and previously defined constants joined with +, e.g. "TLV_EXTENSIONS + 1"
+fn evaluate(expression: &str, constants: &HashMap<String, u32>) -> Option<u32> {
+    expression
+        .trim()
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .split('+')
+        .map(|term| {
+            let term = term.trim();
+            term.parse::<u32>()
+                .ok()
+                .or_else(|| constants.get(term).copied())
+        })
+        .sum()
+}
+
+fn camel_case(upstream_name: &str) -> String {
+    upstream_name
+        .split('_')
+        .map(|word| {
+            let word = word.to_lowercase();
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,120 @@
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use uuid::Builder;
+
+use crate::packet::Packet;
+use crate::tlv::TlvType;
+
+// Everything random that ends up on the wire (XOR keys, request ids, IVs) comes from here,
+// a seeded encoder produces the same bytes on every run
+pub struct PacketEncoder {
+    rng: Box<dyn RngCore + Send>,
+}
+
+impl PacketEncoder {
+    pub fn new() -> PacketEncoder {
+        Self::with_rng(StdRng::from_entropy())
+    }
+
+    pub fn seeded(seed: u64) -> PacketEncoder {
+        Self::with_rng(StdRng::seed_from_u64(seed))
+    }
+
+    pub fn with_rng(rng: impl RngCore + Send + 'static) -> PacketEncoder {
+        Self { rng: Box::new(rng) }
+    }
+
+    // Like upstream, none of the key bytes is zero so no part of the packet goes out in clear
+    pub fn xor_key(&mut self) -> [u8; 4] {
+        [(); 4].map(|_| self.rng.gen_range(1..=255))
+    }
+
+    pub fn request_id(&mut self) -> String {
+        Builder::from_random_bytes(self.rng.gen())
+            .into_uuid()
+            .simple()
+            .to_string()
+    }
+
+    // For the handler side, which picks the GUID of each session it accepts
+    pub fn session_guid(&mut self) -> [u8; 16] {
+        self.rng.gen()
+    }
+
+    pub fn iv(&mut self) -> [u8; 16] {
+        self.rng.gen()
+    }
+
+    // Packets created by the agent itself get their request id when they are sent
+    pub fn encode(&mut self, packet: &mut Packet, session_guid: &[u8]) -> Vec<u8> {
+        if !packet.tlvs.contains_key(&TlvType::RequestId) {
+            packet.set_request_id(self.request_id());
+        }
+        packet.to_raw_with_xor_key(session_guid, self.xor_key())
+    }
+}
+
+impl Default for PacketEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    use super::PacketEncoder;
+    use crate::packet::Packet;
+    use crate::tlv::{Add, TlvType};
+
+    fn encode(encoder: &mut PacketEncoder) -> Vec<u8> {
+        let mut packet = Packet::new(String::from("core_channel_write"));
+        packet.add(TlvType::ChannelId, 1);
+        packet.add(TlvType::ChannelData, b"data".to_vec());
+        encoder.encode(&mut packet, &[0; 16])
+    }
+
+    #[test]
+    fn test_seeded_encoders_are_deterministic() {
+        let mut first = PacketEncoder::seeded(7);
+        let mut second = PacketEncoder::seeded(7);
+
+        assert_eq!(encode(&mut first), encode(&mut second));
+        assert_eq!(first.iv(), second.iv());
+        assert_ne!(encode(&mut first), encode(&mut PacketEncoder::seeded(8)));
+    }
+
+    #[test]
+    fn test_encode_adds_missing_request_id() {
+        let mut encoder = PacketEncoder::seeded(1);
+        let raw = encode(&mut encoder);
+
+        let packet = Packet::from_raw(&raw, &mut 0).unwrap();
+        let request_id = packet.get_request_id();
+        assert_eq!(request_id.len(), 32);
+        assert!(request_id.chars().all(|c| c.is_ascii_hexdigit()));
+
+        let mut response = packet.create_response();
+        let raw_response = encoder.encode(&mut response, &[0; 16]);
+        assert_eq!(
+            Packet::from_raw(&raw_response, &mut 0)
+                .unwrap()
+                .get_request_id(),
+            request_id
+        );
+    }
+
+    #[test]
+    fn test_xor_key_has_no_zero_bytes() {
+        let mut encoder = PacketEncoder::seeded(3);
+        for _ in 0..1000 {
+            assert!(!encoder.xor_key().contains(&0));
+        }
+    }
+}
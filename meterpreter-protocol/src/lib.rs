@@ -0,0 +1,24 @@
+// The TLV packet format spoken between the agent and the handler. Builds without std, the
+// std feature adds PacketEncoder, which needs an entropy source for XOR keys and request ids
+#![no_std]
+
+#[macro_use]
+extern crate alloc;
+#[cfg(any(feature = "std", test))]
+extern crate std;
+
+pub mod config;
+pub mod decode;
+#[cfg(feature = "std")]
+pub mod definitions;
+#[cfg(feature = "std")]
+pub mod encoder;
+pub mod packet;
+pub mod tlv;
+
+// Used by the exported macros, extensions don't have to depend on alloc themselves
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::string::String;
+    pub use alloc::vec::Vec;
+}
@@ -0,0 +1,482 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io;
+
+use crate::decode::{DecodeError, DecodeLimits};
+#[cfg(feature = "std")]
+use crate::encoder::PacketEncoder;
+use crate::tlv::{Add, BinaryReader, BinaryWriter, Query, Tlv, TlvList, TlvType};
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+#[repr(u32)]
+pub enum PacketResult {
+    Success = 0,
+    InvalidFunction = 1,
+    FileNotFound = 2,
+    AccessDenied = 5,
+    InvalidData = 13,
+    BrokenPipe = 109,
+    CallNotImplemented = 120,
+    BadArguments = 160,
+    Busy = 170,
+    ErrorAlreadyExists = 183,
+    Timeout = 1460,
+    ConnectionRefused = 10061,
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for PacketResult {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::NotFound => PacketResult::FileNotFound,
+            io::ErrorKind::PermissionDenied => PacketResult::AccessDenied,
+            io::ErrorKind::BrokenPipe => PacketResult::BrokenPipe,
+            io::ErrorKind::ConnectionRefused => PacketResult::ConnectionRefused,
+            io::ErrorKind::InvalidInput => PacketResult::BadArguments,
+            io::ErrorKind::Unsupported => PacketResult::CallNotImplemented,
+            io::ErrorKind::TimedOut => PacketResult::Timeout,
+            _ => PacketResult::InvalidFunction,
+        }
+    }
+}
+
+impl TryFrom<u32> for PacketResult {
+    type Error = DecodeError;
+
+    fn try_from(val: u32) -> Result<Self, DecodeError> {
+        match val {
+            0 => Ok(PacketResult::Success),
+            1 => Ok(PacketResult::InvalidFunction),
+            2 => Ok(PacketResult::FileNotFound),
+            5 => Ok(PacketResult::AccessDenied),
+            13 => Ok(PacketResult::InvalidData),
+            109 => Ok(PacketResult::BrokenPipe),
+            120 => Ok(PacketResult::CallNotImplemented),
+            160 => Ok(PacketResult::BadArguments),
+            170 => Ok(PacketResult::Busy),
+            183 => Ok(PacketResult::ErrorAlreadyExists),
+            1460 => Ok(PacketResult::Timeout),
+            10061 => Ok(PacketResult::ConnectionRefused),
+            _ => Err(DecodeError::UnknownResult(val)),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+#[repr(u32)]
+pub enum PacketType {
+    Request = 0,
+    Response = 1,
+    PlainRequest = 10,
+    PlainResponse = 11,
+}
+
+impl TryFrom<u32> for PacketType {
+    type Error = DecodeError;
+
+    fn try_from(val: u32) -> Result<Self, DecodeError> {
+        match val {
+            0 => Ok(PacketType::Request),
+            1 => Ok(PacketType::Response),
+            10 => Ok(PacketType::PlainRequest),
+            11 => Ok(PacketType::PlainResponse),
+            _ => Err(DecodeError::InvalidPacketType(val)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Packet {
+    pub packet_type: PacketType,
+    pub tlvs: TlvList,
+}
+
+impl Packet {
+    pub const HEADER_SIZE: u32 = 4 + 16 + 4 + 4 + 4; // XOR Key + SESSION GUID + ENCRYPTION FLAG + Packet Body Length + Packet Type
+    const ENC_LENGTH: u32 = 20;
+
+    // The request id is added by PacketEncoder::encode unless one is set before
+    pub fn new(method: String) -> Packet {
+        let mut instance = Self {
+            packet_type: PacketType::Request,
+            tlvs: TlvList::new(),
+        };
+
+        instance.set_method(method);
+
+        instance
+    }
+
+    pub fn from_raw(storage: &[u8], position: &mut usize) -> Result<Packet, DecodeError> {
+        Self::from_raw_with_limits(storage, position, &DecodeLimits::default())
+    }
+
+    // Checks the header against the limits before anything of the body is copied
+    pub fn from_raw_with_limits(
+        storage: &[u8],
+        position: &mut usize,
+        limits: &DecodeLimits,
+    ) -> Result<Packet, DecodeError> {
+        let header_size = Packet::HEADER_SIZE as usize;
+        if storage.len().saturating_sub(*position) < header_size {
+            return Err(DecodeError::Truncated);
+        }
+        let mut header = storage[*position..*position + header_size].to_vec();
+        let mut xor_key = [0; 4];
+        xor_key.copy_from_slice(&header[..4]);
+
+        Packet::xor(&mut header, xor_key);
+
+        // Move to encryption flags
+        let mut header_position = Packet::ENC_LENGTH as usize;
+        let encryption_flag = BinaryReader::read_dword(&header, &mut header_position);
+        let length = BinaryReader::read_dword(&header, &mut header_position);
+        // tlv bytes length + packet type + packet length
+        let tlv_bytes_length = length
+            .checked_sub(8)
+            .ok_or(DecodeError::InvalidPacketLength(length))?
+            as usize;
+        if tlv_bytes_length > limits.max_body_bytes {
+            return Err(DecodeError::BodyTooLarge {
+                length: tlv_bytes_length,
+                max: limits.max_body_bytes,
+            });
+        }
+        let packet_type =
+            PacketType::try_from(BinaryReader::read_dword(&header, &mut header_position))?;
+        //TODO: turn this to an enum when implementing encryption
+        if encryption_flag != 0 {
+            return Err(DecodeError::Encrypted);
+        }
+
+        let body_start = *position + header_size;
+        if storage.len() - body_start < tlv_bytes_length {
+            return Err(DecodeError::Truncated);
+        }
+        let mut packet_body = storage[body_start..body_start + tlv_bytes_length].to_vec();
+        *position = body_start + tlv_bytes_length;
+
+        Packet::xor(&mut packet_body, xor_key);
+
+        let mut packet = Packet {
+            packet_type,
+            tlvs: TlvList::new(),
+        };
+        let mut body_position = 0;
+        let mut count = 0;
+        while body_position < packet_body.len() {
+            packet.add_tlv(Tlv::decode(
+                &packet_body,
+                &mut body_position,
+                packet_body.len(),
+                limits,
+                0,
+                &mut count,
+            )?)
+        }
+
+        Ok(packet)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn to_raw(&self, session_guid: &[u8]) -> Vec<u8> {
+        self.to_raw_with_xor_key(session_guid, PacketEncoder::new().xor_key())
+    }
+
+    // to_raw with a given XOR key instead of a random one, encoding is deterministic then.
+    // The whole packet is written into one buffer sized for it up front
+    pub fn to_raw_with_xor_key(&self, session_guid: &[u8], xor_key: [u8; 4]) -> Vec<u8> {
+        let tlv_length: usize = self.tlvs.iter().map(Tlv::raw_len).sum();
+        let mut packet_data: Vec<u8> =
+            Vec::with_capacity(Packet::HEADER_SIZE as usize - 16 + session_guid.len() + tlv_length);
+        BinaryWriter::write_dword(&mut packet_data, 0); //XOR key, will be filled later
+
+        BinaryWriter::write_bytes(&mut packet_data, session_guid);
+        //TODO: replace encryption flag by an enum
+        BinaryWriter::write_dword(&mut packet_data, 0); // Encryption flag - 0 -> None
+        BinaryWriter::write_dword(&mut packet_data, tlv_length as u32 + 8); // tlv Length + packetType + packetLength field
+        BinaryWriter::write_packet_type(&mut packet_data, self.packet_type);
+        //TODO: encrypt the tlvs
+        for tlv in self.tlvs.iter() {
+            tlv.to_raw(&mut packet_data);
+        }
+
+        Packet::xor(&mut packet_data, xor_key);
+
+        packet_data
+    }
+
+    pub fn get_request_id(&self) -> String {
+        String::from(self.get_str(TlvType::RequestId).unwrap_or_default())
+    }
+
+    pub fn set_request_id(&mut self, request_id: String) {
+        self.tlvs.remove(&TlvType::RequestId);
+        self.add(TlvType::RequestId, request_id);
+    }
+
+    pub fn get_method(&self) -> String {
+        String::from(self.get_str(TlvType::Method).unwrap_or_default())
+    }
+
+    fn set_method(&mut self, method: String) {
+        self.tlvs.remove(&TlvType::Method);
+        self.add(TlvType::Method, method);
+    }
+
+    // A packet without a result counts as a success, the other side decides on the codes so
+    // ones this side doesn't know are an error
+    pub fn get_result(&self) -> Result<PacketResult, DecodeError> {
+        PacketResult::try_from(self.get_u32(TlvType::Result).unwrap_or_default())
+    }
+
+    pub fn set_result(&mut self, packet_result: PacketResult) {
+        self.tlvs.remove(&TlvType::Result);
+        self.add(TlvType::Result, packet_result as u32);
+    }
+
+    pub fn create_response(&self) -> Packet {
+        let packet_type = if self.packet_type == PacketType::Request {
+            PacketType::Response
+        } else {
+            PacketType::PlainResponse
+        };
+        let mut response = Self {
+            packet_type,
+            tlvs: TlvList::new(),
+        };
+
+        if self.tlvs.contains_key(&TlvType::RequestId) {
+            response.set_request_id(self.get_request_id());
+        }
+        response.set_method(self.get_method());
+
+        response
+    }
+
+    fn xor(target: &mut [u8], xor_key: [u8; 4]) {
+        for i in 0..target.len() {
+            target[i] ^= xor_key[i % xor_key.len()];
+        }
+    }
+}
+
+impl Add for Packet {
+    fn add_tlv(&mut self, tlv: Tlv) {
+        self.tlvs.push(tlv);
+    }
+}
+
+impl Query for Packet {
+    fn tlv_list(&self) -> &TlvList {
+        &self.tlvs
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::string::String;
+    use alloc::vec::Vec;
+    use std::ffi::OsStr;
+    use std::fs;
+    use std::path::Path;
+
+    use serde_json::{json, Value};
+
+    use crate::{
+        decode::DecodeError,
+        packet::{Packet, PacketResult, PacketType},
+        tlv::{test_extension, TlvList, TlvType, TlvValue},
+    };
+
+    use super::{Add, Query};
+
+    // hex digits of a fixture, everything after a # is a comment
+    fn parse_hex(text: &str) -> Vec<u8> {
+        let digits: String = text
+            .lines()
+            .flat_map(|line| line.split('#').next().unwrap().split_whitespace())
+            .collect();
+        (0..digits.len())
+            .step_by(2)
+            .map(|index| u8::from_str_radix(&digits[index..index + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn to_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    fn tlvs_to_json(tlvs: &TlvList) -> Value {
+        tlvs.iter()
+            .map(|tlv| {
+                let name = tlv.tlv_type.name().unwrap();
+                match &tlv.value {
+                    None => json!({ "type": name, "tlvs": tlvs_to_json(&tlv.tlvs) }),
+                    Some(TlvValue::Bool(value)) => json!({ "type": name, "value": value }),
+                    Some(TlvValue::UInt(value)) => json!({ "type": name, "value": value }),
+                    Some(TlvValue::ULongInt(value)) => json!({ "type": name, "value": value }),
+                    Some(TlvValue::String(value)) => json!({ "type": name, "value": value }),
+                    Some(TlvValue::Bytes(value)) => json!({ "type": name, "value": to_hex(value) }),
+                }
+            })
+            .collect()
+    }
+
+    // Every fixtures/packets/*.hex has a .json next to it with the decoded packet and the
+    // XOR key and session guid it was encoded with. Its header says where the bytes came
+    // from, fixtures/packets/generate.rb encodes them with Rex
+    #[test]
+    fn test_golden_packets() {
+        test_extension::register();
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/packets");
+        let mut fixtures = 0;
+        for entry in fs::read_dir(directory).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension() != Some(OsStr::new("hex")) {
+                continue;
+            }
+            let hex = fs::read_to_string(&path).unwrap();
+            assert!(
+                hex.lines().any(|line| line.starts_with("# Source: ")),
+                "{:?} doesn't say where it came from",
+                path
+            );
+            let raw = parse_hex(&hex);
+            let expected: Value =
+                serde_json::from_str(&fs::read_to_string(path.with_extension("json")).unwrap())
+                    .unwrap();
+
+            let mut position = 0;
+            let packet = Packet::from_raw(&raw, &mut position).unwrap();
+            assert_eq!(position, raw.len(), "{:?} not decoded to the end", path);
+            assert_eq!(
+                expected["packet_type"],
+                format!("{:?}", packet.packet_type),
+                "{:?}",
+                path
+            );
+            assert_eq!(tlvs_to_json(&packet.tlvs), expected["tlvs"], "{:?}", path);
+
+            let xor_key = parse_hex(expected["xor_key"].as_str().unwrap());
+            let session_guid = parse_hex(expected["session_guid"].as_str().unwrap());
+            assert_eq!(
+                packet.to_raw_with_xor_key(&session_guid, xor_key.try_into().unwrap()),
+                raw,
+                "{:?} not encoded to the same bytes",
+                path
+            );
+            fixtures += 1;
+        }
+        assert!(fixtures > 0);
+    }
+
+    #[test]
+    fn test_empty_packet() {
+        let packet = Packet::new(String::from("core_channel_close"));
+        assert!(packet.tlvs.contains_key(&TlvType::Method));
+        assert!(!packet.tlvs.contains_key(&TlvType::RequestId));
+        assert_eq!(packet.get_method(), "core_channel_close");
+    }
+
+    #[test]
+    fn test_add() {
+        let mut packet = Packet::new(String::from("core_channel_open"));
+        packet.add(TlvType::ChannelType, String::from("unidirectional"));
+        packet.add(TlvType::ChannelId, 2);
+        packet.add(TlvType::ChannelData, vec![3, 5, 8, 9]);
+
+        assert_eq!(
+            packet.get_str(TlvType::ChannelType).unwrap(),
+            "unidirectional"
+        );
+        assert_eq!(packet.get_u32(TlvType::ChannelId).unwrap(), 2);
+        assert_eq!(
+            packet.get_bytes(TlvType::ChannelData).unwrap(),
+            [3, 5, 8, 9]
+        );
+    }
+
+    #[test]
+    fn test_create_response() {
+        let mut request_packet = Packet::new(String::from("core_channel_open"));
+        request_packet.set_request_id(String::from("38617410372826521432451458357478"));
+        let response_packet = request_packet.create_response();
+
+        assert_eq!(
+            response_packet.get_request_id(),
+            request_packet.get_request_id()
+        );
+        assert_eq!(response_packet.get_method(), request_packet.get_method());
+        assert_eq!(response_packet.packet_type, PacketType::Response);
+    }
+
+    #[test]
+    fn test_result() {
+        let mut packet = Packet::new(String::from("core_channel_open"));
+        assert_eq!(packet.get_result(), Ok(PacketResult::Success));
+        packet.set_result(PacketResult::Timeout);
+        assert_eq!(packet.get_result(), Ok(PacketResult::Timeout));
+
+        // codes come from the other side, unknown ones are kept as they are
+        packet.tlvs.remove(&TlvType::Result);
+        packet.add(TlvType::Result, 12345u32);
+        assert_eq!(packet.get_result(), Err(DecodeError::UnknownResult(12345)));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_packet_to_raw() {
+        let request_packet = Packet::new(String::from("core_channel_open"));
+        let mut response_packet = request_packet.create_response();
+        response_packet.add(test_extension::StdapiProxyCfgAutodetect, true);
+        response_packet.add(TlvType::ChannelId, 2);
+        response_packet.add(test_extension::StdapiMountSpaceFree, 65535);
+        response_packet.add(TlvType::ChannelType, String::from("duplex"));
+
+        let session_guid = [0; 16];
+        let mut raw_data = response_packet.to_raw(&session_guid);
+
+        //xor decrypt
+        let xor_key = raw_data[0..4].try_into().unwrap();
+        Packet::xor(&mut raw_data, xor_key);
+
+        assert_eq!(raw_data[0..4], [0; 4]); // xor_key xored with itself
+        assert_eq!(raw_data[4..20], [0; 16]); // session guid
+        assert_eq!(raw_data[20..24], [0; 4]); // encryption flag
+        assert_eq!(raw_data[28..32], [0, 0, 0, 1]) // packet type - response = 1
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_from_raw_to_packet() {
+        let request_packet = Packet::new(String::from("core_channel_open"));
+        let mut response_packet = request_packet.create_response();
+        response_packet.add(test_extension::StdapiProxyCfgAutodetect, true);
+        response_packet.add(TlvType::ChannelId, 2);
+        response_packet.add(test_extension::StdapiMountSpaceFree, 65535);
+        response_packet.add(TlvType::ChannelType, String::from("duplex"));
+
+        let session_guid = [0; 16];
+        let raw_data = response_packet.to_raw(&session_guid);
+
+        let mut position = 0;
+        let packet = Packet::from_raw(&raw_data, &mut position).unwrap();
+
+        assert_eq!(packet.packet_type, PacketType::Response);
+        assert_eq!(
+            packet.get_bool(test_extension::StdapiProxyCfgAutodetect),
+            Some(true)
+        );
+
+        assert_eq!(packet.get_u32(TlvType::ChannelId), Some(2));
+
+        assert_eq!(
+            packet.get_u64(test_extension::StdapiMountSpaceFree),
+            Some(65535)
+        );
+
+        assert_eq!(packet.get_str(TlvType::ChannelType), Some("duplex"));
+    }
+}
@@ -0,0 +1,13 @@
+use crate::tlv::{Group, Tlv, TlvData, TlvKey};
+
+pub trait Add {
+    fn add<T: TlvData>(&mut self, key: TlvKey<T>, value: T) {
+        self.add_tlv(Tlv::typed(key, value));
+    }
+
+    fn add_group(&mut self, key: TlvKey<Group>) {
+        self.add_tlv(Tlv::group(key));
+    }
+
+    fn add_tlv(&mut self, tlv: Tlv);
+}
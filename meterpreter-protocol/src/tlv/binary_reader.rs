@@ -1,11 +1,12 @@
-use crate::protocol::packet::PacketType;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 
 use super::TlvType;
 
 pub struct BinaryReader;
 
 impl BinaryReader {
-    pub fn read_bool(storage: &Vec<u8>, position: &mut usize) -> bool {
+    pub fn read_bool(storage: &[u8], position: &mut usize) -> bool {
         let mut val = false;
         if storage[*position] == 1 {
             val = true;
@@ -15,45 +16,42 @@ impl BinaryReader {
         val
     }
 
-    pub fn read_dword(storage: &Vec<u8>, position: &mut usize) -> u32 {
+    pub fn read_dword(storage: &[u8], position: &mut usize) -> u32 {
         let bytes = storage[*position..*position + 4].try_into().unwrap();
         *position += 4;
         u32::from_be_bytes(bytes)
     }
 
-    pub fn read_qword(storage: &Vec<u8>, position: &mut usize) -> u64 {
+    pub fn read_qword(storage: &[u8], position: &mut usize) -> u64 {
         let bytes = storage[*position..*position + 8].try_into().unwrap();
         *position += 8;
         u64::from_be_bytes(bytes)
     }
 
-    pub fn read_string(storage: &Vec<u8>, position: &mut usize, length: u32) -> String {
-        let data = std::str::from_utf8(&storage[*position..*position + (length as usize - 1)])
+    pub fn read_string(storage: &[u8], position: &mut usize, length: u32) -> String {
+        let data = core::str::from_utf8(&storage[*position..*position + (length as usize - 1)])
             .unwrap()
             .to_string();
         *position += length as usize;
         data
     }
 
-    pub fn read_bytes(storage: &Vec<u8>, position: &mut usize, length: u32) -> Vec<u8> {
+    pub fn read_bytes(storage: &[u8], position: &mut usize, length: u32) -> Vec<u8> {
         let bytes = storage[*position..*position + length as usize].to_vec();
         *position += length as usize;
         bytes
     }
 
-    pub fn read_tlv_type(storage: &Vec<u8>, position: &mut usize) -> TlvType {
+    pub fn read_tlv_type(storage: &[u8], position: &mut usize) -> TlvType {
         let tlv_type = BinaryReader::read_dword(storage, position);
         TlvType::from(tlv_type)
     }
-
-    pub fn read_packet_type(storage: &Vec<u8>, position: &mut usize) -> PacketType {
-        let packet_type = BinaryReader::read_dword(storage, position);
-        PacketType::from(packet_type)
-    }
 }
 
 #[cfg(test)]
 mod test {
+    use alloc::vec::Vec;
+
     use super::BinaryReader;
 
     #[test]
@@ -61,7 +59,7 @@ mod test {
         let storage: Vec<u8> = vec![1];
         let mut position = 0;
         let data = BinaryReader::read_bool(&storage, &mut position);
-        assert_eq!(data, true);
+        assert!(data);
     }
 
     #[test]
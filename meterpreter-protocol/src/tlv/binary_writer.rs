@@ -1,4 +1,6 @@
-use crate::protocol::packet::PacketType;
+use alloc::vec::Vec;
+
+use crate::packet::PacketType;
 
 use super::TlvType;
 
@@ -17,7 +19,7 @@ impl BinaryWriter {
         storage.extend(data.to_be_bytes());
     }
 
-    pub fn write_string(storage: &mut Vec<u8>, data: String) {
+    pub fn write_string(storage: &mut Vec<u8>, data: &str) {
         storage.extend(data.as_bytes());
         storage.push(0); // terminating null charachter
     }
@@ -31,12 +33,19 @@ impl BinaryWriter {
     }
 
     pub fn write_tlv_type(storage: &mut Vec<u8>, tlv_type: TlvType) {
-        BinaryWriter::write_dword(storage, tlv_type as u32);
+        BinaryWriter::write_dword(storage, tlv_type.into());
+    }
+
+    // Fills in a length written as a placeholder once what it covers is known
+    pub fn patch_dword(storage: &mut [u8], offset: usize, data: u32) {
+        storage[offset..offset + 4].copy_from_slice(&data.to_be_bytes());
     }
 }
 
 #[cfg(test)]
 mod test {
+    use alloc::vec::Vec;
+
     use super::BinaryWriter;
 
     #[test]
@@ -66,11 +75,18 @@ mod test {
     #[test]
     fn test_write_string() {
         let mut storage: Vec<u8> = vec![];
-        BinaryWriter::write_string(&mut storage, String::from("hello"));
+        BinaryWriter::write_string(&mut storage, "hello");
         assert_eq!(storage.len(), 6);
         assert!(storage == [104, 101, 108, 108, 111, 0]);
     }
 
+    #[test]
+    fn test_patch_dword() {
+        let mut storage: Vec<u8> = vec![7, 0, 0, 0, 0, 7];
+        BinaryWriter::patch_dword(&mut storage, 1, 258);
+        assert!(storage == [7, 0, 0, 1, 2, 7]);
+    }
+
     #[test]
     fn test_write_bytes() {
         let mut storage: Vec<u8> = vec![];
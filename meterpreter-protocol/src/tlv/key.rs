@@ -0,0 +1,224 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::Deref;
+
+use super::{MetaType, TlvType, TlvValue};
+
+// Types a TlvKey can be typed with, each one belongs to the meta types listed with it
+pub trait KeyType: 'static {
+    const META_TYPES: &'static [MetaType];
+}
+
+// Rust types TLV values are stored as
+pub trait TlvData: KeyType + Sized {
+    fn into_value(self) -> TlvValue;
+
+    fn from_value(value: &TlvValue) -> Option<&Self>;
+}
+
+impl KeyType for String {
+    const META_TYPES: &'static [MetaType] = &[MetaType::String];
+}
+
+impl TlvData for String {
+    fn into_value(self) -> TlvValue {
+        TlvValue::String(self)
+    }
+
+    fn from_value(value: &TlvValue) -> Option<&Self> {
+        match value {
+            TlvValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl KeyType for u32 {
+    const META_TYPES: &'static [MetaType] = &[MetaType::Uint];
+}
+
+impl TlvData for u32 {
+    fn into_value(self) -> TlvValue {
+        TlvValue::UInt(self)
+    }
+
+    fn from_value(value: &TlvValue) -> Option<&Self> {
+        match value {
+            TlvValue::UInt(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl KeyType for u64 {
+    const META_TYPES: &'static [MetaType] = &[MetaType::Qword];
+}
+
+impl TlvData for u64 {
+    fn into_value(self) -> TlvValue {
+        TlvValue::ULongInt(self)
+    }
+
+    fn from_value(value: &TlvValue) -> Option<&Self> {
+        match value {
+            TlvValue::ULongInt(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl KeyType for bool {
+    const META_TYPES: &'static [MetaType] = &[MetaType::Bool];
+}
+
+impl TlvData for bool {
+    fn into_value(self) -> TlvValue {
+        TlvValue::Bool(self)
+    }
+
+    fn from_value(value: &TlvValue) -> Option<&Self> {
+        match value {
+            TlvValue::Bool(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl KeyType for Vec<u8> {
+    const META_TYPES: &'static [MetaType] = &[MetaType::Raw, MetaType::Complex];
+}
+
+impl TlvData for Vec<u8> {
+    fn into_value(self) -> TlvValue {
+        TlvValue::Bytes(self)
+    }
+
+    fn from_value(value: &TlvValue) -> Option<&Self> {
+        match value {
+            TlvValue::Bytes(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+// Type parameter of the keys for group TLVs, they hold other TLVs instead of a value
+pub struct Group;
+
+impl KeyType for Group {
+    const META_TYPES: &'static [MetaType] = &[MetaType::Group];
+}
+
+// The key of a type without a value of its own
+impl KeyType for () {
+    const META_TYPES: &'static [MetaType] = &[MetaType::None, MetaType::Compressed];
+}
+
+/// A TlvType that knows the Rust type of its value. The generated constants are keys, so
+/// adding a value of the wrong type to a TLV doesn't compile:
+///
+/// ```compile_fail
+/// use meterpreter_protocol::packet::Packet;
+/// use meterpreter_protocol::tlv::{Add, TlvType};
+///
+/// let mut packet = Packet::new(String::from("core_channel_close"));
+/// packet.add(TlvType::ChannelId, String::from("x"));
+/// ```
+///
+/// Neither does a key whose meta type doesn't fit its value type:
+///
+/// ```compile_fail
+/// use meterpreter_protocol::tlv::{MetaType, TlvKey};
+///
+/// const WRONG: TlvKey<u32> = TlvKey::new(MetaType::String, 1000);
+/// ```
+pub struct TlvKey<T> {
+    tlv_type: TlvType,
+    value_type: PhantomData<fn() -> T>,
+}
+
+impl<T: KeyType> TlvKey<T> {
+    // Checked when the constant is evaluated, a mismatch doesn't build
+    pub const fn new(meta_type: MetaType, number: u32) -> TlvKey<T> {
+        let mut index = 0;
+        while index < T::META_TYPES.len() && T::META_TYPES[index] as u32 != meta_type as u32 {
+            index += 1;
+        }
+        assert!(
+            index < T::META_TYPES.len(),
+            "the meta type doesn't fit the value type of the key"
+        );
+        Self {
+            tlv_type: TlvType::new(meta_type, number),
+            value_type: PhantomData,
+        }
+    }
+}
+
+impl<T> TlvKey<T> {
+    pub const fn tlv_type(&self) -> TlvType {
+        self.tlv_type
+    }
+}
+
+impl<T> Clone for TlvKey<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for TlvKey<T> {}
+
+impl<T> Deref for TlvKey<T> {
+    type Target = TlvType;
+
+    fn deref(&self) -> &TlvType {
+        &self.tlv_type
+    }
+}
+
+impl<T> From<TlvKey<T>> for TlvType {
+    fn from(key: TlvKey<T>) -> Self {
+        key.tlv_type
+    }
+}
+
+impl<T> PartialEq for TlvKey<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.tlv_type == other.tlv_type
+    }
+}
+
+impl<T> PartialEq<TlvType> for TlvKey<T> {
+    fn eq(&self, other: &TlvType) -> bool {
+        self.tlv_type == *other
+    }
+}
+
+impl<T> PartialEq<TlvKey<T>> for TlvType {
+    fn eq(&self, other: &TlvKey<T>) -> bool {
+        *self == other.tlv_type
+    }
+}
+
+impl<T> fmt::Debug for TlvKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.tlv_type.fmt(f)
+    }
+}
+
+// Value type of the keys generated for each meta type
+#[doc(hidden)]
+#[macro_export]
+macro_rules! tlv_value_type {
+    (String) => { $crate::__private::String };
+    (Uint) => { u32 };
+    (Qword) => { u64 };
+    (Bool) => { bool };
+    (Raw) => { $crate::__private::Vec<u8> };
+    (Complex) => { $crate::__private::Vec<u8> };
+    (Group) => { $crate::tlv::Group };
+    (None) => { () };
+    (Compressed) => { () };
+}
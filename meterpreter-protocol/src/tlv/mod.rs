@@ -0,0 +1,920 @@
+use alloc::borrow::ToOwned;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::hash::Hash;
+
+use crate::decode::{DecodeError, DecodeLimits};
+
+mod add;
+mod binary_reader;
+mod binary_writer;
+mod key;
+mod query;
+#[cfg(any(feature = "std", test))]
+mod registry;
+#[cfg(test)]
+pub(crate) mod test_extension;
+mod tlv_list;
+
+pub use add::Add;
+pub use key::{Group, KeyType, TlvData, TlvKey};
+pub use query::Query;
+#[cfg(any(feature = "std", test))]
+pub use registry::register_tlv_types;
+pub use tlv_list::TlvList;
+
+pub use self::binary_reader::BinaryReader;
+pub use self::binary_writer::BinaryWriter;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[repr(u32)]
+pub enum MetaType {
+    None = 0,
+    String = (1 << 16),
+    Uint = (1 << 17),
+    Raw = (1 << 18),
+    Bool = (1 << 19),
+    Qword = (1 << 20),
+    Compressed = (1 << 29),
+    Group = (1 << 30),
+    Complex = (1 << 31),
+    All = MetaType::None as u32
+        | MetaType::String as u32
+        | MetaType::Uint as u32
+        | MetaType::Raw as u32
+        | MetaType::Bool as u32
+        | MetaType::Qword as u32
+        | MetaType::Compressed as u32
+        | MetaType::Group as u32
+        | MetaType::Complex as u32,
+}
+
+const META_TYPES: [MetaType; 9] = [
+    MetaType::None,
+    MetaType::String,
+    MetaType::Uint,
+    MetaType::Raw,
+    MetaType::Bool,
+    MetaType::Qword,
+    MetaType::Compressed,
+    MetaType::Group,
+    MetaType::Complex,
+];
+
+// Declares the core TLV types as associated constants of TlvType together with a TLV_TYPES
+// table. The constants are TlvKeys typed after the meta type
+macro_rules! tlv_types {
+    ($($name:ident = $meta_type:ident | $number:expr,)*) => {
+        #[allow(non_upper_case_globals)]
+        impl $crate::tlv::TlvType {
+            $(pub const $name: $crate::tlv::TlvKey<$crate::tlv_value_type!($meta_type)> =
+                $crate::tlv::TlvKey::new($crate::tlv::MetaType::$meta_type, $number);)*
+        }
+
+        pub const TLV_TYPES: &[($crate::tlv::TlvType, &str)] = &[
+            $(($crate::tlv::TlvType::$name.tlv_type(), stringify!($name)),)*
+        ];
+    };
+}
+
+// Declares the TLV types of an extension as TlvKey constants of the module it is used in,
+// together with the TLV_TYPES table the extension registers its names with once it's loaded
+#[macro_export]
+macro_rules! tlv_keys {
+    ($($name:ident = $meta_type:ident | $number:expr,)*) => {
+        $(#[allow(non_upper_case_globals)]
+        pub const $name: $crate::tlv::TlvKey<$crate::tlv_value_type!($meta_type)> =
+            $crate::tlv::TlvKey::new($crate::tlv::MetaType::$meta_type, $number);)*
+
+        pub const TLV_TYPES: &[($crate::tlv::TlvType, &str)] = &[
+            $(($name.tlv_type(), stringify!($name)),)*
+        ];
+    };
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+pub struct TlvType(u32);
+
+// Keys, passwords and channel contents, their values are kept out of the logs
+const SENSITIVE_TLV_TYPES: &[TlvType] = &[
+    TlvType::SymKey.tlv_type(),
+    TlvType::EncSymKey.tlv_type(),
+    TlvType::TransProxyPass.tlv_type(),
+    TlvType::RsaPubKey.tlv_type(),
+    TlvType::ChannelData.tlv_type(),
+];
+
+include!(concat!(env!("OUT_DIR"), "/core_tlv_types.rs"));
+include!(concat!(env!("OUT_DIR"), "/tlv_names.rs"));
+
+impl TlvType {
+    pub const fn new(meta_type: MetaType, number: u32) -> TlvType {
+        TlvType(meta_type as u32 | number)
+    }
+
+    // The type number without the meta type bits, this is what extension ranges refer to
+    pub fn number(&self) -> u32 {
+        self.0 & !(MetaType::All as u32)
+    }
+
+    // Name of the constant generated for this type. Extension types only have one once the
+    // extension registered them
+    pub fn name(&self) -> Option<&'static str> {
+        let name = KNOWN_TLV_TYPES
+            .binary_search_by_key(&self.0, |(value, _)| *value)
+            .ok()
+            .map(|index| KNOWN_TLV_TYPES[index].1);
+        #[cfg(any(feature = "std", test))]
+        let name = name.or_else(|| registry::name(*self));
+        name
+    }
+
+    pub fn from_name(name: &str) -> Option<TlvType> {
+        let tlv_type = KNOWN_TLV_TYPES
+            .iter()
+            .find(|(_, known)| *known == name)
+            .map(|(value, _)| TlvType(*value));
+        #[cfg(any(feature = "std", test))]
+        let tlv_type = tlv_type.or_else(|| registry::from_name(name));
+        tlv_type
+    }
+
+    pub fn is_sensitive(&self) -> bool {
+        SENSITIVE_TLV_TYPES.contains(self)
+    }
+
+    // All when more than one meta bit is set, which none of the defined types does
+    fn to_meta_type(self) -> MetaType {
+        let val = MetaType::All as u32 & self.0;
+        META_TYPES
+            .into_iter()
+            .find(|meta_type| *meta_type as u32 == val)
+            .unwrap_or(MetaType::All)
+    }
+}
+
+impl fmt::Debug for TlvType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "TlvType({:#010x})", self.0),
+        }
+    }
+}
+
+impl From<u32> for TlvType {
+    fn from(val: u32) -> Self {
+        TlvType(val)
+    }
+}
+
+impl From<TlvType> for u32 {
+    fn from(tlv_type: TlvType) -> Self {
+        tlv_type.0
+    }
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum TlvValue {
+    Bool(bool),
+    UInt(u32),
+    ULongInt(u64),
+    String(String),
+    Bytes(Vec<u8>),
+}
+
+impl TlvValue {
+    fn fits(&self, meta_type: MetaType) -> bool {
+        matches!(
+            (meta_type, self),
+            (MetaType::String, TlvValue::String(_))
+                | (MetaType::Uint, TlvValue::UInt(_))
+                | (MetaType::Qword, TlvValue::ULongInt(_))
+                | (MetaType::Bool, TlvValue::Bool(_))
+                | (MetaType::Raw | MetaType::Complex, TlvValue::Bytes(_))
+        )
+    }
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum TlvError {
+    #[error("{tlv_type:?} has meta type {meta_type:?}, it can't hold {value:?}")]
+    MetaTypeMismatch {
+        tlv_type: TlvType,
+        meta_type: MetaType,
+        value: TlvValue,
+    },
+}
+
+#[derive(Debug)]
+pub struct Tlv {
+    pub value: Option<TlvValue>,
+    pub tlv_type: TlvType,
+    pub tlvs: TlvList,
+}
+
+impl Tlv {
+    // For types only known at runtime, the value has to match the meta type
+    pub fn new(tlv_type: impl Into<TlvType>, value: TlvValue) -> Result<Tlv, TlvError> {
+        let tlv_type = tlv_type.into();
+        let meta_type = tlv_type.to_meta_type();
+        if !value.fits(meta_type) {
+            return Err(TlvError::MetaTypeMismatch {
+                tlv_type,
+                meta_type,
+                value,
+            });
+        }
+
+        Ok(Self {
+            tlv_type,
+            value: Some(value),
+            tlvs: TlvList::new(),
+        })
+    }
+
+    pub fn typed<T: TlvData>(key: TlvKey<T>, value: T) -> Tlv {
+        Self {
+            tlv_type: key.tlv_type(),
+            value: Some(value.into_value()),
+            tlvs: TlvList::new(),
+        }
+    }
+
+    pub fn group(key: TlvKey<Group>) -> Tlv {
+        Self {
+            tlv_type: key.tlv_type(),
+            value: None,
+            tlvs: TlvList::new(),
+        }
+    }
+
+    // None for groups and when the value is not a T
+    pub fn value_as<T: TlvData>(&self) -> Option<&T> {
+        self.value.as_ref().and_then(T::from_value)
+    }
+
+    pub fn from_raw(storage: &[u8], position: &mut usize) -> Result<Tlv, DecodeError> {
+        Self::from_raw_with_limits(storage, position, &DecodeLimits::default())
+    }
+
+    pub fn from_raw_with_limits(
+        storage: &[u8],
+        position: &mut usize,
+        limits: &DecodeLimits,
+    ) -> Result<Tlv, DecodeError> {
+        Self::decode(storage, position, storage.len(), limits, 0, &mut 0)
+    }
+
+    // Decodes the TLV at position, which has to end before `end`. `depth` is the number of
+    // groups around it and `count` the number of TLVs decoded so far in the packet
+    pub(crate) fn decode(
+        storage: &[u8],
+        position: &mut usize,
+        end: usize,
+        limits: &DecodeLimits,
+        depth: usize,
+        count: &mut usize,
+    ) -> Result<Tlv, DecodeError> {
+        *count += 1;
+        if *count > limits.max_tlvs {
+            return Err(DecodeError::TooManyTlvs(limits.max_tlvs));
+        }
+        if end.saturating_sub(*position) < 8 {
+            return Err(DecodeError::Truncated);
+        }
+        let length = BinaryReader::read_dword(storage, position);
+        let tlv_type = BinaryReader::read_tlv_type(storage, position);
+        let invalid_length = DecodeError::InvalidLength { tlv_type, length };
+        let value_length = length.checked_sub(8).ok_or(invalid_length.clone())? as usize;
+        if end - *position < value_length {
+            return Err(DecodeError::Truncated);
+        }
+        let value_end = *position + value_length;
+        let mut tlv = Self {
+            tlv_type,
+            value: None,
+            tlvs: TlvList::new(),
+        };
+
+        let minimum_length = match tlv_type.to_meta_type() {
+            MetaType::Bool => 1,
+            MetaType::Uint => 4,
+            MetaType::Qword => 8,
+            // the terminating NUL
+            MetaType::String => 1,
+            _ => 0,
+        };
+        if value_length < minimum_length {
+            return Err(invalid_length);
+        }
+
+        match tlv_type.to_meta_type() {
+            MetaType::Group => {
+                if depth >= limits.max_group_depth {
+                    return Err(DecodeError::GroupTooDeep(limits.max_group_depth));
+                }
+                while *position < value_end {
+                    let child =
+                        Self::decode(storage, position, value_end, limits, depth + 1, count)?;
+                    tlv.add_tlv(child);
+                }
+            }
+            MetaType::Bool => {
+                tlv.value = Some(TlvValue::Bool(BinaryReader::read_bool(storage, position)));
+            }
+            MetaType::Uint => {
+                tlv.value = Some(TlvValue::UInt(BinaryReader::read_dword(storage, position)));
+            }
+            MetaType::Qword => {
+                tlv.value = Some(TlvValue::ULongInt(BinaryReader::read_qword(
+                    storage, position,
+                )));
+            }
+            MetaType::String => {
+                if value_length - 1 > limits.max_string_length {
+                    return Err(DecodeError::StringTooLong {
+                        tlv_type,
+                        length: value_length - 1,
+                        max: limits.max_string_length,
+                    });
+                }
+                if core::str::from_utf8(&storage[*position..value_end - 1]).is_err() {
+                    return Err(DecodeError::InvalidString(tlv_type));
+                }
+                tlv.value = Some(TlvValue::String(BinaryReader::read_string(
+                    storage,
+                    position,
+                    value_length as u32,
+                )));
+            }
+            MetaType::Raw | MetaType::Complex => {
+                tlv.value = Some(TlvValue::Bytes(BinaryReader::read_bytes(
+                    storage,
+                    position,
+                    value_length as u32,
+                )))
+            }
+            _ => return Err(DecodeError::UnsupportedMetaType(tlv_type)),
+        }
+
+        // values longer than their meta type needs are padded, the next TLV starts after it
+        *position = value_end;
+        Ok(tlv)
+    }
+
+    // Size of the TLV once encoded, header included
+    pub fn raw_len(&self) -> usize {
+        let value_length = match self.tlv_type.to_meta_type() {
+            MetaType::Group => self.tlvs.iter().map(Tlv::raw_len).sum(),
+            MetaType::Bool => 1,
+            MetaType::Uint => 4,
+            MetaType::Qword => 8,
+            MetaType::String => self.value_as_str().len() + 1,
+            MetaType::Raw | MetaType::Complex => self.value_as_bytes().len(),
+            meta_type => panic!("Unexpected MetaType {:?}", meta_type),
+        };
+        value_length + 8
+    }
+
+    // Appends the encoded TLV. A group's length is patched in after its TLVs were written
+    // straight into the same storage
+    pub fn to_raw(&self, storage: &mut Vec<u8>) {
+        let meta_type = self.tlv_type.to_meta_type();
+        if meta_type == MetaType::Group {
+            let start = storage.len();
+            BinaryWriter::write_dword(storage, 0); // Length, known once the TLVs are in
+            BinaryWriter::write_tlv_type(storage, self.tlv_type);
+            for tlv in self.tlvs.iter() {
+                tlv.to_raw(storage);
+            }
+            let length = storage.len() - start;
+            BinaryWriter::patch_dword(storage, start, length as u32);
+        } else {
+            match meta_type {
+                MetaType::Bool => {
+                    BinaryWriter::write_dword(storage, 1 + 8); // Length
+                    BinaryWriter::write_tlv_type(storage, self.tlv_type); // Type
+                    BinaryWriter::write_bool(storage, self.value_as_bool()); //Value
+                }
+                MetaType::Uint => {
+                    BinaryWriter::write_dword(storage, 4 + 8);
+                    BinaryWriter::write_tlv_type(storage, self.tlv_type);
+                    BinaryWriter::write_dword(storage, self.value_as_uint32());
+                }
+                MetaType::Qword => {
+                    BinaryWriter::write_dword(storage, 8 + 8);
+                    BinaryWriter::write_tlv_type(storage, self.tlv_type);
+                    BinaryWriter::write_qword(storage, self.value_as_uint64());
+                }
+                MetaType::String => {
+                    let value = self.value_as_str();
+                    BinaryWriter::write_dword(storage, value.len() as u32 + 1 + 8);
+                    BinaryWriter::write_tlv_type(storage, self.tlv_type);
+                    BinaryWriter::write_string(storage, value);
+                }
+                MetaType::Raw | MetaType::Complex => {
+                    let value = self.value_as_bytes();
+                    BinaryWriter::write_dword(storage, value.len() as u32 + 8);
+                    BinaryWriter::write_tlv_type(storage, self.tlv_type);
+                    BinaryWriter::write_bytes(storage, value);
+                }
+                MetaType::None | MetaType::Compressed => {
+                    panic!("Compressed or None MetaType Not Supported")
+                }
+                _ => panic!("Unexpected MetaType {:?}", meta_type),
+            }
+        }
+    }
+
+    fn validate_meta_type(&self, expected_types: Vec<MetaType>) {
+        if !expected_types.contains(&self.tlv_type.to_meta_type()) {
+            panic!(
+                "Expecting MetaType {:?} but provided type {:?}",
+                expected_types, &self.tlv_type
+            );
+        }
+    }
+
+    pub fn value_as_string(&self) -> String {
+        self.value_as_str().to_string()
+    }
+
+    pub fn value_as_str(&self) -> &str {
+        match self
+            .value
+            .as_ref()
+            .expect("Unable to extract value from a TLV")
+        {
+            TlvValue::String(val) => val,
+            _ => panic!("Didn't find expected type"),
+        }
+    }
+
+    pub fn value_as_bool(&self) -> bool {
+        match self
+            .value
+            .as_ref()
+            .expect("Unable to extract value from a TLV")
+        {
+            TlvValue::Bool(val) => val.to_owned(),
+            _ => panic!("Didn't find expected type"),
+        }
+    }
+
+    pub fn value_as_uint32(&self) -> u32 {
+        match self
+            .value
+            .as_ref()
+            .expect("Unable to extract value from a TLV")
+        {
+            TlvValue::UInt(val) => val.to_owned(),
+            _ => panic!("Didn't find expected type"),
+        }
+    }
+
+    pub fn value_as_uint64(&self) -> u64 {
+        match self
+            .value
+            .as_ref()
+            .expect("Unable to extract value from a TLV")
+        {
+            TlvValue::ULongInt(val) => val.to_owned(),
+            _ => panic!("Didn't find expected type"),
+        }
+    }
+
+    pub fn value_as_bytes(&self) -> &Vec<u8> {
+        match self
+            .value
+            .as_ref()
+            .expect("Unable to extract value from a TLV")
+        {
+            TlvValue::Bytes(val) => val,
+            _ => panic!("Didn't find expected type"),
+        }
+    }
+}
+impl Add for Tlv {
+    fn add_tlv(&mut self, tlv: Tlv) {
+        self.validate_meta_type(vec![MetaType::Group]);
+        self.tlvs.push(tlv);
+    }
+}
+
+//TODO: better grouping of tests
+#[cfg(test)]
+mod test {
+    use alloc::borrow::ToOwned;
+    use alloc::string::{String, ToString};
+    use alloc::vec::Vec;
+
+    use crate::tlv::{test_extension, MetaType, Tlv, TlvError, TlvType, TlvValue};
+
+    use super::{Add, KNOWN_TLV_TYPES, TLV_TYPES};
+
+    #[test]
+    fn test_generated_meta_bits() {
+        let meta_types = [
+            MetaType::None as u32,
+            MetaType::String as u32,
+            MetaType::Uint as u32,
+            MetaType::Raw as u32,
+            MetaType::Bool as u32,
+            MetaType::Qword as u32,
+            MetaType::Group as u32,
+            MetaType::Complex as u32,
+        ];
+        for (value, name) in KNOWN_TLV_TYPES {
+            let tlv_type = TlvType::from(*value);
+            assert!(
+                meta_types.contains(&(value & MetaType::All as u32)),
+                "{} doesn't have exactly one meta type",
+                name
+            );
+            assert!(
+                tlv_type.number() <= 0xffff,
+                "{} has a type number overlapping the meta bits",
+                name
+            );
+        }
+        // sorted without duplicates, the lookup relies on it
+        assert!(KNOWN_TLV_TYPES.windows(2).all(|pair| pair[0].0 < pair[1].0));
+
+        for (tlv_type, name) in TLV_TYPES {
+            assert_eq!(tlv_type.name(), Some(*name));
+        }
+    }
+
+    #[test]
+    fn test_upstream_meta_types() {
+        assert_eq!(TlvType::MigratePayload.to_meta_type(), MetaType::Raw);
+        assert_eq!(TlvType::MigrateSocketPath.to_meta_type(), MetaType::String);
+        assert_eq!(TlvType::MigrateStub.to_meta_type(), MetaType::Raw);
+        assert_eq!(TlvType::RsaPubKey.to_meta_type(), MetaType::Raw);
+    }
+
+    #[test]
+    fn test_tlvtype_debug() {
+        test_extension::register();
+        assert_eq!(format!("{:?}", TlvType::ChannelId), "ChannelId");
+        assert_eq!(
+            format!("{:?}", test_extension::StdapiProcessId),
+            "StdapiProcessId"
+        );
+        assert_eq!(
+            format!("{:?}", TlvType::new(MetaType::Uint, 19999)),
+            "TlvType(0x00024e1f)"
+        );
+    }
+
+    #[test]
+    fn test_register_tlv_types() {
+        // a type no other test registers
+        let tlv_type = TlvType::new(MetaType::Uint, 19998);
+        assert_eq!(tlv_type.name(), None);
+        assert_eq!(TlvType::from_name("TestRegistered"), None);
+
+        super::register_tlv_types(&[(tlv_type, "TestRegistered")]);
+        assert_eq!(tlv_type.name(), Some("TestRegistered"));
+        assert_eq!(TlvType::from_name("TestRegistered"), Some(tlv_type));
+
+        super::register_tlv_types(&[(tlv_type, "TestRenamed")]);
+        assert_eq!(tlv_type.name(), Some("TestRegistered"));
+        assert_eq!(TlvType::from_name("TestRenamed"), None);
+    }
+
+    #[test]
+    fn test_tlvtype_to_metatype() {
+        assert_eq!(TlvType::Any.to_meta_type(), MetaType::None);
+        assert_eq!(TlvType::Method.to_meta_type(), MetaType::String);
+        assert_eq!(
+            test_extension::StdapiMountSpaceTotal.to_meta_type(),
+            MetaType::Qword
+        );
+    }
+
+    #[test]
+    fn test_value_as_string() {
+        let tlv = Tlv::new(
+            TlvType::ChannelType,
+            TlvValue::String(String::from("OneWay")),
+        )
+        .unwrap();
+        assert_eq!(tlv.value_as_string(), "OneWay");
+    }
+
+    #[test]
+    fn test_value_as_uint32() {
+        let tlv = Tlv::new(TlvType::ChannelId, TlvValue::UInt(2)).unwrap();
+        assert_eq!(tlv.value_as_uint32(), 2);
+    }
+
+    #[test]
+    fn test_value_as_uint64() {
+        let tlv = Tlv::new(
+            test_extension::StdapiMountSpaceFree,
+            TlvValue::ULongInt(624636823236762),
+        )
+        .unwrap();
+        assert_eq!(tlv.value_as_uint64(), 624636823236762);
+    }
+
+    #[test]
+    fn test_value_as_bool() {
+        let tlv = Tlv::new(
+            test_extension::StdapiProxyCfgAutodetect,
+            TlvValue::Bool(true),
+        )
+        .unwrap();
+        assert!(tlv.value_as_bool());
+    }
+
+    #[test]
+    fn test_value_as_bytes() {
+        let tlv = Tlv::new(TlvType::ChannelData, TlvValue::Bytes(vec![35, 67, 0, 255])).unwrap();
+        let byte_val = tlv.value_as_bytes();
+        assert_eq!(byte_val[0], 35);
+        assert_eq!(byte_val[1], 67);
+        assert_eq!(byte_val[2], 0);
+        assert_eq!(byte_val[3], 255);
+    }
+
+    #[test]
+    fn test_add() {
+        let mut tlv = Tlv::group(test_extension::StdapiMount);
+        tlv.add(test_extension::StdapiMountName, String::from("/sdf"));
+        tlv.add(test_extension::StdapiMountType, 2);
+        tlv.add(test_extension::StdapiMountSpaceFree, 2614672732);
+
+        assert_eq!(
+            tlv.tlvs
+                .get(&test_extension::StdapiMountName)
+                .unwrap()
+                .first()
+                .unwrap()
+                .value_as_string(),
+            "/sdf"
+        );
+        assert_eq!(
+            tlv.tlvs
+                .get(&test_extension::StdapiMountType)
+                .unwrap()
+                .first()
+                .unwrap()
+                .value_as_uint32(),
+            2
+        );
+        assert_eq!(
+            tlv.tlvs
+                .get(&test_extension::StdapiMountSpaceFree)
+                .unwrap()
+                .first()
+                .unwrap()
+                .value_as_uint64(),
+            2614672732
+        );
+    }
+
+    #[test]
+    fn test_bool_tlv_to_raw() {
+        let tlv = Tlv::new(
+            test_extension::StdapiProxyCfgAutodetect,
+            TlvValue::Bool(true),
+        )
+        .unwrap();
+        let mut storage: Vec<u8> = vec![];
+        tlv.to_raw(&mut storage);
+        assert_eq!(storage.len(), 9);
+        assert!(storage == [0, 0, 0, 9, /**/ 0, 8, 5, 165, /**/ 1]);
+    }
+
+    #[test]
+    fn test_uint_tlv_to_raw() {
+        let tlv = Tlv::new(TlvType::ChannelId, TlvValue::UInt(2)).unwrap();
+        let mut storage: Vec<u8> = vec![];
+        tlv.to_raw(&mut storage);
+        assert_eq!(storage.len(), 12);
+        assert!(storage == [0, 0, 0, 12, /**/ 0, 2, 0, 50, /**/ 0, 0, 0, 2]);
+    }
+
+    #[test]
+    fn test_qword_tlv_to_raw() {
+        let tlv = Tlv::new(
+            test_extension::StdapiMountSpaceFree,
+            TlvValue::ULongInt(65535),
+        )
+        .unwrap();
+        let mut storage: Vec<u8> = vec![];
+        tlv.to_raw(&mut storage);
+        assert_eq!(storage.len(), 16);
+        assert!(storage == [0, 0, 0, 16, /**/ 0, 16, 4, 188, /**/ 0, 0, 0, 0, 0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn test_string_tlv_to_raw() {
+        let tlv = Tlv::new(TlvType::ChannelType, TlvValue::String("duplex".to_owned())).unwrap();
+        let mut storage: Vec<u8> = vec![];
+        tlv.to_raw(&mut storage);
+        assert_eq!(storage.len(), 15);
+        assert!(storage == [0, 0, 0, 15, /**/ 0, 1, 0, 51, /**/ 100, 117, 112, 108, 101, 120, 0]);
+    }
+
+    #[test]
+    fn test_bytes_tlv_to_raw() {
+        let tlv = Tlv::new(
+            TlvType::TransCertHash,
+            TlvValue::Bytes(vec![89, 77, 22, 23, 45]),
+        )
+        .unwrap();
+        let mut storage: Vec<u8> = vec![];
+        tlv.to_raw(&mut storage);
+        assert_eq!(storage.len(), 13);
+        assert!(storage == [0, 0, 0, 13, /**/ 0, 4, 1, 179, /**/ 89, 77, 22, 23, 45]);
+    }
+
+    #[test]
+    fn test_typed_values() {
+        let mut group = Tlv::group(test_extension::StdapiNetworkInterface);
+        group.add(test_extension::StdapiMacName, String::from("eth0"));
+        group.add(test_extension::StdapiInterfaceMtu, 1500);
+        group.add(test_extension::StdapiIp, vec![10, 0, 0, 1]);
+
+        assert_eq!(
+            group.tlvs.get_value(test_extension::StdapiMacName),
+            Some(&String::from("eth0"))
+        );
+        assert_eq!(
+            group.tlvs.get_value(test_extension::StdapiInterfaceMtu),
+            Some(&1500)
+        );
+        assert_eq!(
+            group.tlvs.get_value(test_extension::StdapiIp),
+            Some(&vec![10, 0, 0, 1])
+        );
+        assert_eq!(group.tlvs.get_value(test_extension::StdapiNetmask), None);
+        assert_eq!(group.value_as::<u32>(), None);
+    }
+
+    #[test]
+    fn test_new_checks_meta_type() {
+        assert_eq!(
+            Tlv::new(TlvType::Any, TlvValue::Bool(false)).unwrap_err(),
+            TlvError::MetaTypeMismatch {
+                tlv_type: TlvType::Any.into(),
+                meta_type: MetaType::None,
+                value: TlvValue::Bool(false)
+            }
+        );
+        assert!(Tlv::new(TlvType::ChannelId, TlvValue::String(String::from("1"))).is_err());
+        assert!(Tlv::new(test_extension::StdapiMountSpaceFree, TlvValue::UInt(1)).is_err());
+        assert!(Tlv::new(TlvType::RsaPubKey, TlvValue::Bytes(vec![1])).is_ok());
+
+        // types only known at runtime get the same check
+        let tlv_type = TlvType::from(MetaType::Bool as u32 | 42);
+        assert!(Tlv::new(tlv_type, TlvValue::Bool(true)).is_ok());
+        assert!(Tlv::new(tlv_type, TlvValue::UInt(1)).is_err());
+    }
+
+    #[test]
+    fn test_from_raw_to_bool_tlv() {
+        let tlv = Tlv::new(
+            test_extension::StdapiProxyCfgAutodetect,
+            TlvValue::Bool(true),
+        )
+        .unwrap();
+        let mut storage: Vec<u8> = vec![];
+        tlv.to_raw(&mut storage);
+
+        let mut position = 0;
+        let tlv = Tlv::from_raw(&storage, &mut position).unwrap();
+
+        assert_eq!(tlv.tlv_type, test_extension::StdapiProxyCfgAutodetect);
+        assert_eq!(tlv.value.unwrap(), TlvValue::Bool(true));
+    }
+
+    #[test]
+    fn test_from_raw_to_uint_tlv() {
+        let tlv = Tlv::new(TlvType::ChannelId, TlvValue::UInt(2)).unwrap();
+        let mut storage: Vec<u8> = vec![];
+        tlv.to_raw(&mut storage);
+
+        let mut position = 0;
+        let tlv = Tlv::from_raw(&storage, &mut position).unwrap();
+
+        assert_eq!(tlv.tlv_type, TlvType::ChannelId);
+        assert_eq!(tlv.value.unwrap(), TlvValue::UInt(2));
+    }
+
+    #[test]
+    fn test_from_raw_to_qword_tlv() {
+        let tlv = Tlv::new(
+            test_extension::StdapiMountSpaceFree,
+            TlvValue::ULongInt(65535),
+        )
+        .unwrap();
+        let mut storage: Vec<u8> = vec![];
+        tlv.to_raw(&mut storage);
+
+        let mut position = 0;
+        let tlv = Tlv::from_raw(&storage, &mut position).unwrap();
+
+        assert_eq!(tlv.tlv_type, test_extension::StdapiMountSpaceFree);
+        assert_eq!(tlv.value.unwrap(), TlvValue::ULongInt(65535));
+    }
+
+    #[test]
+    fn test_from_raw_to_string_tlv() {
+        let tlv = Tlv::new(TlvType::ChannelType, TlvValue::String("duplex".to_owned())).unwrap();
+        let mut storage: Vec<u8> = vec![];
+        tlv.to_raw(&mut storage);
+
+        let mut position = 0;
+        let tlv = Tlv::from_raw(&storage, &mut position).unwrap();
+
+        assert_eq!(tlv.tlv_type, TlvType::ChannelType);
+        assert_eq!(tlv.value.unwrap(), TlvValue::String("duplex".to_owned()));
+    }
+
+    #[test]
+    fn test_from_raw_to_bytes_tlv() {
+        let tlv = Tlv::new(
+            TlvType::TransCertHash,
+            TlvValue::Bytes(vec![89, 77, 22, 23, 45]),
+        )
+        .unwrap();
+        let mut storage: Vec<u8> = vec![];
+        tlv.to_raw(&mut storage);
+
+        let mut position = 0;
+        let tlv = Tlv::from_raw(&storage, &mut position).unwrap();
+
+        assert_eq!(tlv.tlv_type, TlvType::TransCertHash);
+        assert_eq!(
+            tlv.value.unwrap(),
+            TlvValue::Bytes(vec![89, 77, 22, 23, 45])
+        );
+    }
+
+    #[test]
+    fn test_from_raw_to_group_tlv() {
+        let mut tlv = Tlv::group(TlvType::TransGroup);
+        tlv.add(TlvType::TransType, 3);
+        tlv.add(TlvType::TransUrl, "https://ch.rs".to_string());
+        tlv.add(TlvType::UUID, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        tlv.add(test_extension::StdapiMountSpaceFree, 65548);
+
+        let mut storage: Vec<u8> = vec![];
+        tlv.to_raw(&mut storage);
+
+        let mut position = 0;
+        let tlv = Tlv::from_raw(&storage, &mut position).unwrap();
+
+        assert_eq!(tlv.tlv_type, TlvType::TransGroup);
+        assert_eq!(
+            tlv.tlvs
+                .get(&TlvType::TransType)
+                .unwrap()
+                .first()
+                .unwrap()
+                .value
+                .as_ref()
+                .unwrap(),
+            &TlvValue::UInt(3)
+        );
+        assert_eq!(
+            tlv.tlvs
+                .get(&TlvType::TransUrl)
+                .unwrap()
+                .first()
+                .unwrap()
+                .value
+                .as_ref()
+                .unwrap(),
+            &TlvValue::String("https://ch.rs".to_string())
+        );
+        assert_eq!(
+            tlv.tlvs
+                .get(&TlvType::UUID)
+                .unwrap()
+                .first()
+                .unwrap()
+                .value
+                .as_ref()
+                .unwrap(),
+            &TlvValue::Bytes(vec![1, 2, 3, 4, 5, 6, 7, 8])
+        );
+        assert_eq!(
+            tlv.tlvs
+                .get(&test_extension::StdapiMountSpaceFree)
+                .unwrap()
+                .first()
+                .unwrap()
+                .value
+                .as_ref()
+                .unwrap(),
+            &TlvValue::ULongInt(65548)
+        );
+    }
+}
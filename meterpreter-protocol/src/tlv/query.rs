@@ -0,0 +1,147 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::tlv::{Group, Tlv, TlvData, TlvKey, TlvList, TlvType};
+
+// Read access shared by packets and group TLVs. Lookups return None or nothing rather than
+// panicking when a TLV is missing or holds a different kind of value
+pub trait Query {
+    fn tlv_list(&self) -> &TlvList;
+
+    // Value of the first TLV of this type
+    fn get<T: TlvData>(&self, key: TlvKey<T>) -> Option<&T> {
+        self.tlv_list().get_value(key)
+    }
+
+    fn get_str(&self, key: TlvKey<String>) -> Option<&str> {
+        self.get(key).map(|value| value.as_str())
+    }
+
+    fn get_u32(&self, key: TlvKey<u32>) -> Option<u32> {
+        self.get(key).copied()
+    }
+
+    fn get_u64(&self, key: TlvKey<u64>) -> Option<u64> {
+        self.get(key).copied()
+    }
+
+    fn get_bool(&self, key: TlvKey<bool>) -> Option<bool> {
+        self.get(key).copied()
+    }
+
+    fn get_bytes(&self, key: TlvKey<Vec<u8>>) -> Option<&[u8]> {
+        self.get(key).map(|value| value.as_slice())
+    }
+
+    // Values of every TLV of this type, in the order they were added
+    fn get_all<T: TlvData>(&self, key: TlvKey<T>) -> impl Iterator<Item = &T> {
+        self.tlv_list()
+            .get(&key)
+            .into_iter()
+            .flatten()
+            .filter_map(|tlv| tlv.value_as())
+    }
+
+    fn group(&self, key: TlvKey<Group>) -> Option<&Tlv> {
+        self.groups(key).next()
+    }
+
+    fn groups(&self, key: TlvKey<Group>) -> impl Iterator<Item = &Tlv> {
+        self.tlv_list().get(&key).into_iter().flatten()
+    }
+
+    // TLVs at a path of type names separated by slashes, like "StdapiNetworkInterface/StdapiIp".
+    // Every segment but the last goes through all the groups of that type, a name that
+    // isn't a known type matches nothing
+    fn query(&self, path: &str) -> Vec<&Tlv> {
+        let mut found: Vec<&Tlv> = Vec::new();
+        let mut lists = vec![self.tlv_list()];
+        for segment in path.split('/') {
+            let tlv_type = match TlvType::from_name(segment) {
+                Some(tlv_type) => tlv_type,
+                None => return Vec::new(),
+            };
+            found = lists
+                .iter()
+                .filter_map(|list| list.get(&tlv_type))
+                .flatten()
+                .collect();
+            lists = found.iter().map(|tlv| &tlv.tlvs).collect();
+        }
+        found
+    }
+}
+
+impl Query for Tlv {
+    fn tlv_list(&self) -> &TlvList {
+        &self.tlvs
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    use super::Query;
+    use crate::tlv::{test_extension, Add, Tlv, TlvType};
+
+    fn interfaces() -> Tlv {
+        let mut response = Tlv::group(test_extension::StdapiNetworkInterface);
+        for (name, ips) in [("lo", [[127, 0, 0, 1]]), ("eth0", [[10, 0, 0, 2]])] {
+            let mut interface = Tlv::group(test_extension::StdapiNetworkInterface);
+            interface.add(test_extension::StdapiMacName, String::from(name));
+            for ip in ips {
+                interface.add(test_extension::StdapiIp, ip.to_vec());
+            }
+            response.add_tlv(interface);
+        }
+        response.add(TlvType::ChannelId, 4);
+        response.add(TlvType::String, String::from("first"));
+        response.add(TlvType::String, String::from("second"));
+        response
+    }
+
+    #[test]
+    fn test_get() {
+        let tlv = interfaces();
+        assert_eq!(tlv.get_u32(TlvType::ChannelId), Some(4));
+        assert_eq!(tlv.get_str(TlvType::String), Some("first"));
+        assert_eq!(tlv.get_u32(TlvType::Length), None);
+        assert_eq!(tlv.get_bytes(TlvType::ChannelData), None);
+    }
+
+    #[test]
+    fn test_get_all() {
+        let tlv = interfaces();
+        let strings: Vec<&String> = tlv.get_all(TlvType::String).collect();
+        assert_eq!(strings, ["first", "second"]);
+        assert_eq!(tlv.get_all(test_extension::StdapiIp).count(), 0);
+    }
+
+    #[test]
+    fn test_groups() {
+        let tlv = interfaces();
+        let names: Vec<&str> = tlv
+            .groups(test_extension::StdapiNetworkInterface)
+            .filter_map(|interface| interface.get_str(test_extension::StdapiMacName))
+            .collect();
+        assert_eq!(names, ["lo", "eth0"]);
+        assert!(tlv.group(test_extension::StdapiNetworkRoute).is_none());
+    }
+
+    #[test]
+    fn test_query() {
+        test_extension::register();
+        let tlv = interfaces();
+        let ips: Vec<&Vec<u8>> = tlv
+            .query("StdapiNetworkInterface/StdapiIp")
+            .into_iter()
+            .filter_map(|ip| ip.value_as())
+            .collect();
+        assert_eq!(ips, [&vec![127, 0, 0, 1], &vec![10, 0, 0, 2]]);
+        assert_eq!(tlv.query("ChannelId").len(), 1);
+        assert!(tlv.query("StdapiNetworkInterface/NotAType").is_empty());
+        assert!(tlv.query("StdapiIp").is_empty());
+    }
+}
@@ -0,0 +1,37 @@
+// Names of the TLV types extensions define. The protocol only knows the core types, the
+// rest are registered by the extensions when they're loaded
+use std::sync::RwLock;
+use std::vec::Vec;
+
+use super::TlvType;
+
+static EXTENSION_TLV_TYPES: RwLock<Vec<(TlvType, &'static str)>> = RwLock::new(Vec::new());
+
+// Registering the same types again, like every time a dispatcher loads the extension, keeps
+// the names they got first
+pub fn register_tlv_types(tlv_types: &[(TlvType, &'static str)]) {
+    let mut registered = EXTENSION_TLV_TYPES.write().unwrap();
+    for (tlv_type, name) in tlv_types {
+        if !registered.iter().any(|(known, _)| known == tlv_type) {
+            registered.push((*tlv_type, name));
+        }
+    }
+}
+
+pub(super) fn name(tlv_type: TlvType) -> Option<&'static str> {
+    EXTENSION_TLV_TYPES
+        .read()
+        .unwrap()
+        .iter()
+        .find(|(known, _)| *known == tlv_type)
+        .map(|(_, name)| *name)
+}
+
+pub(super) fn from_name(name: &str) -> Option<TlvType> {
+    EXTENSION_TLV_TYPES
+        .read()
+        .unwrap()
+        .iter()
+        .find(|(_, known)| *known == name)
+        .map(|(tlv_type, _)| *tlv_type)
+}
@@ -0,0 +1,29 @@
+// Some of the stdapi TLV types, declared the way an extension declares its own, for the tests
+// of the codec
+crate::tlv_keys! {
+    StdapiProcessHandle = Qword | 630,
+    StdapiFileName = String | 1201,
+    StdapiFilePath = String | 1202,
+    StdapiFileMode = String | 1203,
+    StdapiMount = Group | 1207,
+    StdapiMountName = String | 1208,
+    StdapiMountType = Uint | 1209,
+    StdapiMountSpaceTotal = Qword | 1211,
+    StdapiMountSpaceFree = Qword | 1212,
+    StdapiInterfaceMtu = Uint | 1402,
+    StdapiInterfaceFlags = String | 1403,
+    StdapiInterfaceIndex = Uint | 1404,
+    StdapiNetmask = Raw | 1421,
+    StdapiNetworkRoute = Group | 1423,
+    StdapiIp = Raw | 1430,
+    StdapiMacAddr = Raw | 1431,
+    StdapiMacName = String | 1432,
+    StdapiNetworkInterface = Group | 1433,
+    StdapiProxyCfgAutodetect = Bool | 1445,
+    StdapiProcessId = Uint | 2300,
+}
+
+// The names are only known once registered, as they are for a loaded extension
+pub fn register() {
+    super::register_tlv_types(TLV_TYPES);
+}
@@ -0,0 +1,93 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::ops::Deref;
+
+use super::{Tlv, TlvData, TlvKey, TlvType};
+
+// TLVs looked up by type that keep the order they were added in, so a decoded packet
+// encodes back to the same bytes even when types are interleaved
+#[derive(Debug, Default)]
+pub struct TlvList {
+    by_type: BTreeMap<TlvType, Vec<Tlv>>,
+    // type and index among the TLVs of that type, iterating doesn't need to allocate then
+    order: Vec<(TlvType, usize)>,
+}
+
+impl TlvList {
+    pub fn new() -> TlvList {
+        Self::default()
+    }
+
+    pub fn push(&mut self, tlv: Tlv) {
+        let same_type = self.by_type.entry(tlv.tlv_type).or_default();
+        self.order.push((tlv.tlv_type, same_type.len()));
+        same_type.push(tlv);
+    }
+
+    // Take &TlvType rather than going through the map's generic lookups, so a &TlvKey works
+    // as well
+    pub fn get(&self, tlv_type: &TlvType) -> Option<&Vec<Tlv>> {
+        self.by_type.get(tlv_type)
+    }
+
+    pub fn contains_key(&self, tlv_type: &TlvType) -> bool {
+        self.by_type.contains_key(tlv_type)
+    }
+
+    // Value of the first TLV of this type
+    pub fn get_value<T: TlvData>(&self, key: TlvKey<T>) -> Option<&T> {
+        self.get(&key)?.first()?.value_as()
+    }
+
+    pub fn remove(&mut self, tlv_type: &TlvType) -> Option<Vec<Tlv>> {
+        self.order.retain(|(added, _)| added != tlv_type);
+        self.by_type.remove(tlv_type)
+    }
+
+    // All TLVs in the order they were added
+    pub fn iter(&self) -> impl Iterator<Item = &Tlv> {
+        self.order
+            .iter()
+            .map(|(tlv_type, index)| &self.by_type[tlv_type][*index])
+    }
+}
+
+impl Deref for TlvList {
+    type Target = BTreeMap<TlvType, Vec<Tlv>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.by_type
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::vec::Vec;
+
+    use super::TlvList;
+    use crate::tlv::{test_extension, Tlv, TlvType, TlvValue};
+
+    #[test]
+    fn test_iter_keeps_interleaved_order() {
+        let mut list = TlvList::new();
+        list.push(Tlv::new(test_extension::StdapiIp, TlvValue::Bytes(vec![1])).unwrap());
+        list.push(Tlv::new(test_extension::StdapiNetmask, TlvValue::Bytes(vec![2])).unwrap());
+        list.push(Tlv::new(test_extension::StdapiIp, TlvValue::Bytes(vec![3])).unwrap());
+
+        let values: Vec<&Vec<u8>> = list.iter().map(|tlv| tlv.value_as_bytes()).collect();
+        assert_eq!(values, [&vec![1], &vec![2], &vec![3]]);
+        assert_eq!(list.get(&test_extension::StdapiIp).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut list = TlvList::new();
+        list.push(Tlv::new(TlvType::ChannelId, TlvValue::UInt(1)).unwrap());
+        list.push(Tlv::new(TlvType::Result, TlvValue::UInt(0)).unwrap());
+        list.push(Tlv::new(TlvType::ChannelId, TlvValue::UInt(2)).unwrap());
+
+        assert_eq!(list.remove(&TlvType::ChannelId).unwrap().len(), 2);
+        assert_eq!(list.iter().count(), 1);
+        assert!(!list.contains_key(&TlvType::ChannelId));
+    }
+}
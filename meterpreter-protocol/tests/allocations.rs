@@ -0,0 +1,59 @@
+// Counts the allocations encoding a packet takes, the whole packet is meant to be written
+// into a single buffer sized up front
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+#[path = "../benches/shapes/mod.rs"]
+mod shapes;
+
+struct CountingAllocator;
+
+thread_local! {
+    // per thread, so tests running at the same time don't count each other's allocations
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    (result, ALLOCATIONS.with(Cell::get) - before)
+}
+
+#[test]
+fn test_encoding_allocates_once() {
+    for (name, packet) in shapes::all() {
+        let (raw, count) = allocations(|| packet.to_raw_with_xor_key(&[0; 16], [1, 2, 3, 4]));
+        assert_eq!(count, 1, "{} took {} allocations", name, count);
+        // the single buffer was big enough for all of it
+        assert_eq!(raw.len(), raw.capacity(), "{}", name);
+    }
+}
+
+#[test]
+fn test_group_encoding_doesnt_reallocate() {
+    let packet = shapes::deep_groups();
+    let tlv = packet.tlvs.iter().last().unwrap();
+    let mut storage = Vec::with_capacity(tlv.raw_len());
+    let (_, count) = allocations(|| tlv.to_raw(&mut storage));
+    assert_eq!(count, 0);
+    assert_eq!(storage.len(), tlv.raw_len());
+}
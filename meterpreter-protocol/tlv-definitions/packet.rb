@@ -0,0 +1,116 @@
+# -*- coding: binary -*-
+#
+# TLV constants from metasploit-framework lib/rex/post/meterpreter/packet.rb
+# (string method protocol, before command ids were introduced).
+#
+# Only the constant definitions are kept, build.rs turns the TLV_TYPE_* lines
+# into TlvType constants. Keep the upstream names and layout when updating.
+#
+module Rex
+module Post
+module Meterpreter
+
+#
+# Constants
+#
+PACKET_TYPE_REQUEST         = 0
+PACKET_TYPE_RESPONSE        = 1
+PACKET_TYPE_PLAIN_REQUEST   = 10
+PACKET_TYPE_PLAIN_RESPONSE  = 11
+
+#
+# TLV Meta Types
+#
+TLV_META_TYPE_NONE          = 0
+TLV_META_TYPE_STRING        = (1 << 16)
+TLV_META_TYPE_UINT          = (1 << 17)
+TLV_META_TYPE_RAW           = (1 << 18)
+TLV_META_TYPE_BOOL          = (1 << 19)
+TLV_META_TYPE_QWORD         = (1 << 20)
+TLV_META_TYPE_COMPRESSED    = (1 << 29)
+TLV_META_TYPE_GROUP         = (1 << 30)
+TLV_META_TYPE_COMPLEX       = (1 << 31)
+
+#
+# TLV base starting points
+#
+TLV_RESERVED                = 0
+TLV_EXTENSIONS              = 20000
+TLV_USER                    = 40000
+TLV_TEMP                    = 60000
+
+#
+# TLV Specific Types
+#
+TLV_TYPE_ANY                 = TLV_META_TYPE_NONE   |   0
+TLV_TYPE_METHOD              = TLV_META_TYPE_STRING |   1
+TLV_TYPE_REQUEST_ID          = TLV_META_TYPE_STRING |   2
+TLV_TYPE_EXCEPTION           = TLV_META_TYPE_GROUP  |   3
+TLV_TYPE_RESULT              = TLV_META_TYPE_UINT   |   4
+
+TLV_TYPE_STRING              = TLV_META_TYPE_STRING |  10
+TLV_TYPE_UINT                = TLV_META_TYPE_UINT   |  11
+TLV_TYPE_BOOL                = TLV_META_TYPE_BOOL   |  12
+
+TLV_TYPE_LENGTH              = TLV_META_TYPE_UINT   |  25
+TLV_TYPE_DATA                = TLV_META_TYPE_RAW    |  26
+TLV_TYPE_FLAGS               = TLV_META_TYPE_UINT   |  27
+
+TLV_TYPE_CHANNEL_ID          = TLV_META_TYPE_UINT   |  50
+TLV_TYPE_CHANNEL_TYPE        = TLV_META_TYPE_STRING |  51
+TLV_TYPE_CHANNEL_DATA        = TLV_META_TYPE_RAW    |  52
+TLV_TYPE_CHANNEL_DATA_GROUP  = TLV_META_TYPE_GROUP  |  53
+TLV_TYPE_CHANNEL_CLASS       = TLV_META_TYPE_UINT   |  54
+TLV_TYPE_CHANNEL_PARENTID    = TLV_META_TYPE_UINT   |  55
+
+TLV_TYPE_SEEK_WHENCE         = TLV_META_TYPE_UINT   |  70
+TLV_TYPE_SEEK_OFFSET         = TLV_META_TYPE_UINT   |  71
+TLV_TYPE_SEEK_POS            = TLV_META_TYPE_UINT   |  72
+
+TLV_TYPE_EXCEPTION_CODE      = TLV_META_TYPE_UINT   | 300
+TLV_TYPE_EXCEPTION_STRING    = TLV_META_TYPE_STRING | 301
+
+TLV_TYPE_LIBRARY_PATH        = TLV_META_TYPE_STRING | 400
+TLV_TYPE_TARGET_PATH         = TLV_META_TYPE_STRING | 401
+TLV_TYPE_MIGRATE_PID         = TLV_META_TYPE_UINT   | 402
+TLV_TYPE_MIGRATE_PAYLOAD_LEN = TLV_META_TYPE_UINT   | 403
+TLV_TYPE_MIGRATE_PAYLOAD     = TLV_META_TYPE_RAW    | 404
+TLV_TYPE_MIGRATE_ARCH        = TLV_META_TYPE_UINT   | 405
+TLV_TYPE_MIGRATE_BASE_ADDR   = TLV_META_TYPE_UINT   | 407
+TLV_TYPE_MIGRATE_ENTRY_POINT = TLV_META_TYPE_UINT   | 408
+TLV_TYPE_MIGRATE_SOCKET_PATH = TLV_META_TYPE_STRING | 409
+TLV_TYPE_MIGRATE_STUB_LEN    = TLV_META_TYPE_UINT   | 410
+TLV_TYPE_MIGRATE_STUB        = TLV_META_TYPE_RAW    | 411
+
+TLV_TYPE_TRANS_TYPE          = TLV_META_TYPE_UINT   | 430
+TLV_TYPE_TRANS_URL           = TLV_META_TYPE_STRING | 431
+TLV_TYPE_TRANS_UA            = TLV_META_TYPE_STRING | 432
+TLV_TYPE_TRANS_COMM_TIMEOUT  = TLV_META_TYPE_UINT   | 433
+TLV_TYPE_TRANS_SESSION_EXP   = TLV_META_TYPE_UINT   | 434
+TLV_TYPE_TRANS_CERT_HASH     = TLV_META_TYPE_RAW    | 435
+TLV_TYPE_TRANS_PROXY_HOST    = TLV_META_TYPE_STRING | 436
+TLV_TYPE_TRANS_PROXY_USER    = TLV_META_TYPE_STRING | 437
+TLV_TYPE_TRANS_PROXY_PASS    = TLV_META_TYPE_STRING | 438
+TLV_TYPE_TRANS_RETRY_TOTAL   = TLV_META_TYPE_UINT   | 439
+TLV_TYPE_TRANS_RETRY_WAIT    = TLV_META_TYPE_UINT   | 440
+TLV_TYPE_TRANS_HEADERS       = TLV_META_TYPE_STRING | 441
+TLV_TYPE_TRANS_GROUP         = TLV_META_TYPE_GROUP  | 442
+
+TLV_TYPE_MACHINE_ID          = TLV_META_TYPE_STRING | 460
+TLV_TYPE_UUID                = TLV_META_TYPE_RAW    | 461
+TLV_TYPE_SESSION_GUID        = TLV_META_TYPE_RAW    | 462
+
+TLV_TYPE_RSA_PUB_KEY         = TLV_META_TYPE_RAW    | 550
+TLV_TYPE_SYM_KEY_TYPE        = TLV_META_TYPE_UINT   | 551
+TLV_TYPE_SYM_KEY             = TLV_META_TYPE_RAW    | 552
+TLV_TYPE_ENC_SYM_KEY         = TLV_META_TYPE_RAW    | 553
+
+#
+# Pivots
+#
+TLV_TYPE_PIVOT_ID              = TLV_META_TYPE_RAW    |  650
+TLV_TYPE_PIVOT_STAGE_DATA      = TLV_META_TYPE_RAW    |  651
+TLV_TYPE_PIVOT_STAGE_DATA_SIZE = TLV_META_TYPE_UINT   |  652
+TLV_TYPE_PIVOT_NAMED_PIPE_NAME = TLV_META_TYPE_STRING |  653
+
+end; end; end
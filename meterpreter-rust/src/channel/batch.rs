@@ -0,0 +1,219 @@
+use std::time::Instant;
+
+use meterpreter_protocol::packet::{Packet, PacketType};
+use meterpreter_protocol::tlv::{Add, Query, Tlv, TlvType};
+
+use super::{write_request, ChannelLimits, ChannelManager};
+
+// Collects the channel data the agent sends on its own into as few packets as the limits
+// allow. Other packets go straight through, after the data that was queued before them
+pub struct Batcher {
+    channels: ChannelManager,
+    limits: ChannelLimits,
+    // in the order it came, consecutive writes to the same channel joined up
+    queued: Vec<(u32, Vec<u8>)>,
+    bytes: usize,
+    // when the oldest queued data came
+    since: Option<Instant>,
+}
+
+impl Batcher {
+    pub fn new(channels: ChannelManager) -> Batcher {
+        Self {
+            limits: channels.limits(),
+            channels,
+            queued: vec![],
+            bytes: 0,
+            since: None,
+        }
+    }
+
+    // The packets to send now, in order
+    pub fn add(&mut self, packet: Packet) -> Vec<Packet> {
+        let (channel_id, data) = match written_data(&packet) {
+            Some((channel_id, data)) => (channel_id, data.to_vec()),
+            None => {
+                let mut ready: Vec<Packet> = self.flush().into_iter().collect();
+                ready.push(packet);
+                return ready;
+            }
+        };
+        // the reader can go on, the data is out of its queue and the batch is bounded
+        self.channels.release(channel_id, data.len());
+
+        let mut ready = vec![];
+        if self.bytes + data.len() > self.limits.batch_bytes {
+            ready.extend(self.flush());
+        }
+        self.since.get_or_insert_with(Instant::now);
+        self.bytes += data.len();
+        match self.queued.last_mut() {
+            Some((last, queued)) if *last == channel_id => queued.extend(data),
+            _ => self.queued.push((channel_id, data)),
+        }
+        if self.bytes >= self.limits.batch_bytes {
+            ready.extend(self.flush());
+        }
+        ready
+    }
+
+    // The batch once its data waited long enough
+    pub fn poll(&mut self, now: Instant) -> Option<Packet> {
+        let delay = self.limits.batch_delay;
+        if self.since.is_some_and(|since| now >= since + delay) {
+            self.flush()
+        } else {
+            None
+        }
+    }
+
+    // A single write goes out as it is, several as one packet with a ChannelDataGroup each
+    pub fn flush(&mut self) -> Option<Packet> {
+        self.bytes = 0;
+        self.since = None;
+        let mut queued = std::mem::take(&mut self.queued);
+        if queued.len() <= 1 {
+            return queued
+                .pop()
+                .map(|(channel_id, data)| write_request(channel_id, data));
+        }
+
+        let mut packet = Packet::new(String::from("core_channel_write"));
+        for (channel_id, data) in queued {
+            let mut group = Tlv::group(TlvType::ChannelDataGroup);
+            group.add(TlvType::ChannelId, channel_id);
+            group.add(TlvType::Length, data.len() as u32);
+            group.add(TlvType::ChannelData, data);
+            packet.add_tlv(group);
+        }
+        Some(packet)
+    }
+}
+
+// Channel and data of a plain core_channel_write from the agent
+fn written_data(packet: &Packet) -> Option<(u32, &[u8])> {
+    if packet.packet_type != PacketType::Request || packet.get_method() != "core_channel_write" {
+        return None;
+    }
+    Some((
+        packet.get_u32(TlvType::ChannelId)?,
+        packet.get_bytes(TlvType::ChannelData)?,
+    ))
+}
+
+// Every write a core_channel_write carries, batched or not
+pub fn channel_writes(packet: &Packet) -> Vec<(u32, &[u8])> {
+    match written_data(packet) {
+        Some(write) => vec![write],
+        None => packet
+            .groups(TlvType::ChannelDataGroup)
+            .filter_map(|group| {
+                Some((
+                    group.get_u32(TlvType::ChannelId)?,
+                    group.get_bytes(TlvType::ChannelData)?,
+                ))
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use meterpreter_protocol::tlv::{Query, TlvType};
+
+    use super::{channel_writes, Batcher};
+    use crate::channel::{self, ChannelLimits, ChannelManager};
+
+    fn batcher(batch_bytes: usize) -> Batcher {
+        Batcher::new(ChannelManager::with_limits(ChannelLimits {
+            batch_bytes,
+            batch_delay: Duration::from_millis(50),
+            window: 8,
+        }))
+    }
+
+    #[test]
+    fn test_small_writes_are_batched() {
+        let mut batcher = batcher(1024);
+        for (channel_id, data) in [(1, "ab"), (1, "c"), (2, "de"), (1, "f")] {
+            let ready = batcher.add(channel::write_request(channel_id, data.as_bytes().to_vec()));
+            assert!(ready.is_empty());
+        }
+        assert!(batcher.poll(Instant::now()).is_none());
+
+        let batch = batcher
+            .poll(Instant::now() + Duration::from_millis(50))
+            .unwrap();
+        assert_eq!(
+            channel_writes(&batch),
+            [(1, &b"abc"[..]), (2, b"de"), (1, b"f")]
+        );
+        assert_eq!(batch.groups(TlvType::ChannelDataGroup).count(), 3);
+        assert!(batcher.flush().is_none());
+    }
+
+    #[test]
+    fn test_batch_size_budget() {
+        let mut batcher = batcher(4);
+        assert!(batcher
+            .add(channel::write_request(1, b"abc".to_vec()))
+            .is_empty());
+        // doesn't fit anymore, the queued data goes first
+        let ready = batcher.add(channel::write_request(2, b"de".to_vec()));
+        assert_eq!(ready.len(), 1);
+        assert_eq!(channel_writes(&ready[0]), [(1, &b"abc"[..])]);
+        // a single write is sent as it is
+        assert_eq!(ready[0].get_bytes(TlvType::ChannelData), Some(&b"abc"[..]));
+
+        let ready = batcher.add(channel::write_request(2, b"fg".to_vec()));
+        assert_eq!(channel_writes(&ready[0]), [(2, &b"defg"[..])]);
+    }
+
+    #[test]
+    fn test_other_packets_follow_queued_data() {
+        let mut batcher = batcher(1024);
+        batcher.add(channel::write_request(1, b"data".to_vec()));
+        let ready = batcher.add(channel::close_request(1));
+        assert_eq!(ready.len(), 2);
+        assert_eq!(channel_writes(&ready[0]), [(1, &b"data"[..])]);
+        assert_eq!(ready[1].get_method(), "core_channel_close");
+    }
+
+    #[test]
+    fn test_reader_waits_for_the_window() {
+        let mut batcher = batcher(1024);
+        let channels = batcher.channels.clone();
+        let channel_id = channels.next_id();
+        channels.insert(channel_id, channel::test::NullChannel);
+        let (sender, receiver) = mpsc::channel();
+
+        // the window of 8 bytes lets two writes of 4 through, the third one waits
+        let output = (&b"aaaa"[..]).chain(&b"bbbb"[..]).chain(&b"cccc"[..]);
+        let reader_channels = channels.clone();
+        let reader = thread::spawn(move || {
+            channel::forward_output(&reader_channels, channel_id, output, &sender)
+        });
+        let mut received = vec![];
+        while received.len() < 2 {
+            received.push(receiver.recv().unwrap());
+        }
+        thread::sleep(Duration::from_millis(50));
+        assert!(receiver.try_recv().is_err());
+
+        for packet in received {
+            assert!(batcher.add(packet).is_empty());
+        }
+        let last = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        reader.join().unwrap();
+        batcher.add(last);
+        assert_eq!(
+            channel_writes(&batcher.flush().unwrap()),
+            [(channel_id, &b"aaaabbbbcccc"[..])]
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use std::io::SeekFrom;
+
+use meterpreter_protocol::packet::{Packet, PacketResult};
+use meterpreter_protocol::tlv::{Add, TlvType};
+
+use super::MAX_CHUNK_SIZE;
+use crate::dispatcher::{optional_value, required_value, CommandResult, Dispatcher};
+use crate::session::Session;
+
+pub fn register(dispatcher: &mut Dispatcher) {
+    dispatcher.register("core_channel_write", channel_write);
+    dispatcher.register("core_channel_read", channel_read);
+    dispatcher.register("core_channel_eof", channel_eof);
+    dispatcher.register("core_channel_seek", channel_seek);
+    dispatcher.register("core_channel_tell", channel_tell);
+    dispatcher.register("core_channel_close", channel_close);
+}
+
+// Values of the SeekWhence TLV, same as the C library
+const SEEK_SET: u32 = 0;
+const SEEK_CUR: u32 = 1;
+const SEEK_END: u32 = 2;
+
+fn channel_write(request: &Packet, response: &mut Packet, session: &mut Session) -> CommandResult {
+    let channel_id = *required_value(request, TlvType::ChannelId)?;
+    let data = required_value(request, TlvType::ChannelData)?;
+    let length = match optional_value(request, TlvType::Length) {
+        Some(length) => (*length as usize).min(data.len()),
+        None => data.len(),
+    };
+
+    let channel = session
+        .channels
+        .get(channel_id)
+        .ok_or(PacketResult::BadArguments)?;
+    let written = channel.lock().unwrap().write(&data[..length])?;
+
+    response.add(TlvType::ChannelId, channel_id);
+    response.add(
+        TlvType::Length,
+        u32::try_from(written).map_err(|_| PacketResult::InvalidData)?,
+    );
+    Ok(())
+}
+
+fn channel_read(request: &Packet, response: &mut Packet, session: &mut Session) -> CommandResult {
+    let channel_id = *required_value(request, TlvType::ChannelId)?;
+    let length = *required_value(request, TlvType::Length)? as usize;
+
+    let channel = session
+        .channels
+        .get(channel_id)
+        .ok_or(PacketResult::BadArguments)?;
+    let data = channel.lock().unwrap().read(length.min(MAX_CHUNK_SIZE))?;
+
+    response.add(TlvType::ChannelId, channel_id);
+    response.add(TlvType::ChannelData, data);
+    Ok(())
+}
+
+fn channel_eof(request: &Packet, response: &mut Packet, session: &mut Session) -> CommandResult {
+    let channel_id = *required_value(request, TlvType::ChannelId)?;
+
+    let channel = session
+        .channels
+        .get(channel_id)
+        .ok_or(PacketResult::BadArguments)?;
+    let eof = channel.lock().unwrap().eof()?;
+
+    response.add(TlvType::Bool, eof);
+    Ok(())
+}
+
+fn channel_seek(request: &Packet, _response: &mut Packet, session: &mut Session) -> CommandResult {
+    let channel_id = *required_value(request, TlvType::ChannelId)?;
+    // the offset travels as an uint but is signed, so relative seeks can go backwards
+    let offset = *required_value(request, TlvType::SeekOffset)? as i32;
+    let whence = *required_value(request, TlvType::SeekWhence)?;
+
+    let position = match whence {
+        SEEK_SET if offset >= 0 => SeekFrom::Start(offset as u64),
+        SEEK_CUR => SeekFrom::Current(offset as i64),
+        SEEK_END => SeekFrom::End(offset as i64),
+        _ => return Err(PacketResult::BadArguments),
+    };
+
+    let channel = session
+        .channels
+        .get(channel_id)
+        .ok_or(PacketResult::BadArguments)?;
+    channel.lock().unwrap().seek(position)?;
+    Ok(())
+}
+
+fn channel_tell(request: &Packet, response: &mut Packet, session: &mut Session) -> CommandResult {
+    let channel_id = *required_value(request, TlvType::ChannelId)?;
+
+    let channel = session
+        .channels
+        .get(channel_id)
+        .ok_or(PacketResult::BadArguments)?;
+    let position = channel.lock().unwrap().tell()?;
+
+    // the position is a 32 bit TLV, further into a file it can't be told
+    let position = u32::try_from(position).map_err(|_| PacketResult::InvalidData)?;
+    response.add(TlvType::SeekPos, position);
+    Ok(())
+}
+
+fn channel_close(request: &Packet, response: &mut Packet, session: &mut Session) -> CommandResult {
+    let channel_id = *required_value(request, TlvType::ChannelId)?;
+
+    let channel = session
+        .channels
+        .remove(channel_id)
+        .ok_or(PacketResult::BadArguments)?;
+    channel.lock().unwrap().close()?;
+
+    response.add(TlvType::ChannelId, channel_id);
+    Ok(())
+}
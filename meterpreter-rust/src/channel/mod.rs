@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+use std::io::{self, Read, SeekFrom};
+use std::net::Shutdown;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use meterpreter_protocol::packet::Packet;
+use meterpreter_protocol::tlv::{Add, TlvType};
+
+mod batch;
+mod commands;
+
+pub use batch::{channel_writes, Batcher};
+pub use commands::register;
+
+// Upper bound for a single core_channel_read, bigger transfers are done in several round trips
+pub const MAX_CHUNK_SIZE: usize = 64 * 1024;
+
+const READ_BUFFER_SIZE: usize = 4096;
+
+pub trait Channel: Send {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize>;
+
+    fn close(&mut self) -> io::Result<()>;
+
+    // Pull based reads, channels that push their data to the handler don't need this
+    fn read(&mut self, _length: usize) -> io::Result<Vec<u8>> {
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+
+    fn eof(&mut self) -> io::Result<bool> {
+        Ok(false)
+    }
+
+    fn seek(&mut self, _position: SeekFrom) -> io::Result<u64> {
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+
+    fn tell(&mut self) -> io::Result<u64> {
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+
+    fn shutdown(&mut self, _how: Shutdown) -> io::Result<()> {
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+}
+
+pub type SharedChannel = Arc<Mutex<dyn Channel>>;
+
+// How channel output travels to the handler
+#[derive(Debug, Clone, Copy)]
+pub struct ChannelLimits {
+    // channel data waiting to be sent goes out together, up to this many bytes a packet
+    pub batch_bytes: usize,
+    // how long data waits for more to join it
+    pub batch_delay: Duration,
+    // bytes a channel can have queued before its reader waits for the agent to catch up
+    pub window: usize,
+}
+
+impl Default for ChannelLimits {
+    fn default() -> Self {
+        Self {
+            batch_bytes: MAX_CHUNK_SIZE,
+            batch_delay: Duration::from_millis(10),
+            window: 4 * MAX_CHUNK_SIZE,
+        }
+    }
+}
+
+// Cheap to clone so that background threads (accepting sockets, reading output) can register
+// and remove channels while the dispatcher keeps using the same table
+#[derive(Clone)]
+pub struct ChannelManager {
+    next_id: Arc<AtomicU32>,
+    channels: Arc<Mutex<HashMap<u32, SharedChannel>>>,
+    limits: ChannelLimits,
+    // bytes of each channel on their way to the handler, signalled as the agent takes them
+    queued: Arc<(Mutex<HashMap<u32, usize>>, Condvar)>,
+}
+
+impl ChannelManager {
+    pub fn new() -> ChannelManager {
+        Self::with_limits(ChannelLimits::default())
+    }
+
+    pub fn with_limits(limits: ChannelLimits) -> ChannelManager {
+        Self {
+            next_id: Arc::new(AtomicU32::new(1)),
+            channels: Arc::new(Mutex::new(HashMap::new())),
+            limits,
+            queued: Arc::new((Mutex::new(HashMap::new()), Condvar::new())),
+        }
+    }
+
+    pub fn limits(&self) -> ChannelLimits {
+        self.limits
+    }
+
+    // Ids are handed out before the channel exists so that reader threads can be told about them
+    pub fn next_id(&self) -> u32 {
+        self.next_id.fetch_add(1, Ordering::SeqCst)
+    }
+
+    pub fn insert<C: Channel + 'static>(&self, id: u32, channel: C) {
+        self.channels
+            .lock()
+            .unwrap()
+            .insert(id, Arc::new(Mutex::new(channel)));
+    }
+
+    pub fn get(&self, id: u32) -> Option<SharedChannel> {
+        self.channels.lock().unwrap().get(&id).cloned()
+    }
+
+    pub fn remove(&self, id: u32) -> Option<SharedChannel> {
+        let channel = self.channels.lock().unwrap().remove(&id);
+        // a reader waiting for room has nobody to wait for anymore
+        let (queued, drained) = &*self.queued;
+        let _queued = queued.lock().unwrap();
+        drained.notify_all();
+        channel
+    }
+
+    pub fn contains(&self, id: u32) -> bool {
+        self.channels.lock().unwrap().contains_key(&id)
+    }
+
+    // Called by the reader of a channel before it queues data, waits while the channel
+    // already has a window's worth queued. Data of a closed channel doesn't wait, and
+    // neither does data that finds the queue empty, however big it is
+    pub fn reserve(&self, id: u32, length: usize) {
+        let (queued, drained) = &*self.queued;
+        let mut queued = queued.lock().unwrap();
+        loop {
+            let pending = queued.get(&id).copied().unwrap_or(0);
+            if pending == 0 || pending + length <= self.limits.window || !self.contains(id) {
+                break;
+            }
+            queued = drained.wait(queued).unwrap();
+        }
+        *queued.entry(id).or_default() += length;
+    }
+
+    // Called by the agent for the data it took off the queue
+    pub fn release(&self, id: u32, length: usize) {
+        let (queued, drained) = &*self.queued;
+        let mut queued = queued.lock().unwrap();
+        if let Some(pending) = queued.get_mut(&id) {
+            *pending = pending.saturating_sub(length);
+            if *pending == 0 {
+                queued.remove(&id);
+            }
+        }
+        drained.notify_all();
+    }
+}
+
+impl Default for ChannelManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn write_request(channel_id: u32, data: Vec<u8>) -> Packet {
+    let mut packet = Packet::new(String::from("core_channel_write"));
+    packet.add(TlvType::ChannelId, channel_id);
+    packet.add(TlvType::Length, data.len() as u32);
+    packet.add(TlvType::ChannelData, data);
+    packet
+}
+
+pub fn close_request(channel_id: u32) -> Packet {
+    let mut packet = Packet::new(String::from("core_channel_close"));
+    packet.add(TlvType::ChannelId, channel_id);
+    packet
+}
+
+// Pushes everything read from the source to the handler as channel data until EOF. Reading
+// stops for a while when the handler doesn't keep up
+pub fn forward_output(
+    channels: &ChannelManager,
+    channel_id: u32,
+    mut output: impl Read,
+    outbound: &Sender<Packet>,
+) {
+    let mut buffer = [0; READ_BUFFER_SIZE];
+    loop {
+        match output.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(count) => {
+                channels.reserve(channel_id, count);
+                let packet = write_request(channel_id, buffer[..count].to_vec());
+                if outbound.send(packet).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+// The handler only hears about the close if it didn't close the channel itself
+pub fn notify_closed(channels: &ChannelManager, channel_id: u32, outbound: &Sender<Packet>) {
+    if channels.remove(channel_id).is_some() {
+        let _ = outbound.send(close_request(channel_id));
+    }
+}
+
+pub fn spawn_forwarder(
+    channels: ChannelManager,
+    channel_id: u32,
+    output: impl Read + Send + 'static,
+    outbound: Sender<Packet>,
+) {
+    thread::spawn(move || {
+        forward_output(&channels, channel_id, output, &outbound);
+        notify_closed(&channels, channel_id, &outbound);
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+    use std::sync::mpsc;
+
+    use super::{Channel, ChannelManager};
+
+    pub struct NullChannel;
+
+    impl Channel for NullChannel {
+        fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+            Ok(data.len())
+        }
+
+        fn close(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_channel_ids_are_unique() {
+        let manager = ChannelManager::new();
+        let first = manager.next_id();
+        let second = manager.next_id();
+        assert_ne!(first, second);
+        assert_ne!(first, 0);
+    }
+
+    #[test]
+    fn test_insert_and_remove() {
+        let manager = ChannelManager::new();
+        let id = manager.next_id();
+        manager.insert(id, NullChannel);
+
+        assert!(manager.contains(id));
+        let channel = manager.get(id).unwrap();
+        assert_eq!(channel.lock().unwrap().write(&[1, 2, 3]).unwrap(), 3);
+        assert!(manager.remove(id).is_some());
+        assert!(!manager.contains(id));
+    }
+
+    #[test]
+    fn test_clones_share_channels() {
+        let manager = ChannelManager::new();
+        let clone = manager.clone();
+        let id = clone.next_id();
+        clone.insert(id, NullChannel);
+
+        assert!(manager.contains(id));
+        assert_ne!(manager.next_id(), id);
+    }
+
+    #[test]
+    fn test_notify_closed() {
+        let manager = ChannelManager::new();
+        let (sender, receiver) = mpsc::channel();
+        let id = manager.next_id();
+        manager.insert(id, NullChannel);
+
+        super::notify_closed(&manager, id, &sender);
+        assert_eq!(
+            receiver.try_recv().unwrap().get_method(),
+            "core_channel_close"
+        );
+
+        // already gone, the handler closed it first
+        super::notify_closed(&manager, id, &sender);
+        assert!(receiver.try_recv().is_err());
+    }
+}
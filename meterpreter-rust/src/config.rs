@@ -0,0 +1,216 @@
+use std::fs;
+use std::path::Path;
+
+use meterpreter_protocol::config::{Config, TransportConfig};
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+
+// Same defaults as the upstream payload options
+const DEFAULT_EXPIRY: u32 = 7 * 24 * 60 * 60;
+const DEFAULT_COMM_TIMEOUT: u32 = 5 * 60;
+const DEFAULT_RETRY_TOTAL: u32 = 60 * 60;
+const DEFAULT_RETRY_WAIT: u32 = 10;
+
+pub fn load(path: &Path) -> Result<Config> {
+    Ok(Config::from_bytes(&fs::read(path)?)?)
+}
+
+// Human editable form of a configuration block, meant for generating test setups
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlConfig {
+    session_guid: Option<String>,
+    uuid: Option<String>,
+    #[serde(default = "default_expiry")]
+    expiry: u32,
+    #[serde(default)]
+    transports: Vec<TomlTransport>,
+    #[serde(default)]
+    extensions: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlTransport {
+    url: String,
+    #[serde(default = "default_comm_timeout")]
+    comm_timeout: u32,
+    #[serde(default = "default_retry_total")]
+    retry_total: u32,
+    #[serde(default = "default_retry_wait")]
+    retry_wait: u32,
+}
+
+fn default_expiry() -> u32 {
+    DEFAULT_EXPIRY
+}
+
+fn default_comm_timeout() -> u32 {
+    DEFAULT_COMM_TIMEOUT
+}
+
+fn default_retry_total() -> u32 {
+    DEFAULT_RETRY_TOTAL
+}
+
+fn default_retry_wait() -> u32 {
+    DEFAULT_RETRY_WAIT
+}
+
+pub fn from_toml(text: &str) -> Result<Config> {
+    let toml: TomlConfig =
+        toml::from_str(text).map_err(|err| Error::InvalidConfig(err.to_string()))?;
+    let guid = |value: Option<String>| match value {
+        Some(value) => parse_guid(&value),
+        None => Ok([0; 16]),
+    };
+
+    Ok(Config {
+        session_guid: guid(toml.session_guid)?,
+        uuid: guid(toml.uuid)?,
+        expiry: toml.expiry,
+        transports: toml
+            .transports
+            .into_iter()
+            .map(|transport| TransportConfig {
+                url: transport.url,
+                comm_timeout: transport.comm_timeout,
+                retry_total: transport.retry_total,
+                retry_wait: transport.retry_wait,
+            })
+            .collect(),
+        extensions: toml.extensions,
+    })
+}
+
+// 32 hex digits, the dashes of the usual GUID notation are skipped
+fn parse_guid(text: &str) -> Result<[u8; 16]> {
+    let invalid = || Error::InvalidConfig(format!("'{}' is not a GUID", text));
+    let digits: Vec<u8> = text.bytes().filter(|digit| *digit != b'-').collect();
+    if digits.len() != 32 {
+        return Err(invalid());
+    }
+
+    let mut guid = [0; 16];
+    for (byte, pair) in guid.iter_mut().zip(digits.chunks(2)) {
+        let pair = std::str::from_utf8(pair).map_err(|_| invalid())?;
+        *byte = u8::from_str_radix(pair, 16).map_err(|_| invalid())?;
+    }
+    Ok(guid)
+}
+
+// Transport for an address given on the command line, with the usual timeouts
+pub fn tcp_transport(address: &str) -> TransportConfig {
+    TransportConfig {
+        url: format!("tcp://{}", address),
+        comm_timeout: DEFAULT_COMM_TIMEOUT,
+        retry_total: DEFAULT_RETRY_TOTAL,
+        retry_wait: DEFAULT_RETRY_WAIT,
+    }
+}
+
+// host:port to connect to for a transport, tcp is the only transport there is
+pub fn transport_address(transport: &TransportConfig) -> Result<&str> {
+    transport
+        .url
+        .strip_prefix("tcp://")
+        .ok_or_else(|| Error::InvalidConfig(format!("unsupported transport '{}'", transport.url)))
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use meterpreter_protocol::config::{Config, TransportConfig};
+
+    use crate::error::Error;
+
+    #[test]
+    fn test_from_toml() {
+        let config = super::from_toml(
+            r#"
+            session_guid = "00112233-4455-6677-8899-aabbccddeeff"
+            expiry = 60
+            extensions = ["stdapi"]
+
+            [[transports]]
+            url = "tcp://127.0.0.1:4444"
+            comm_timeout = 30
+
+            [[transports]]
+            url = "tcp://10.0.0.1:443"
+            retry_wait = 1
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            Config {
+                session_guid: [
+                    0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc,
+                    0xdd, 0xee, 0xff
+                ],
+                uuid: [0; 16],
+                expiry: 60,
+                transports: vec![
+                    TransportConfig {
+                        url: String::from("tcp://127.0.0.1:4444"),
+                        comm_timeout: 30,
+                        retry_total: super::DEFAULT_RETRY_TOTAL,
+                        retry_wait: super::DEFAULT_RETRY_WAIT,
+                    },
+                    TransportConfig {
+                        url: String::from("tcp://10.0.0.1:443"),
+                        comm_timeout: super::DEFAULT_COMM_TIMEOUT,
+                        retry_total: super::DEFAULT_RETRY_TOTAL,
+                        retry_wait: 1,
+                    },
+                ],
+                extensions: vec![String::from("stdapi")],
+            }
+        );
+        assert_eq!(
+            super::transport_address(&config.transports[0]).unwrap(),
+            "127.0.0.1:4444"
+        );
+        assert_eq!(
+            super::tcp_transport("127.0.0.1:4444"),
+            TransportConfig {
+                comm_timeout: super::DEFAULT_COMM_TIMEOUT,
+                ..config.transports[0].clone()
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_toml() {
+        for text in [
+            "session_guid = \"0011\"",
+            "session_guid = \"zz112233445566778899aabbccddeeff\"",
+            "expiry = -1",
+            "unknown = 1",
+            "[[transports]]\ncomm_timeout = 1",
+        ] {
+            assert!(
+                matches!(super::from_toml(text), Err(Error::InvalidConfig(_))),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_load() {
+        let path =
+            std::env::temp_dir().join(format!("meterpreter-rust-{}-config", std::process::id()));
+        let config = super::from_toml("uuid = \"ffffffffffffffffffffffffffffffff\"").unwrap();
+        fs::write(&path, config.to_bytes()).unwrap();
+
+        let loaded = super::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), config);
+        assert!(matches!(super::load(&path), Err(Error::Io(_))));
+    }
+}
@@ -0,0 +1,615 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{Duration, Instant};
+
+use meterpreter_protocol::packet::{Packet, PacketResult};
+use meterpreter_protocol::tlv::{self, Add, Query, TlvData, TlvKey, TlvType};
+use tracing::{error, info, info_span, trace};
+
+use crate::error::{Error, Result};
+use crate::extension::{self, Extension, CORE_TLV_RANGES};
+use crate::logging::{self, LoggedTlvs};
+use crate::session::Session;
+
+pub type CommandResult = std::result::Result<(), PacketResult>;
+
+// A handler fills in the response, the dispatcher takes care of the result code
+pub type CommandHandler = fn(&Packet, &mut Packet, &mut Session) -> CommandResult;
+
+struct LoadedExtension {
+    name: &'static str,
+    tlv_ranges: Vec<RangeInclusive<u32>>,
+    commands: Vec<&'static str>,
+}
+
+pub struct Dispatcher {
+    handlers: HashMap<String, CommandHandler>,
+    channel_openers: HashMap<String, CommandHandler>,
+    available: Vec<Box<dyn Extension>>,
+    loaded: Vec<LoadedExtension>,
+    timeouts: HashMap<String, Option<Duration>>,
+    default_timeout: Option<Duration>,
+}
+
+impl Dispatcher {
+    pub fn new() -> Dispatcher {
+        Self {
+            handlers: HashMap::new(),
+            channel_openers: HashMap::new(),
+            available: vec![],
+            loaded: vec![],
+            timeouts: HashMap::new(),
+            default_timeout: None,
+        }
+    }
+
+    pub fn register(&mut self, method: &str, handler: CommandHandler) {
+        self.handlers.insert(method.to_string(), handler);
+    }
+
+    // core_channel_open is routed by the requested channel type instead of the method
+    pub fn register_channel_type(&mut self, channel_type: &str, opener: CommandHandler) {
+        self.channel_openers
+            .insert(channel_type.to_string(), opener);
+    }
+
+    // Makes an extension available to core_loadlib without loading it yet
+    pub fn add_extension(&mut self, extension: Box<dyn Extension>) {
+        self.available.push(extension);
+    }
+
+    pub fn load_extension(&mut self, name: &str) -> Result<Vec<&'static str>> {
+        if self.loaded.iter().any(|loaded| loaded.name == name) {
+            return Err(Error::ExtensionAlreadyLoaded(name.to_string()));
+        }
+        let extension = self
+            .available
+            .iter()
+            .find(|extension| extension.name() == name)
+            .ok_or_else(|| Error::ExtensionNotFound(name.to_string()))?;
+
+        let tlv_ranges = extension.tlv_ranges();
+        for range in &tlv_ranges {
+            let owners = CORE_TLV_RANGES.iter().map(|core| ("core", core)).chain(
+                self.loaded
+                    .iter()
+                    .flat_map(|loaded| loaded.tlv_ranges.iter().map(|other| (loaded.name, other))),
+            );
+            for (owner, other) in owners {
+                if extension::ranges_overlap(range, other) {
+                    return Err(Error::TlvRangeConflict {
+                        extension: name.to_string(),
+                        owner: owner.to_string(),
+                        start: *range.start(),
+                        end: *range.end(),
+                    });
+                }
+            }
+        }
+
+        tlv::register_tlv_types(extension.tlv_types());
+        let mut commands = vec![];
+        for (method, handler) in extension.commands() {
+            self.handlers.insert(method.to_string(), handler);
+            commands.push(method);
+        }
+        for (channel_type, opener) in extension.channel_types() {
+            self.channel_openers
+                .insert(channel_type.to_string(), opener);
+        }
+
+        self.loaded.push(LoadedExtension {
+            name: extension.name(),
+            tlv_ranges,
+            commands: commands.clone(),
+        });
+        Ok(commands)
+    }
+
+    // Without a name every command the dispatcher answers to is listed
+    pub fn commands(&self, extension_name: Option<&str>) -> Option<Vec<String>> {
+        match extension_name {
+            Some(name) => self
+                .loaded
+                .iter()
+                .find(|loaded| loaded.name == name)
+                .map(|loaded| loaded.commands.iter().map(|c| c.to_string()).collect()),
+            None => {
+                let mut commands: Vec<String> = BUILTIN_COMMANDS
+                    .iter()
+                    .map(|command| command.to_string())
+                    .chain(self.handlers.keys().cloned())
+                    .collect();
+                commands.sort();
+                Some(commands)
+            }
+        }
+    }
+
+    // The extension that defines the given TLV type, None for core types and unknown ones
+    pub fn tlv_owner(&self, tlv_type: impl Into<TlvType>) -> Option<&'static str> {
+        let tlv_type = tlv_type.into();
+        self.loaded
+            .iter()
+            .find(|loaded| {
+                loaded
+                    .tlv_ranges
+                    .iter()
+                    .any(|range| range.contains(&tlv_type.number()))
+            })
+            .map(|loaded| loaded.name)
+    }
+
+    // Where a request goes, without running anything yet
+    pub fn route(&self, request: &Packet) -> Route {
+        let method = request.get_method();
+        let handler = match &method[..] {
+            "core_loadlib" | "core_enumextcmd" | HEARTBEAT => return Route::Builtin,
+            "core_channel_open" => optional_value(request, TlvType::ChannelType)
+                .and_then(|channel_type| self.channel_openers.get(channel_type)),
+            method => self.handlers.get(method),
+        };
+        match handler {
+            Some(handler) => Route::Handler(*handler),
+            None => Route::NotImplemented,
+        }
+    }
+
+    pub fn dispatch(&mut self, request: &Packet, session: &mut Session) -> Packet {
+        match self.route(request) {
+            Route::Builtin => respond(request, |response| match &request.get_method()[..] {
+                "core_loadlib" => self.load_library(request, response),
+                "core_enumextcmd" => self.enumerate_commands(request, response),
+                // a heartbeat only has to be answered
+                _ => Ok(()),
+            }),
+            Route::Handler(handler) => execute(handler, request, session),
+            Route::NotImplemented => respond(request, |_| Err(PacketResult::CallNotImplemented)),
+        }
+    }
+
+    // How long the handler waits for a command before replying with a timeout error, None
+    // lets it take as long as it needs
+    pub fn set_timeout(&mut self, method: &str, timeout: Option<Duration>) {
+        self.timeouts.insert(method.to_string(), timeout);
+    }
+
+    pub fn set_default_timeout(&mut self, timeout: Option<Duration>) {
+        self.default_timeout = timeout;
+    }
+
+    pub fn timeout(&self, method: &str) -> Option<Duration> {
+        self.timeouts
+            .get(method)
+            .copied()
+            .unwrap_or(self.default_timeout)
+    }
+
+    fn load_library(&mut self, request: &Packet, response: &mut Packet) -> CommandResult {
+        let library_path = required_value(request, TlvType::LibraryPath)?.clone();
+        let name = extension::library_name(&library_path);
+
+        // loading a library twice is harmless, the handler just gets the command list again
+        let commands = match self.commands(Some(name)) {
+            Some(commands) => commands,
+            None => self
+                .load_extension(name)
+                .map_err(|err| match err {
+                    Error::ExtensionNotFound(_) => PacketResult::FileNotFound,
+                    _ => PacketResult::InvalidData,
+                })?
+                .iter()
+                .map(|command| command.to_string())
+                .collect(),
+        };
+
+        for command in commands {
+            response.add(TlvType::Method, command);
+        }
+        Ok(())
+    }
+
+    fn enumerate_commands(&self, request: &Packet, response: &mut Packet) -> CommandResult {
+        let extension_name = optional_value(request, TlvType::String).cloned();
+        let commands = self
+            .commands(extension_name.as_deref())
+            .ok_or(PacketResult::BadArguments)?;
+
+        for command in commands {
+            response.add(TlvType::String, command);
+        }
+        Ok(())
+    }
+}
+
+// No-op request either side sends to a peer that went quiet, answering it is all it takes
+pub const HEARTBEAT: &str = "core_heartbeat";
+
+// Commands implemented by the dispatcher itself rather than by a registered handler
+const BUILTIN_COMMANDS: &[&str] = &[
+    "core_channel_open",
+    "core_enumextcmd",
+    HEARTBEAT,
+    "core_loadlib",
+];
+
+impl Default for Dispatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub enum Route {
+    // core_loadlib, core_enumextcmd and heartbeats are handled by the dispatcher itself
+    Builtin,
+    Handler(CommandHandler),
+    NotImplemented,
+}
+
+// Runs a handler outside of the dispatcher, on a worker thread for instance
+pub fn execute(handler: CommandHandler, request: &Packet, session: &mut Session) -> Packet {
+    respond(request, |response| {
+        // a panicking handler fails its command, the worker and the agent carry on
+        panic::catch_unwind(AssertUnwindSafe(|| handler(request, response, session)))
+            .unwrap_or_else(|payload| {
+                error!(panic = %panic_message(&*payload), "command handler panicked");
+                *response = request.create_response();
+                Err(PacketResult::InvalidFunction)
+            })
+    })
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => payload
+            .downcast_ref::<String>()
+            .map_or("unknown", String::as_str),
+    }
+}
+
+// Turns the response prepared for a command that didn't finish in time into its reply,
+// whatever the handler produces later is dropped
+pub fn timed_out(mut response: Packet) -> Packet {
+    response.set_result(PacketResult::Timeout);
+    info!(
+        method = %logging::method(&response),
+        request_id = %logging::request_id(&response),
+        "command timed out"
+    );
+    response
+}
+
+// The reply to a command that found every worker busy and the queue full, it never runs
+pub fn busy(mut response: Packet) -> Packet {
+    response.set_result(PacketResult::Busy);
+    info!(
+        method = %logging::method(&response),
+        request_id = %logging::request_id(&response),
+        "no worker free for the command"
+    );
+    response
+}
+
+fn respond(request: &Packet, run: impl FnOnce(&mut Packet) -> CommandResult) -> Packet {
+    let method = request.get_method();
+    let span = info_span!("dispatch", method = %method, request_id = %logging::request_id(request));
+    let _entered = span.enter();
+    let started = Instant::now();
+    trace!(tlvs = %LoggedTlvs(&request.tlvs), "request");
+
+    let mut response = request.create_response();
+    let packet_result = match run(&mut response) {
+        Ok(()) => PacketResult::Success,
+        Err(packet_result) => packet_result,
+    };
+    response.set_result(packet_result);
+
+    info!(
+        result = packet_result as u32,
+        elapsed_us = started.elapsed().as_micros() as u64,
+        "command dispatched"
+    );
+    trace!(tlvs = %LoggedTlvs(&response.tlvs), "response");
+    response
+}
+
+pub fn optional_value<T: TlvData>(request: &Packet, key: TlvKey<T>) -> Option<&T> {
+    request.get(key)
+}
+
+pub fn required_value<T: TlvData>(
+    request: &Packet,
+    key: TlvKey<T>,
+) -> std::result::Result<&T, PacketResult> {
+    optional_value(request, key).ok_or(PacketResult::BadArguments)
+}
+
+#[cfg(test)]
+mod test {
+    use std::ops::RangeInclusive;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    use meterpreter_protocol::packet::{Packet, PacketResult};
+    use meterpreter_protocol::tlv::{Add, Query, TlvKey, TlvType};
+
+    use super::{CommandHandler, CommandResult, Dispatcher};
+    use crate::error::Error;
+    use crate::extension::Extension;
+    use crate::session::Session;
+    use crate::stdapi::tlv;
+    use crate::stdapi::Stdapi;
+    use crate::worker::WorkerPool;
+
+    // Claims part of the stdapi TLV space, so it can't be loaded next to it
+    struct Clashing;
+
+    impl Extension for Clashing {
+        fn name(&self) -> &'static str {
+            "clashing"
+        }
+
+        fn tlv_ranges(&self) -> Vec<RangeInclusive<u32>> {
+            vec![20000..=20999, 4000..=4100]
+        }
+
+        fn tlv_types(&self) -> &'static [(TlvType, &'static str)] {
+            &[]
+        }
+
+        fn commands(&self) -> Vec<(&'static str, CommandHandler)> {
+            vec![("clashing_echo_uint", echo_uint)]
+        }
+    }
+
+    fn strings(packet: &Packet, key: TlvKey<String>) -> Vec<String> {
+        packet
+            .tlvs
+            .get(&key)
+            .map(|tlvs| {
+                tlvs.iter()
+                    .filter_map(|tlv| tlv.value_as().cloned())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn panic_midway(_: &Packet, response: &mut Packet, _: &mut Session) -> CommandResult {
+        response.add(TlvType::Uint, 1);
+        panic!("handler bug");
+    }
+
+    fn echo_uint(request: &Packet, response: &mut Packet, _: &mut Session) -> CommandResult {
+        let value = *super::required_value(request, TlvType::Uint)?;
+        response.add(TlvType::Uint, value);
+        Ok(())
+    }
+
+    #[test]
+    fn test_dispatch_registered_command() {
+        let (sender, _receiver) = mpsc::channel();
+        let mut session = Session::new(sender);
+        let mut dispatcher = Dispatcher::new();
+        dispatcher.register("test_echo_uint", echo_uint);
+
+        let mut request = Packet::new(String::from("test_echo_uint"));
+        request.set_request_id(String::from("38617410372826521432451458357478"));
+        request.add(TlvType::Uint, 42);
+        let response = dispatcher.dispatch(&request, &mut session);
+
+        assert_eq!(response.get_result(), Ok(PacketResult::Success));
+        assert_eq!(response.get_request_id(), request.get_request_id());
+        assert_eq!(
+            *super::required_value(&response, TlvType::Uint).unwrap(),
+            42
+        );
+    }
+
+    #[test]
+    fn test_panicking_handler() {
+        let (sender, _receiver) = mpsc::channel();
+        let session = Session::new(sender);
+        let pool = WorkerPool::new(1, 1);
+        let (completed, responses) = mpsc::channel();
+
+        // the only worker survives every panic and is still there for the echo
+        let handlers: [(CommandHandler, bool); 4] = [
+            (panic_midway, true),
+            (panic_midway, true),
+            (panic_midway, true),
+            (echo_uint, false),
+        ];
+        for (handler, panics) in handlers {
+            let mut request = Packet::new(String::from("test_echo_uint"));
+            request.add(TlvType::Uint, 42);
+            let mut command_session = session.for_command().session;
+            let completed = completed.clone();
+            pool.execute(move || {
+                let response = super::execute(handler, &request, &mut command_session);
+                completed.send(response).unwrap();
+            })
+            .unwrap();
+            let response = responses.recv_timeout(Duration::from_secs(5)).unwrap();
+
+            if panics {
+                // nothing the handler added before it panicked makes it into the reply
+                assert_eq!(response.get_result(), Ok(PacketResult::InvalidFunction));
+                assert_eq!(response.get_u32(TlvType::Uint), None);
+            } else {
+                assert_eq!(response.get_result(), Ok(PacketResult::Success));
+                assert_eq!(response.get_u32(TlvType::Uint), Some(42));
+            }
+        }
+    }
+
+    #[test]
+    fn test_dispatch_missing_argument() {
+        let (sender, _receiver) = mpsc::channel();
+        let mut session = Session::new(sender);
+        let mut dispatcher = Dispatcher::new();
+        dispatcher.register("test_echo_uint", echo_uint);
+
+        let request = Packet::new(String::from("test_echo_uint"));
+        let response = dispatcher.dispatch(&request, &mut session);
+
+        assert_eq!(response.get_result(), Ok(PacketResult::BadArguments));
+    }
+
+    #[test]
+    fn test_dispatch_channel_open() {
+        let (sender, _receiver) = mpsc::channel();
+        let mut session = Session::new(sender);
+        let mut dispatcher = Dispatcher::new();
+        dispatcher.register_channel_type("test_uint_channel", echo_uint);
+
+        let mut request = Packet::new(String::from("core_channel_open"));
+        request.add(TlvType::ChannelType, String::from("test_uint_channel"));
+        request.add(TlvType::Uint, 7);
+        let response = dispatcher.dispatch(&request, &mut session);
+        assert_eq!(response.get_result(), Ok(PacketResult::Success));
+
+        let mut request = Packet::new(String::from("core_channel_open"));
+        request.add(TlvType::ChannelType, String::from("test_unknown_channel"));
+        let response = dispatcher.dispatch(&request, &mut session);
+        assert_eq!(response.get_result(), Ok(PacketResult::CallNotImplemented));
+    }
+
+    #[test]
+    fn test_dispatch_unknown_command() {
+        let (sender, _receiver) = mpsc::channel();
+        let mut session = Session::new(sender);
+        let mut dispatcher = Dispatcher::new();
+
+        let request = Packet::new(String::from("core_unknown"));
+        let response = dispatcher.dispatch(&request, &mut session);
+
+        assert_eq!(response.get_result(), Ok(PacketResult::CallNotImplemented));
+    }
+
+    #[test]
+    fn test_core_loadlib() {
+        let (sender, _receiver) = mpsc::channel();
+        let mut session = Session::new(sender);
+        let mut dispatcher = Dispatcher::new();
+        dispatcher.add_extension(Box::new(Stdapi));
+
+        let mut sha1 = Packet::new(String::from("stdapi_fs_sha1"));
+        sha1.add(tlv::StdapiFilePath, String::from("/nonexistent"));
+        assert_eq!(
+            dispatcher.dispatch(&sha1, &mut session).get_result(),
+            Ok(PacketResult::CallNotImplemented)
+        );
+
+        let mut request = Packet::new(String::from("core_loadlib"));
+        request.add(
+            TlvType::LibraryPath,
+            String::from("ext_server_stdapi.x64.dll"),
+        );
+        let response = dispatcher.dispatch(&request, &mut session);
+        assert_eq!(response.get_result(), Ok(PacketResult::Success));
+        assert!(strings(&response, TlvType::Method).contains(&String::from("stdapi_fs_sha1")));
+
+        // stdapi is there now, the file just doesn't exist
+        assert_eq!(
+            dispatcher.dispatch(&sha1, &mut session).get_result(),
+            Ok(PacketResult::FileNotFound)
+        );
+
+        let response = dispatcher.dispatch(&request, &mut session);
+        assert_eq!(response.get_result(), Ok(PacketResult::Success));
+        assert!(strings(&response, TlvType::Method).contains(&String::from("stdapi_fs_sha1")));
+    }
+
+    #[test]
+    fn test_core_loadlib_unknown_library() {
+        let (sender, _receiver) = mpsc::channel();
+        let mut session = Session::new(sender);
+        let mut dispatcher = Dispatcher::new();
+
+        let mut request = Packet::new(String::from("core_loadlib"));
+        request.add(
+            TlvType::LibraryPath,
+            String::from("ext_server_kiwi.x64.dll"),
+        );
+        let response = dispatcher.dispatch(&request, &mut session);
+        assert_eq!(response.get_result(), Ok(PacketResult::FileNotFound));
+    }
+
+    #[test]
+    fn test_heartbeat() {
+        let (sender, _receiver) = mpsc::channel();
+        let request = Packet::new(String::from(super::HEARTBEAT));
+        let response = Dispatcher::new().dispatch(&request, &mut Session::new(sender));
+        assert_eq!(response.get_result(), Ok(PacketResult::Success));
+    }
+
+    #[test]
+    fn test_core_enumextcmd() {
+        let (sender, _receiver) = mpsc::channel();
+        let mut session = Session::new(sender);
+        let mut dispatcher = Dispatcher::new();
+        dispatcher.register("test_echo_uint", echo_uint);
+        dispatcher.add_extension(Box::new(Stdapi));
+        dispatcher.load_extension("stdapi").unwrap();
+
+        let mut request = Packet::new(String::from("core_enumextcmd"));
+        request.add(TlvType::String, String::from("stdapi"));
+        let response = dispatcher.dispatch(&request, &mut session);
+        assert_eq!(response.get_result(), Ok(PacketResult::Success));
+        let commands = strings(&response, TlvType::String);
+        assert!(commands.contains(&String::from("stdapi_sys_process_execute")));
+        assert!(!commands.contains(&String::from("test_echo_uint")));
+        assert!(commands
+            .iter()
+            .all(|command| command.starts_with("stdapi_")));
+
+        let request = Packet::new(String::from("core_enumextcmd"));
+        let response = dispatcher.dispatch(&request, &mut session);
+        let commands = strings(&response, TlvType::String);
+        assert!(commands.contains(&String::from("stdapi_sys_process_execute")));
+        assert!(commands.contains(&String::from("test_echo_uint")));
+        assert!(commands.contains(&String::from("core_loadlib")));
+        assert!(commands.contains(&String::from(super::HEARTBEAT)));
+
+        let mut request = Packet::new(String::from("core_enumextcmd"));
+        request.add(TlvType::String, String::from("priv"));
+        let response = dispatcher.dispatch(&request, &mut session);
+        assert_eq!(response.get_result(), Ok(PacketResult::BadArguments));
+    }
+
+    #[test]
+    fn test_load_extension_errors() {
+        let mut dispatcher = Dispatcher::new();
+        dispatcher.add_extension(Box::new(Stdapi));
+        dispatcher.add_extension(Box::new(Clashing));
+
+        assert!(matches!(
+            dispatcher.load_extension("priv"),
+            Err(Error::ExtensionNotFound(_))
+        ));
+        dispatcher.load_extension("stdapi").unwrap();
+        assert!(matches!(
+            dispatcher.load_extension("stdapi"),
+            Err(Error::ExtensionAlreadyLoaded(_))
+        ));
+        assert!(matches!(
+            dispatcher.load_extension("clashing"),
+            Err(Error::TlvRangeConflict { owner, .. }) if owner == "stdapi"
+        ));
+        assert!(dispatcher.commands(Some("clashing")).is_none());
+    }
+
+    #[test]
+    fn test_tlv_owner() {
+        let mut dispatcher = Dispatcher::new();
+        dispatcher.add_extension(Box::new(Stdapi));
+        assert_eq!(dispatcher.tlv_owner(tlv::StdapiProcessPath), None);
+
+        dispatcher.load_extension("stdapi").unwrap();
+        assert_eq!(dispatcher.tlv_owner(tlv::StdapiProcessPath), Some("stdapi"));
+        assert_eq!(dispatcher.tlv_owner(TlvType::ChannelId), None);
+    }
+}
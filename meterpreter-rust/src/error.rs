@@ -0,0 +1,30 @@
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Extension '{0}' is not compiled in")]
+    ExtensionNotFound(String),
+
+    #[error("Extension '{0}' is already loaded")]
+    ExtensionAlreadyLoaded(String),
+
+    #[error("TLV types {start}..={end} of extension '{extension}' are already owned by '{owner}'")]
+    TlvRangeConflict {
+        extension: String,
+        owner: String,
+        start: u32,
+        end: u32,
+    },
+
+    #[error("Invalid recording: {0}")]
+    InvalidRecording(String),
+
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+
+    #[error("Invalid configuration block: {0}")]
+    ConfigBlock(#[from] meterpreter_protocol::config::ConfigError),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
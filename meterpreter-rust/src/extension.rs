@@ -0,0 +1,89 @@
+use std::ops::RangeInclusive;
+
+use meterpreter_protocol::tlv::{self, TlvType};
+
+use crate::dispatcher::CommandHandler;
+use crate::stdapi::Stdapi;
+
+// TLV numbers the core protocol keeps for itself, extensions have to stay out of them
+pub const CORE_TLV_RANGES: &[RangeInclusive<u32>] = &[0..=599, 650..=999];
+
+pub trait Extension {
+    fn name(&self) -> &'static str;
+
+    // TLV type numbers (without the meta type bits) defined by the extension
+    fn tlv_ranges(&self) -> Vec<RangeInclusive<u32>>;
+
+    // The TLV types the extension defines with their names, registered when it's loaded
+    fn tlv_types(&self) -> &'static [(TlvType, &'static str)];
+
+    fn commands(&self) -> Vec<(&'static str, CommandHandler)>;
+
+    fn channel_types(&self) -> Vec<(&'static str, CommandHandler)> {
+        vec![]
+    }
+}
+
+// Everything that can be loaded with core_loadlib, there is no loading of code at runtime
+pub fn compiled_in() -> Vec<Box<dyn Extension>> {
+    vec![Box::new(Stdapi)]
+}
+
+// The handler knows the names of every extension's TLV types, loaded by the agent or not
+pub fn register_tlv_types() {
+    for extension in compiled_in() {
+        tlv::register_tlv_types(extension.tlv_types());
+    }
+}
+
+// core_loadlib sends library paths such as "ext_server_stdapi.x64.dll"
+pub fn library_name(library_path: &str) -> &str {
+    let file_name = library_path
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or(library_path);
+    let name = file_name.strip_prefix("ext_server_").unwrap_or(file_name);
+    name.split('.').next().unwrap_or(name)
+}
+
+pub fn ranges_overlap(first: &RangeInclusive<u32>, second: &RangeInclusive<u32>) -> bool {
+    first.start() <= second.end() && second.start() <= first.end()
+}
+
+#[cfg(test)]
+mod test {
+    use meterpreter_protocol::tlv::TLV_TYPES;
+
+    use super::CORE_TLV_RANGES;
+
+    #[test]
+    fn test_library_name() {
+        assert_eq!(super::library_name("stdapi"), "stdapi");
+        assert_eq!(super::library_name("ext_server_stdapi.x64.dll"), "stdapi");
+        assert_eq!(
+            super::library_name("/usr/share/ext_server_stdapi.lso"),
+            "stdapi"
+        );
+        assert_eq!(super::library_name("C:\\ext_server_priv.x86.dll"), "priv");
+    }
+
+    #[test]
+    fn test_ranges_overlap() {
+        assert!(super::ranges_overlap(&(0..=10), &(10..=20)));
+        assert!(super::ranges_overlap(&(5..=6), &(0..=10)));
+        assert!(!super::ranges_overlap(&(0..=9), &(10..=20)));
+    }
+
+    #[test]
+    fn test_core_tlvs_in_core_ranges() {
+        for (tlv_type, name) in TLV_TYPES {
+            assert!(
+                CORE_TLV_RANGES
+                    .iter()
+                    .any(|range| range.contains(&tlv_type.number())),
+                "{} is outside of the core TLV ranges",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use std::ascii;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::sync::Arc;
+
+use meterpreter_protocol::packet::{Packet, PacketResult};
+use meterpreter_protocol::tlv::{Add, TlvList, TlvType, TlvValue};
+
+use super::{Handler, RemoteSession};
+use crate::stdapi::tlv;
+
+// Longer byte values are cut off when a response is printed
+const MAX_PRINTED_BYTES: usize = 256;
+// What a read asks for when no length is given
+const DEFAULT_READ_LENGTH: u32 = 4096;
+
+const HELP: &str = "\
+sessions                   list the connected sessions
+use <id|guid>              send the following commands to that session
+sysinfo                    computer name, operating system and architecture
+pwd                        working directory of the agent
+ls [path]                  list a directory
+getenv <name>...           environment variables
+open <path> [mode]         open a file channel, mode defaults to rb
+read <channel> [length]    data the agent pushed or read from the channel
+write <channel> <text>     write a line to the channel
+close <channel>            close the channel
+exit                       leave the console
+";
+
+// Reads commands line by line until the input ends or says exit. Failed commands are
+// reported and the console goes on, only failing to write the output stops it
+pub fn run(handler: &Handler, input: impl BufRead, output: &mut impl Write) -> io::Result<()> {
+    let mut console = Console {
+        handler,
+        current: None,
+    };
+    write!(output, "{}", console.prompt())?;
+    output.flush()?;
+    for line in input.lines() {
+        let words: Vec<&str> = line
+            .as_ref()
+            .map_or(vec![], |line| line.split_whitespace().collect());
+        match &words[..] {
+            ["exit" | "quit", ..] => break,
+            [] => {}
+            words => {
+                if let Err(message) = console.execute(words, output) {
+                    writeln!(output, "error: {}", message)?;
+                }
+            }
+        }
+        write!(output, "{}", console.prompt())?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+struct Console<'a> {
+    handler: &'a Handler,
+    current: Option<Arc<RemoteSession>>,
+}
+
+impl Console<'_> {
+    fn prompt(&self) -> String {
+        match &self.current {
+            Some(session) => format!("session {} > ", session.id),
+            None => String::from("handler > "),
+        }
+    }
+
+    fn session(&self) -> Result<&Arc<RemoteSession>, String> {
+        self.current
+            .as_ref()
+            .ok_or_else(|| String::from("no session selected, see sessions and use"))
+    }
+
+    fn execute(&mut self, words: &[&str], output: &mut impl Write) -> Result<(), String> {
+        let request = match words {
+            ["help"] => return print(output, HELP),
+            ["sessions"] => {
+                let mut list = String::new();
+                for session in self.handler.sessions() {
+                    let selected = self
+                        .current
+                        .as_ref()
+                        .is_some_and(|current| current.guid == session.guid);
+                    list += &format!(
+                        "{} {:>3}  {}  {}\n",
+                        if selected { '*' } else { ' ' },
+                        session.id,
+                        hex(&session.guid),
+                        session.peer
+                    );
+                }
+                return print(output, &list);
+            }
+            ["use", session] => {
+                let found = self.handler.sessions().into_iter().find(|candidate| {
+                    candidate.id.to_string() == *session || hex(&candidate.guid) == *session
+                });
+                self.current = Some(found.ok_or_else(|| format!("no session {}", session))?);
+                return Ok(());
+            }
+            ["sysinfo"] => Packet::new(String::from("stdapi_sys_config_sysinfo")),
+            ["pwd"] => Packet::new(String::from("stdapi_fs_getwd")),
+            ["ls", path @ ..] if path.len() <= 1 => {
+                let mut request = Packet::new(String::from("stdapi_fs_ls"));
+                request.add(
+                    tlv::StdapiDirectoryPath,
+                    String::from(*path.first().unwrap_or(&".")),
+                );
+                request
+            }
+            ["getenv", names @ ..] if !names.is_empty() => {
+                let mut request = Packet::new(String::from("stdapi_sys_config_getenv"));
+                for name in names {
+                    request.add(tlv::StdapiEnvVariable, name.to_string());
+                }
+                request
+            }
+            ["open", path, mode @ ..] if mode.len() <= 1 => {
+                let mut request = Packet::new(String::from("core_channel_open"));
+                request.add(TlvType::ChannelType, String::from("stdapi_fs_file"));
+                request.add(tlv::StdapiFilePath, path.to_string());
+                request.add(
+                    tlv::StdapiFileMode,
+                    String::from(*mode.first().unwrap_or(&"rb")),
+                );
+                request
+            }
+            ["read", channel_id, length @ ..] if length.len() <= 1 => {
+                let channel_id = number(channel_id)?;
+                // pushed data is what the agent already sent, a read would miss it
+                let pushed = self.session()?.take_channel_data(channel_id);
+                if !pushed.is_empty() {
+                    return print(output, &format!("{}\n", Escaped(&pushed, usize::MAX)));
+                }
+                let mut request = Packet::new(String::from("core_channel_read"));
+                request.add(TlvType::ChannelId, channel_id);
+                request.add(
+                    TlvType::Length,
+                    length.first().map_or(Ok(DEFAULT_READ_LENGTH), number)?,
+                );
+                request
+            }
+            ["write", channel_id, text @ ..] if !text.is_empty() => {
+                let mut request = Packet::new(String::from("core_channel_write"));
+                request.add(TlvType::ChannelId, number(channel_id)?);
+                request.add(
+                    TlvType::ChannelData,
+                    format!("{}\n", text.join(" ")).into_bytes(),
+                );
+                request
+            }
+            ["close", channel_id] => {
+                let mut request = Packet::new(String::from("core_channel_close"));
+                request.add(TlvType::ChannelId, number(channel_id)?);
+                request
+            }
+            _ => return Err(format!("unknown command '{}', try help", words.join(" "))),
+        };
+
+        let response = self
+            .session()?
+            .request(request)
+            .map_err(|err| err.to_string())?;
+        match response.get_result() {
+            Ok(PacketResult::Success) => {}
+            Ok(result) => return Err(format!("{:?}", result)),
+            // the agent may answer with codes this side has no name for
+            Err(err) => return Err(err.to_string()),
+        }
+        print(output, &TlvTree(&response.tlvs, 0).to_string())
+    }
+}
+
+fn print(output: &mut impl Write, text: &str) -> Result<(), String> {
+    write!(output, "{}", text).map_err(|err| err.to_string())
+}
+
+fn number(text: &&str) -> Result<u32, String> {
+    text.parse()
+        .map_err(|_| format!("'{}' is not a number", text))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Bytes as text, anything that isn't printable ASCII escaped
+struct Escaped<'a>(&'a [u8], usize);
+
+impl fmt::Display for Escaped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0.iter().take(self.1) {
+            write!(f, "{}", ascii::escape_default(*byte))?;
+        }
+        if self.0.len() > self.1 {
+            write!(f, "...({} bytes)", self.0.len())?;
+        }
+        Ok(())
+    }
+}
+
+// One TLV per line, the TLVs of a group indented below it. The ids of the packet itself
+// are left out, they mean nothing to whoever reads the console
+struct TlvTree<'a>(&'a TlvList, usize);
+
+impl fmt::Display for TlvTree<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for tlv in self.0.iter() {
+            if [*TlvType::Method, *TlvType::RequestId, *TlvType::Result].contains(&tlv.tlv_type) {
+                continue;
+            }
+            write!(f, "{:indent$}{:?}", "", tlv.tlv_type, indent = self.1)?;
+            match &tlv.value {
+                None => write!(f, "\n{}", TlvTree(&tlv.tlvs, self.1 + 2))?,
+                Some(TlvValue::Bytes(bytes)) => {
+                    writeln!(f, ": {}", Escaped(bytes, MAX_PRINTED_BYTES))?
+                }
+                Some(TlvValue::String(value)) => writeln!(f, ": {}", value)?,
+                Some(TlvValue::UInt(value)) => writeln!(f, ": {}", value)?,
+                Some(TlvValue::ULongInt(value)) => writeln!(f, ": {}", value)?,
+                Some(TlvValue::Bool(value)) => writeln!(f, ": {}", value)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+    use std::fs;
+    use std::io::Cursor;
+
+    use meterpreter_protocol::packet::Packet;
+    use meterpreter_protocol::tlv::{Add, Tlv, TlvType};
+
+    use super::super::test::{spawn_agent, wait_for_sessions};
+    use super::{Handler, TlvTree};
+    use crate::extension;
+    use crate::stdapi::tlv;
+
+    #[test]
+    fn test_tlv_tree() {
+        // the names of stdapi types are known once it's registered
+        extension::register_tlv_types();
+        let mut group = Tlv::group(tlv::StdapiEnvGroup);
+        group.add(tlv::StdapiEnvVariable, String::from("HOME"));
+        group.add(tlv::StdapiEnvValue, String::from("/root"));
+        let mut packet = Packet::new(String::from("stdapi_sys_config_getenv"));
+        packet.set_request_id(String::from("1"));
+        packet.add_tlv(group);
+        packet.add(TlvType::ChannelData, b"a\n\xff".to_vec());
+
+        assert_eq!(
+            TlvTree(&packet.tlvs, 0).to_string(),
+            "StdapiEnvGroup\n  StdapiEnvVariable: HOME\n  StdapiEnvValue: /root\n\
+             ChannelData: a\\n\\xff\n"
+        );
+    }
+
+    #[test]
+    fn test_console() {
+        let handler = Handler::listen("127.0.0.1:0").unwrap();
+        let agent = spawn_agent(&handler);
+        wait_for_sessions(&handler, 1);
+        let session = handler.sessions().remove(0);
+
+        let path = env::temp_dir().join(format!("meterpreter-rust-{}-console", std::process::id()));
+        fs::write(&path, "from the file\n").unwrap();
+        let script = format!(
+            "sysinfo\nuse 2\nuse {}\nsysinfo\ngetenv PATH\nopen {}\nread 1\nclose 1\nread 1\n\
+             bogus\nexit\nsysinfo\n",
+            session.id,
+            path.display()
+        );
+        let mut output = vec![];
+        super::run(&handler, Cursor::new(script), &mut output).unwrap();
+        fs::remove_file(&path).unwrap();
+        session.close();
+        agent.join().unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let expected = [
+            "handler > error: no session selected",
+            "handler > error: no session 2",
+            "handler > session 1 > ",
+            &format!("StdapiOperatingSystemName: {}\n", env::consts::OS),
+            &format!("StdapiEnvValue: {}\n", env::var("PATH").unwrap()),
+            "ChannelId: 1\n",
+            "ChannelData: from the file\\n\n",
+            "error: BadArguments\n",
+            "error: unknown command 'bogus', try help\n",
+        ];
+        let mut rest = &output[..];
+        for text in expected {
+            let found = rest
+                .find(text)
+                .unwrap_or_else(|| panic!("{:?} not in {:?}", text, output));
+            rest = &rest[found + text.len()..];
+        }
+        // nothing runs after exit
+        assert_eq!(rest, "session 1 > ");
+    }
+}
@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+use std::io::{self, ErrorKind, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use meterpreter_protocol::encoder::PacketEncoder;
+use meterpreter_protocol::packet::{Packet, PacketType};
+use meterpreter_protocol::tlv::{Query, TlvType};
+use tracing::{debug, info, warn};
+
+use crate::channel;
+use crate::dispatcher;
+use crate::extension;
+use crate::transport::{StreamTransport, Transport};
+
+pub mod console;
+
+// How long a request waits for the agent's response
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Localhost stand-in for the handler, accepts agents and keeps track of their sessions by
+// the GUID it gave each of them
+pub struct Handler {
+    local_addr: SocketAddr,
+    sessions: Arc<Mutex<HashMap<[u8; 16], Arc<RemoteSession>>>>,
+}
+
+impl Handler {
+    pub fn listen(address: impl ToSocketAddrs) -> io::Result<Handler> {
+        extension::register_tlv_types();
+        let listener = TcpListener::bind(address)?;
+        let handler = Handler {
+            local_addr: listener.local_addr()?,
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+        };
+
+        let sessions = Arc::clone(&handler.sessions);
+        thread::spawn(move || accept(listener, sessions));
+        Ok(handler)
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    // Live sessions in the order they connected
+    pub fn sessions(&self) -> Vec<Arc<RemoteSession>> {
+        let mut sessions: Vec<Arc<RemoteSession>> =
+            self.sessions.lock().unwrap().values().cloned().collect();
+        sessions.sort_by_key(|session| session.id);
+        sessions
+    }
+
+    pub fn session(&self, guid: &[u8; 16]) -> Option<Arc<RemoteSession>> {
+        self.sessions.lock().unwrap().get(guid).cloned()
+    }
+}
+
+fn accept(listener: TcpListener, sessions: Arc<Mutex<HashMap<[u8; 16], Arc<RemoteSession>>>>) {
+    let next_id = AtomicU32::new(1);
+    let mut encoder = PacketEncoder::new();
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                warn!(error = %err, "accepting a session failed");
+                continue;
+            }
+        };
+        let session = match RemoteSession::new(
+            next_id.fetch_add(1, Ordering::SeqCst),
+            encoder.session_guid(),
+            stream,
+        ) {
+            Ok(session) => Arc::new(session),
+            Err(err) => {
+                warn!(error = %err, "setting up a session failed");
+                continue;
+            }
+        };
+        info!(id = session.id, peer = %session.peer, "session opened");
+        sessions
+            .lock()
+            .unwrap()
+            .insert(session.guid, Arc::clone(&session));
+
+        let sessions = Arc::clone(&sessions);
+        thread::spawn(move || {
+            let err = session.receive();
+            sessions.lock().unwrap().remove(&session.guid);
+            info!(id = session.id, reason = %err, "session closed");
+        });
+    }
+}
+
+// One connected agent. Requests can be made from several threads, the reader thread hands
+// each response to the request waiting for it
+pub struct RemoteSession {
+    pub id: u32,
+    pub guid: [u8; 16],
+    pub peer: SocketAddr,
+    writer: Mutex<(TcpStream, PacketEncoder)>,
+    waiting: Mutex<HashMap<String, Sender<Packet>>>,
+    // what the agent pushed with core_channel_write and nobody read yet
+    channel_data: Mutex<HashMap<u32, Vec<u8>>>,
+}
+
+impl RemoteSession {
+    fn new(id: u32, guid: [u8; 16], stream: TcpStream) -> io::Result<RemoteSession> {
+        stream.set_nodelay(true)?;
+        Ok(RemoteSession {
+            id,
+            guid,
+            peer: stream.peer_addr()?,
+            writer: Mutex::new((stream, PacketEncoder::new())),
+            waiting: Mutex::new(HashMap::new()),
+            channel_data: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn request(&self, mut packet: Packet) -> io::Result<Packet> {
+        let (sender, receiver) = mpsc::channel();
+        {
+            let mut writer = self.writer.lock().unwrap();
+            let (stream, encoder) = &mut *writer;
+            let request_id = encoder.request_id();
+            packet.set_request_id(request_id.clone());
+            self.waiting.lock().unwrap().insert(request_id, sender);
+            stream.write_all(&encoder.encode(&mut packet, &self.guid))?;
+        }
+
+        receiver
+            .recv_timeout(RESPONSE_TIMEOUT)
+            .map_err(|err| match err {
+                mpsc::RecvTimeoutError::Timeout => {
+                    io::Error::new(ErrorKind::TimedOut, "no response from the agent")
+                }
+                mpsc::RecvTimeoutError::Disconnected => {
+                    io::Error::new(ErrorKind::NotConnected, "session closed")
+                }
+            })
+    }
+
+    // Channel data the agent pushed since the last call
+    pub fn take_channel_data(&self, channel_id: u32) -> Vec<u8> {
+        self.channel_data
+            .lock()
+            .unwrap()
+            .remove(&channel_id)
+            .unwrap_or_default()
+    }
+
+    pub fn close(&self) {
+        let _ = self.writer.lock().unwrap().0.shutdown(Shutdown::Both);
+    }
+
+    // Reads until the connection ends, the error says why it did
+    fn receive(&self) -> io::Error {
+        let stream = match self.writer.lock().unwrap().0.try_clone() {
+            Ok(stream) => stream,
+            Err(err) => return err,
+        };
+        let mut transport = StreamTransport::new(stream);
+        loop {
+            let raw = match transport.read_packet() {
+                Ok(Some(raw)) => raw,
+                Ok(None) => continue,
+                Err(err) => {
+                    // nobody is going to answer the requests still waiting
+                    self.waiting.lock().unwrap().clear();
+                    return err;
+                }
+            };
+            match Packet::from_raw(&raw, &mut 0) {
+                Ok(packet) => self.received(packet),
+                Err(err) => warn!(id = self.id, error = %err, "dropping undecodable packet"),
+            }
+        }
+    }
+
+    fn received(&self, packet: Packet) {
+        if matches!(
+            packet.packet_type,
+            PacketType::Response | PacketType::PlainResponse
+        ) {
+            let waiting = self
+                .waiting
+                .lock()
+                .unwrap()
+                .remove(&packet.get_request_id());
+            match waiting {
+                Some(sender) => {
+                    let _ = sender.send(packet);
+                }
+                None => debug!(id = self.id, "response nobody waits for"),
+            }
+            return;
+        }
+
+        // requests from the agent don't get a response, the agent doesn't wait for one,
+        // except for heartbeats which are all about being answered
+        let channel_id = packet.get_u32(TlvType::ChannelId).unwrap_or(0);
+        match &packet.get_method()[..] {
+            dispatcher::HEARTBEAT => {
+                let mut writer = self.writer.lock().unwrap();
+                let (stream, encoder) = &mut *writer;
+                let raw = encoder.encode(&mut packet.create_response(), &self.guid);
+                if let Err(err) = stream.write_all(&raw) {
+                    warn!(id = self.id, error = %err, "answering a heartbeat failed");
+                }
+            }
+            "core_channel_write" => {
+                let mut channel_data = self.channel_data.lock().unwrap();
+                for (channel_id, data) in channel::channel_writes(&packet) {
+                    channel_data.entry(channel_id).or_default().extend(data);
+                }
+            }
+            "core_channel_close" => info!(id = self.id, channel_id, "channel closed by the agent"),
+            method => debug!(id = self.id, method, "ignoring request from the agent"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc;
+    use std::thread::{self, JoinHandle};
+    use std::time::{Duration, Instant};
+
+    use meterpreter_protocol::encoder::PacketEncoder;
+    use meterpreter_protocol::packet::{Packet, PacketResult};
+    use meterpreter_protocol::tlv::{Add, Query, TlvType};
+
+    use super::Handler;
+    use crate::agent::{self, Liveness};
+    use crate::session::Session;
+    use crate::stdapi::process::PROCESS_EXECUTE_FLAG_CHANNELIZED;
+    use crate::stdapi::tlv;
+    use crate::transport::StreamTransport;
+    use crate::worker::WorkerPool;
+
+    // An agent connected to the handler, it runs until the handler closes its session
+    pub fn spawn_agent(handler: &Handler) -> JoinHandle<[u8; 16]> {
+        let address = handler.local_addr();
+        thread::spawn(move || {
+            let mut dispatcher = crate::new_dispatcher().unwrap();
+            let (sender, receiver) = mpsc::channel();
+            let mut session = Session::new(sender);
+            agent::run(
+                &mut StreamTransport::connect(address).unwrap(),
+                &mut dispatcher,
+                &mut session,
+                &receiver,
+                &mut PacketEncoder::new(),
+                &WorkerPool::new(2, 2),
+                &Liveness::default(),
+            )
+            .unwrap();
+            session.guid
+        })
+    }
+
+    pub fn wait_for_sessions(handler: &Handler, count: usize) {
+        let started = Instant::now();
+        while handler.sessions().len() != count {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_several_sessions() {
+        let handler = Handler::listen("127.0.0.1:0").unwrap();
+        // one at a time, so the sessions are numbered in the order of the agents
+        let mut agents = vec![];
+        for count in 1..=2 {
+            agents.push(spawn_agent(&handler));
+            wait_for_sessions(&handler, count);
+        }
+
+        let sessions = handler.sessions();
+        assert_eq!(sessions[0].id, 1);
+        assert_eq!(sessions[1].id, 2);
+        assert_ne!(sessions[0].guid, sessions[1].guid);
+        assert!(handler.session(&sessions[1].guid).is_some());
+
+        for session in &sessions {
+            let response = session
+                .request(Packet::new(String::from("stdapi_sys_config_sysinfo")))
+                .unwrap();
+            assert_eq!(response.get_result(), Ok(PacketResult::Success));
+            assert_eq!(
+                response.get_str(tlv::StdapiOperatingSystemName),
+                Some(std::env::consts::OS)
+            );
+        }
+
+        // each agent took over the GUID its handler picked
+        for (session, agent) in sessions.iter().zip(agents) {
+            session.close();
+            assert_eq!(agent.join().unwrap(), session.guid);
+        }
+        wait_for_sessions(&handler, 0);
+    }
+
+    #[test]
+    fn test_pushed_channel_data() {
+        let handler = Handler::listen("127.0.0.1:0").unwrap();
+        let agent = spawn_agent(&handler);
+        wait_for_sessions(&handler, 1);
+        let session = handler.sessions().remove(0);
+
+        let mut execute = Packet::new(String::from("stdapi_sys_process_execute"));
+        execute.add(tlv::StdapiProcessPath, String::from("/bin/echo"));
+        execute.add(tlv::StdapiProcessArguments, String::from("pushed"));
+        execute.add(tlv::StdapiProcessFlags, PROCESS_EXECUTE_FLAG_CHANNELIZED);
+        let response = session.request(execute).unwrap();
+        let channel_id = response.get_u32(TlvType::ChannelId).unwrap();
+
+        let started = Instant::now();
+        let mut data = vec![];
+        while data != b"pushed\n" {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            data.extend(session.take_channel_data(channel_id));
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        session.close();
+        agent.join().unwrap();
+    }
+}
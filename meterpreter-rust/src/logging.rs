@@ -0,0 +1,180 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use meterpreter_protocol::packet::Packet;
+use meterpreter_protocol::tlv::{Query, TlvKey, TlvList, TlvType, TlvValue};
+use tracing::Level;
+
+// Longer byte values are cut off in the trace output
+const MAX_LOGGED_BYTES: usize = 64;
+
+static SHOW_SENSITIVE: AtomicBool = AtomicBool::new(false);
+
+// Logs go to stderr, stdout stays for the output of the subcommands
+pub fn init(level: Level, show_sensitive: bool) {
+    SHOW_SENSITIVE.store(show_sensitive, Ordering::Relaxed);
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(std::io::stderr)
+        .init();
+}
+
+// Method and request id to put on a span, empty when the packet doesn't have them
+pub fn method(packet: &Packet) -> String {
+    string_tlv(packet, TlvType::Method)
+}
+
+pub fn request_id(packet: &Packet) -> String {
+    string_tlv(packet, TlvType::RequestId)
+}
+
+fn string_tlv(packet: &Packet, key: TlvKey<String>) -> String {
+    String::from(packet.get_str(key).unwrap_or_default())
+}
+
+// Formats TLVs for the logs, values of sensitive types are left out
+pub struct LoggedTlvs<'a>(pub &'a TlvList);
+
+impl fmt::Display for LoggedTlvs<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[")?;
+        for (index, tlv) in self.0.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{:?}", tlv.tlv_type)?;
+            match &tlv.value {
+                None => write!(f, " {}", LoggedTlvs(&tlv.tlvs))?,
+                Some(value)
+                    if tlv.tlv_type.is_sensitive() && !SHOW_SENSITIVE.load(Ordering::Relaxed) =>
+                {
+                    write!(f, "=<redacted {} bytes>", value_length(value))?
+                }
+                Some(TlvValue::Bytes(bytes)) => {
+                    write!(f, "=")?;
+                    for byte in bytes.iter().take(MAX_LOGGED_BYTES) {
+                        write!(f, "{:02x}", byte)?;
+                    }
+                    if bytes.len() > MAX_LOGGED_BYTES {
+                        write!(f, "...({} bytes)", bytes.len())?;
+                    }
+                }
+                Some(TlvValue::String(value)) => write!(f, "={:?}", value)?,
+                Some(TlvValue::UInt(value)) => write!(f, "={}", value)?,
+                Some(TlvValue::ULongInt(value)) => write!(f, "={}", value)?,
+                Some(TlvValue::Bool(value)) => write!(f, "={}", value)?,
+            }
+        }
+        write!(f, "]")
+    }
+}
+
+fn value_length(value: &TlvValue) -> usize {
+    match value {
+        TlvValue::Bool(_) => 1,
+        TlvValue::UInt(_) => 4,
+        TlvValue::ULongInt(_) => 8,
+        TlvValue::String(value) => value.len(),
+        TlvValue::Bytes(value) => value.len(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{self, Write};
+    use std::sync::mpsc;
+    use std::sync::{Arc, Mutex};
+
+    use meterpreter_protocol::packet::Packet;
+    use meterpreter_protocol::tlv::{Add, Tlv, TlvType};
+    use tracing::Level;
+
+    use super::LoggedTlvs;
+    use crate::dispatcher::Dispatcher;
+    use crate::extension;
+    use crate::session::Session;
+    use crate::stdapi::tlv;
+
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_dispatch_is_traced() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let writer = output.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(Level::TRACE)
+            .with_ansi(false)
+            .with_writer(move || Capture(writer.clone()))
+            .finish();
+
+        let (sender, _receiver) = mpsc::channel();
+        let mut session = Session::new(sender);
+        let mut request = Packet::new(String::from("core_channel_write"));
+        request.set_request_id(String::from("1234"));
+        request.add(TlvType::ChannelData, b"secret".to_vec());
+        tracing::subscriber::with_default(subscriber, || {
+            // callsites other tests hit first cached that no one was interested in them
+            tracing::callsite::rebuild_interest_cache();
+            Dispatcher::new().dispatch(&request, &mut session);
+        });
+
+        let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+        assert!(output.contains("dispatch{method=core_channel_write request_id=1234}"));
+        assert!(output.contains("result=120"));
+        assert!(output.contains("ChannelData=<redacted 6 bytes>"));
+        assert!(!output.contains(
+            &"secret"
+                .bytes()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()
+        ));
+    }
+
+    #[test]
+    fn test_sensitive_values_are_redacted() {
+        let mut packet = Packet::new(String::from("core_negotiate_tlv_encryption"));
+        packet.add(TlvType::SymKeyType, 1);
+        packet.add(TlvType::SymKey, vec![0x41; 32]);
+        packet.add(TlvType::TransProxyPass, String::from("hunter2"));
+        packet.add(TlvType::ChannelData, b"secret".to_vec());
+
+        let logged = LoggedTlvs(&packet.tlvs).to_string();
+        assert_eq!(
+            logged,
+            "[Method=\"core_negotiate_tlv_encryption\", SymKeyType=1, SymKey=<redacted 32 bytes>, \
+             TransProxyPass=<redacted 7 bytes>, ChannelData=<redacted 6 bytes>]"
+        );
+        assert!(!logged.contains("hunter2"));
+    }
+
+    #[test]
+    fn test_groups_and_long_bytes() {
+        // the names of stdapi types are known once it's registered
+        extension::register_tlv_types();
+        let mut packet = Packet::new(String::from("stdapi_net_config_get_interfaces"));
+        packet.add(TlvType::TransCertHash, vec![0xab; 100]);
+        let mut group = Tlv::group(tlv::StdapiNetworkInterface);
+        group.add(tlv::StdapiIp, vec![127, 0, 0, 1]);
+        packet.add_tlv(group);
+
+        assert_eq!(
+            LoggedTlvs(&packet.tlvs).to_string(),
+            format!(
+                "[Method=\"stdapi_net_config_get_interfaces\", TransCertHash={}...(100 bytes), \
+                 StdapiNetworkInterface [StdapiIp=7f000001]]",
+                "ab".repeat(64)
+            )
+        );
+    }
+}
@@ -1,7 +1,241 @@
-pub mod protocol;
+pub mod agent;
+pub mod channel;
+pub mod config;
+pub mod dispatcher;
+pub mod error;
+pub mod extension;
+pub mod handler;
+pub mod logging;
+pub mod replay;
+pub mod session;
+pub mod stdapi;
+pub mod transport;
+pub mod worker;
+
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::PathBuf;
+use std::process;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use clap::{ArgAction, Parser, Subcommand};
+use meterpreter_protocol::config::{Config, TransportConfig};
+use meterpreter_protocol::encoder::PacketEncoder;
+use tracing::{info, Level};
+
+use channel::{ChannelLimits, ChannelManager};
+use dispatcher::Dispatcher;
+use error::{Error, Result};
+use session::Session;
+use transport::{RecordingTransport, StreamTransport, Transport};
+use worker::WorkerPool;
+
+const QUEUED_COMMANDS_PER_WORKER: usize = 4;
+
+#[derive(Parser)]
+struct Args {
+    #[clap(subcommand)]
+    command: Action,
+    /// log more, -v for every packet, -vv for the TLVs in them as well
+    #[clap(short, long, global = true, action = ArgAction::Count)]
+    verbose: u8,
+    /// don't redact keys, passwords and channel data in the logs
+    #[clap(long, global = true)]
+    log_sensitive: bool,
+}
+
+#[derive(Subcommand)]
+enum Action {
+    /// List the commands the agent supports
+    Commands,
+    /// Connect to a handler and serve the session
+    Connect {
+        /// address of the handler, host:port, tried before the transports of the
+        /// configuration
+        address: Option<String>,
+        /// configuration block to start the session with
+        #[clap(long)]
+        config: Option<PathBuf>,
+        /// file to record every packet of the session to
+        #[clap(long)]
+        record: Option<PathBuf>,
+        /// number of commands that can run at the same time
+        #[clap(long, default_value_t = 4)]
+        workers: usize,
+        /// seconds a command gets before it is answered with a timeout error
+        #[clap(long)]
+        command_timeout: Option<u64>,
+        /// most bytes of channel output sent together in one packet
+        #[clap(long)]
+        channel_batch_bytes: Option<usize>,
+        /// milliseconds channel output waits for more to be sent with it
+        #[clap(long)]
+        channel_batch_delay: Option<u64>,
+        /// bytes a channel can have waiting to be sent before its output isn't read anymore
+        #[clap(long)]
+        channel_window: Option<usize>,
+    },
+    /// Write the configuration block described by a TOML file
+    Config {
+        /// TOML file with the session settings
+        toml: PathBuf,
+        /// where the binary configuration block goes
+        output: PathBuf,
+    },
+    /// Accept agents on a local port and send them commands from an interactive console
+    Handle {
+        /// address to listen on, host:port
+        #[clap(default_value = "127.0.0.1:4444")]
+        address: String,
+    },
+    /// Dispatch the requests of a recording again and compare the responses
+    Replay {
+        /// file written by connect --record
+        recording: PathBuf,
+    },
+}
 
 fn main() {
-    use protocol::tlv::MetaType;
+    let args = Args::parse();
+    let level = match args.verbose {
+        0 => Level::INFO,
+        1 => Level::DEBUG,
+        _ => Level::TRACE,
+    };
+    logging::init(level, args.log_sensitive);
+
+    match handle_actions(args) {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    }
+}
+
+// Ok(false) when the command ran but found a problem, like a replay that differs
+fn handle_actions(args: Args) -> Result<bool> {
+    let mut dispatcher = new_dispatcher()?;
+
+    match args.command {
+        Action::Commands => {
+            println!("{:?}", dispatcher.commands(None));
+        }
+        Action::Connect {
+            address,
+            config,
+            record,
+            workers,
+            command_timeout,
+            channel_batch_bytes,
+            channel_batch_delay,
+            channel_window,
+        } => {
+            dispatcher.set_default_timeout(command_timeout.map(Duration::from_secs));
+            // a few requests can wait for a worker, after that the agent stops reading
+            let pool = WorkerPool::new(workers, workers * QUEUED_COMMANDS_PER_WORKER);
+            let mut config = match config {
+                Some(path) => config::load(&path)?,
+                None => Config::default(),
+            };
+            for extension in &config.extensions {
+                if dispatcher.commands(Some(extension)).is_none() {
+                    dispatcher.load_extension(extension)?;
+                }
+            }
+            // an address on the command line goes before the configured transports
+            if let Some(address) = address {
+                config.transports.insert(0, config::tcp_transport(&address));
+            }
+            if config.transports.is_empty() {
+                return Err(Error::InvalidConfig(String::from(
+                    "no address given and no transport configured",
+                )));
+            }
+            // fail early rather than on the first reconnect
+            for transport in &config.transports {
+                config::transport_address(transport)?;
+            }
+
+            let (sender, receiver) = mpsc::channel();
+            let mut session = Session::new(sender);
+            session.guid = config.session_guid;
+            let defaults = ChannelLimits::default();
+            session.channels = ChannelManager::with_limits(ChannelLimits {
+                batch_bytes: channel_batch_bytes.unwrap_or(defaults.batch_bytes),
+                batch_delay: channel_batch_delay
+                    .map(Duration::from_millis)
+                    .unwrap_or(defaults.batch_delay),
+                window: channel_window.unwrap_or(defaults.window),
+            });
+            // every connection of the session goes into the same recording
+            let recording = record.map(File::create).transpose()?;
+            let mut recording_started = false;
+            let mut connect = |transport: &TransportConfig| -> io::Result<Box<dyn Transport>> {
+                let address = config::transport_address(transport)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))?;
+                let stream = StreamTransport::connect(address)?;
+                Ok(match &recording {
+                    Some(file) if recording_started => {
+                        Box::new(RecordingTransport::resume(stream, file.try_clone()?))
+                    }
+                    Some(file) => {
+                        recording_started = true;
+                        Box::new(RecordingTransport::new(stream, file.try_clone()?)?)
+                    }
+                    None => Box::new(stream),
+                })
+            };
+            let end = agent::serve(
+                &config,
+                &mut connect,
+                &mut dispatcher,
+                &mut session,
+                &receiver,
+                &mut PacketEncoder::new(),
+                &pool,
+            )?;
+            info!(?end, "session over");
+        }
+        Action::Config { toml, output } => {
+            let config = config::from_toml(&fs::read_to_string(toml)?)?;
+            fs::write(output, config.to_bytes())?;
+        }
+        Action::Handle { address } => {
+            let handler = handler::Handler::listen(address)?;
+            println!("listening on {}", handler.local_addr());
+            handler::console::run(&handler, io::stdin().lock(), &mut io::stdout())?;
+        }
+        Action::Replay { recording } => {
+            let records =
+                transport::recording::read_records(BufReader::new(File::open(recording)?))?;
+            // packets the agent sends on its own aren't part of the comparison
+            let (sender, _receiver) = mpsc::channel();
+            let mut session = Session::new(sender);
+
+            let diffs = replay::replay(&records, &mut dispatcher, &mut session);
+            for diff in &diffs {
+                println!("{} {}", diff.request_id, diff.method);
+                for difference in &diff.differences {
+                    println!("    {}", difference);
+                }
+            }
+            return Ok(diffs.iter().all(|diff| diff.differences.is_empty()));
+        }
+    }
+
+    Ok(true)
+}
 
-    println!("{:?}", MetaType::String);
+fn new_dispatcher() -> Result<Dispatcher> {
+    let mut dispatcher = Dispatcher::new();
+    channel::register(&mut dispatcher);
+    for extension in extension::compiled_in() {
+        dispatcher.add_extension(extension);
+    }
+    // stdapi is always there, anything else has to be asked for with core_loadlib
+    dispatcher.load_extension("stdapi")?;
+    Ok(dispatcher)
 }
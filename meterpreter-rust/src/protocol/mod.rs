@@ -1,2 +0,0 @@
-pub mod packet;
-pub mod tlv;
@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+
+use meterpreter_protocol::packet::{Packet, PacketType};
+use meterpreter_protocol::tlv::{TlvList, TlvType};
+
+use crate::dispatcher::Dispatcher;
+use crate::session::Session;
+use crate::transport::recording::{Direction, Record};
+
+pub struct ResponseDiff {
+    pub request_id: String,
+    pub method: String,
+    pub differences: Vec<String>,
+}
+
+// Dispatches every request of a recording again and compares the responses with the ones
+// that were sent back when it was recorded
+pub fn replay(
+    records: &[Record],
+    dispatcher: &mut Dispatcher,
+    session: &mut Session,
+) -> Vec<ResponseDiff> {
+    let mut recorded_responses: HashMap<String, Packet> = records
+        .iter()
+        .filter(|record| record.direction == Direction::Sent)
+        .filter_map(|record| Packet::from_raw(&record.raw, &mut 0).ok())
+        .filter(|packet| {
+            matches!(
+                packet.packet_type,
+                PacketType::Response | PacketType::PlainResponse
+            ) && packet.tlvs.contains_key(&TlvType::RequestId)
+        })
+        .map(|packet| (packet.get_request_id(), packet))
+        .collect();
+
+    let mut diffs = vec![];
+    for record in records
+        .iter()
+        .filter(|record| record.direction == Direction::Received)
+    {
+        let request = match Packet::from_raw(&record.raw, &mut 0) {
+            Ok(request) => request,
+            Err(err) => {
+                diffs.push(ResponseDiff {
+                    request_id: String::new(),
+                    method: String::new(),
+                    differences: vec![format!("request can't be decoded: {}", err)],
+                });
+                continue;
+            }
+        };
+        // answers to the agent's heartbeats, like the agent there's nothing to do with them
+        if matches!(
+            request.packet_type,
+            PacketType::Response | PacketType::PlainResponse
+        ) {
+            continue;
+        }
+        let response = dispatcher.dispatch(&request, session);
+        let request_id = if request.tlvs.contains_key(&TlvType::RequestId) {
+            request.get_request_id()
+        } else {
+            String::new()
+        };
+
+        let mut differences = vec![];
+        match recorded_responses.remove(&request_id) {
+            Some(recorded) => diff_tlvs("", &recorded.tlvs, &response.tlvs, &mut differences),
+            None => differences.push(String::from("no response was recorded")),
+        }
+        diffs.push(ResponseDiff {
+            request_id,
+            method: request.get_method(),
+            differences,
+        });
+    }
+
+    diffs
+}
+
+// Compares TLVs of the same type by position, "Group[0]/Type[1]" points at a nested TLV
+pub fn diff_tlvs(path: &str, expected: &TlvList, actual: &TlvList, differences: &mut Vec<String>) {
+    let mut tlv_types: Vec<TlvType> = vec![];
+    for tlv in expected.iter().chain(actual.iter()) {
+        if !tlv_types.contains(&tlv.tlv_type) {
+            tlv_types.push(tlv.tlv_type);
+        }
+    }
+
+    for tlv_type in tlv_types {
+        let expected = expected
+            .get(&tlv_type)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let actual = actual.get(&tlv_type).map(Vec::as_slice).unwrap_or_default();
+        for index in 0..expected.len().max(actual.len()) {
+            let location = format!("{}{:?}[{}]", path, tlv_type, index);
+            match (expected.get(index), actual.get(index)) {
+                (Some(_), None) => differences.push(format!("{}: missing", location)),
+                (None, Some(tlv)) => {
+                    differences.push(format!("{}: unexpected {:?}", location, tlv.value))
+                }
+                (Some(expected), Some(actual)) if expected.value.is_none() => diff_tlvs(
+                    &format!("{}/", location),
+                    &expected.tlvs,
+                    &actual.tlvs,
+                    differences,
+                ),
+                (Some(expected), Some(actual)) if expected.value != actual.value => differences
+                    .push(format!(
+                        "{}: expected {:?}, got {:?}",
+                        location, expected.value, actual.value
+                    )),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc;
+
+    use meterpreter_protocol::encoder::PacketEncoder;
+    use meterpreter_protocol::packet::Packet;
+    use meterpreter_protocol::tlv::{Add, Tlv, TlvList, TlvType, TlvValue};
+
+    use super::replay;
+    use crate::channel;
+    use crate::dispatcher::{self, Dispatcher};
+    use crate::extension;
+    use crate::session::Session;
+    use crate::stdapi::tlv;
+    use crate::transport::recording::{Direction, Record};
+
+    fn setup() -> (Dispatcher, Session) {
+        let mut dispatcher = Dispatcher::new();
+        channel::register(&mut dispatcher);
+        let (sender, _) = mpsc::channel();
+        (dispatcher, Session::new(sender))
+    }
+
+    fn record(direction: Direction, raw: Vec<u8>) -> Record {
+        Record {
+            direction,
+            timestamp: 0,
+            raw,
+        }
+    }
+
+    // A request and the response to it, with the response passed through `edit` first
+    fn exchange(edit: impl Fn(&mut Packet)) -> Vec<Record> {
+        let (mut dispatcher, mut session) = setup();
+        let mut encoder = PacketEncoder::seeded(4);
+        let mut request = Packet::new(String::from("core_enumextcmd"));
+        let raw_request = encoder.encode(&mut request, &[0; 16]);
+
+        let mut response = dispatcher.dispatch(&request, &mut session);
+        edit(&mut response);
+        let raw_response = encoder.encode(&mut response, &[0; 16]);
+        vec![
+            record(Direction::Received, raw_request),
+            record(Direction::Sent, raw_response),
+        ]
+    }
+
+    #[test]
+    fn test_replay_matches() {
+        let (mut dispatcher, mut session) = setup();
+        let diffs = replay(&exchange(|_| {}), &mut dispatcher, &mut session);
+
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].method, "core_enumextcmd");
+        assert!(diffs[0].differences.is_empty());
+    }
+
+    #[test]
+    fn test_replay_reports_differences() {
+        let records = exchange(|response| {
+            let commands = response.tlvs.remove(&TlvType::String).unwrap();
+            for (index, command) in commands.iter().enumerate() {
+                let value = match index {
+                    1 => String::from("core_bogus"),
+                    _ => command.value_as_string(),
+                };
+                response.add(TlvType::String, value);
+            }
+            response.add(TlvType::ChannelId, 1);
+        });
+        let (mut dispatcher, mut session) = setup();
+        let diffs = replay(&records, &mut dispatcher, &mut session);
+
+        assert_eq!(
+            diffs[0].differences,
+            [
+                "String[1]: expected Some(String(\"core_bogus\")), got Some(String(\"core_channel_eof\"))",
+                "ChannelId[0]: missing",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replay_without_recorded_response() {
+        let mut records = exchange(|_| {});
+        records.pop();
+        let (mut dispatcher, mut session) = setup();
+        let diffs = replay(&records, &mut dispatcher, &mut session);
+
+        assert_eq!(diffs[0].differences, ["no response was recorded"]);
+    }
+
+    #[test]
+    fn test_replay_skips_heartbeat_responses() {
+        let mut records = exchange(|_| {});
+        // the agent asked and the handler answered
+        let mut encoder = PacketEncoder::seeded(5);
+        let mut heartbeat = Packet::new(String::from(dispatcher::HEARTBEAT));
+        heartbeat.set_request_id(encoder.request_id());
+        let mut answer = heartbeat.create_response();
+        records.push(record(
+            Direction::Sent,
+            encoder.encode(&mut heartbeat, &[0; 16]),
+        ));
+        records.push(record(
+            Direction::Received,
+            encoder.encode(&mut answer, &[0; 16]),
+        ));
+        let (mut dispatcher, mut session) = setup();
+        let diffs = replay(&records, &mut dispatcher, &mut session);
+
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].method, "core_enumextcmd");
+        assert!(diffs[0].differences.is_empty());
+    }
+
+    #[test]
+    fn test_diff_nested_groups() {
+        // the names of stdapi types are known once it's registered
+        extension::register_tlv_types();
+        let mut expected = TlvList::new();
+        let mut actual = TlvList::new();
+        for (list, ip) in [(&mut expected, 1), (&mut actual, 2)] {
+            let mut group = Tlv::group(tlv::StdapiNetworkInterface);
+            group.add(tlv::StdapiIp, vec![127, 0, 0, 1]);
+            group.add(tlv::StdapiIp, vec![127, 0, 0, ip]);
+            list.push(group);
+        }
+        let mut differences = vec![];
+        super::diff_tlvs("", &expected, &actual, &mut differences);
+
+        assert_eq!(
+            differences,
+            [format!(
+                "StdapiNetworkInterface[0]/StdapiIp[1]: expected {:?}, got {:?}",
+                Some(TlvValue::Bytes(vec![127, 0, 0, 1])),
+                Some(TlvValue::Bytes(vec![127, 0, 0, 2]))
+            )]
+        );
+    }
+}
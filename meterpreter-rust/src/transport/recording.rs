@@ -0,0 +1,210 @@
+use std::io::{self, ErrorKind, Read, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::{Error, Result};
+use crate::transport::Transport;
+
+// A recording starts with MAGIC, followed by one record per packet:
+// direction (1 byte), timestamp (8 bytes, microseconds since the UNIX epoch),
+// length (4 bytes) and the packet as it was on the wire. Integers are big endian.
+const MAGIC: &[u8; 8] = b"MTRREC01";
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+#[repr(u8)]
+pub enum Direction {
+    Received = 0,
+    Sent = 1,
+}
+
+#[derive(Debug)]
+pub struct Record {
+    pub direction: Direction,
+    pub timestamp: u64,
+    pub raw: Vec<u8>,
+}
+
+pub struct Recorder<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(mut writer: W) -> io::Result<Recorder<W>> {
+        writer.write_all(MAGIC)?;
+        writer.flush()?;
+        Ok(Self { writer })
+    }
+
+    pub fn record(&mut self, direction: Direction, raw: &[u8]) -> io::Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_micros() as u64)
+            .unwrap_or(0);
+
+        self.writer.write_all(&[direction as u8])?;
+        self.writer.write_all(&timestamp.to_be_bytes())?;
+        self.writer.write_all(&(raw.len() as u32).to_be_bytes())?;
+        self.writer.write_all(raw)?;
+        // a recording is mostly needed after a crash, so nothing stays buffered
+        self.writer.flush()
+    }
+}
+
+pub fn read_records(mut reader: impl Read) -> Result<Vec<Record>> {
+    let mut magic = [0; MAGIC.len()];
+    reader
+        .read_exact(&mut magic)
+        .map_err(|_| invalid("missing header"))?;
+    if &magic != MAGIC {
+        return Err(invalid("not a packet recording"));
+    }
+
+    let mut records = vec![];
+    loop {
+        let mut direction = [0; 1];
+        match reader.read_exact(&mut direction) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(records),
+            Err(err) => return Err(err.into()),
+        }
+        let direction = match direction[0] {
+            0 => Direction::Received,
+            1 => Direction::Sent,
+            other => return Err(invalid(&format!("unknown direction {}", other))),
+        };
+
+        let mut timestamp = [0; 8];
+        let mut length = [0; 4];
+        reader
+            .read_exact(&mut timestamp)
+            .and_then(|_| reader.read_exact(&mut length))
+            .map_err(|_| invalid("truncated record"))?;
+        // the length comes from the file, the buffer only grows with what is really there
+        let length = u32::from_be_bytes(length) as usize;
+        let mut raw = vec![];
+        (&mut reader).take(length as u64).read_to_end(&mut raw)?;
+        if raw.len() != length {
+            return Err(invalid("truncated record"));
+        }
+
+        records.push(Record {
+            direction,
+            timestamp: u64::from_be_bytes(timestamp),
+            raw,
+        });
+    }
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::InvalidRecording(reason.to_string())
+}
+
+// Records every packet going through the wrapped transport
+pub struct RecordingTransport<T, W: Write> {
+    inner: T,
+    recorder: Recorder<W>,
+}
+
+impl<T: Transport, W: Write> RecordingTransport<T, W> {
+    pub fn new(inner: T, writer: W) -> io::Result<RecordingTransport<T, W>> {
+        Ok(Self {
+            inner,
+            recorder: Recorder::new(writer)?,
+        })
+    }
+
+    // Adds to a recording an earlier transport started, the agent reconnected
+    pub fn resume(inner: T, writer: W) -> RecordingTransport<T, W> {
+        Self {
+            inner,
+            recorder: Recorder { writer },
+        }
+    }
+}
+
+impl<T: Transport, W: Write> Transport for RecordingTransport<T, W> {
+    fn read_packet(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let raw = self.inner.read_packet()?;
+        if let Some(raw) = &raw {
+            self.recorder.record(Direction::Received, raw)?;
+        }
+        Ok(raw)
+    }
+
+    fn write_packet(&mut self, raw: &[u8]) -> io::Result<()> {
+        self.recorder.record(Direction::Sent, raw)?;
+        self.inner.write_packet(raw)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::VecDeque;
+    use std::io;
+
+    use super::{read_records, Direction, RecordingTransport};
+    use crate::error::Error;
+    use crate::transport::Transport;
+
+    struct Queue {
+        incoming: VecDeque<Vec<u8>>,
+        sent: Vec<Vec<u8>>,
+    }
+
+    impl Transport for Queue {
+        fn read_packet(&mut self) -> io::Result<Option<Vec<u8>>> {
+            Ok(self.incoming.pop_front())
+        }
+
+        fn write_packet(&mut self, raw: &[u8]) -> io::Result<()> {
+            self.sent.push(raw.to_vec());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_record_both_directions() {
+        let queue = Queue {
+            incoming: VecDeque::from([vec![1, 2, 3]]),
+            sent: vec![],
+        };
+        let mut recording = vec![];
+        let mut transport = RecordingTransport::new(queue, &mut recording).unwrap();
+
+        assert_eq!(transport.read_packet().unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(transport.read_packet().unwrap(), None);
+        transport.write_packet(&[4, 5]).unwrap();
+        assert_eq!(transport.inner.sent, [vec![4, 5]]);
+        drop(transport);
+
+        let records = read_records(&recording[..]).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].direction, Direction::Received);
+        assert_eq!(records[0].raw, [1, 2, 3]);
+        assert_eq!(records[1].direction, Direction::Sent);
+        assert_eq!(records[1].raw, [4, 5]);
+        assert!(records[0].timestamp <= records[1].timestamp);
+    }
+
+    #[test]
+    fn test_invalid_recordings() {
+        assert!(matches!(
+            read_records(&b"not a recording"[..]),
+            Err(Error::InvalidRecording(_))
+        ));
+
+        let mut truncated = b"MTRREC01".to_vec();
+        truncated.extend([1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 10, 1, 2]);
+        assert!(matches!(
+            read_records(&truncated[..]),
+            Err(Error::InvalidRecording(_))
+        ));
+
+        // claims 4 GiB, only a few bytes follow
+        let mut oversized = b"MTRREC01".to_vec();
+        oversized.extend([1, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, 1, 2]);
+        assert!(matches!(
+            read_records(&oversized[..]),
+            Err(Error::InvalidRecording(_))
+        ));
+    }
+}
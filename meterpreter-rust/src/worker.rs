@@ -0,0 +1,109 @@
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use tracing::debug;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+// A fixed number of threads running the commands, with a bounded queue in front of them
+pub struct WorkerPool {
+    sender: SyncSender<Job>,
+}
+
+impl WorkerPool {
+    pub fn new(size: usize, queue_size: usize) -> WorkerPool {
+        assert!(size > 0);
+
+        let (sender, receiver) = mpsc::sync_channel::<Job>(queue_size);
+        let receiver = Arc::new(Mutex::new(receiver));
+        for id in 0..size {
+            let receiver = Arc::clone(&receiver);
+            thread::spawn(move || work(id, &receiver));
+        }
+
+        // the threads aren't joined, a command that never returns mustn't keep the agent from
+        // exiting. They end once the pool is dropped and their current job is done
+        WorkerPool { sender }
+    }
+
+    // Never blocks, a job that finds every worker busy and the queue full is refused
+    pub fn execute<F>(&self, job: F) -> Result<(), QueueFull>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        match self.sender.try_send(Box::new(job)) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => Err(QueueFull),
+            Err(TrySendError::Disconnected(_)) => panic!("worker threads exited"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct QueueFull;
+
+fn work(id: usize, receiver: &Mutex<Receiver<Job>>) {
+    loop {
+        let job = receiver.lock().unwrap().recv();
+        match job {
+            Ok(job) => job(),
+            Err(_) => break,
+        }
+    }
+    debug!(worker = id, "worker stopped");
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc;
+    use std::sync::{Arc, Barrier};
+    use std::time::Duration;
+
+    use super::{QueueFull, WorkerPool};
+
+    #[test]
+    fn test_jobs_run_concurrently() {
+        let pool = WorkerPool::new(3, 3);
+        let barrier = Arc::new(Barrier::new(3));
+        let (sender, receiver) = mpsc::channel();
+        for index in 0..3 {
+            let barrier = Arc::clone(&barrier);
+            let sender = sender.clone();
+            pool.execute(move || {
+                // only gets past this when all three run at the same time
+                barrier.wait();
+                sender.send(index).unwrap();
+            })
+            .unwrap();
+        }
+
+        let mut done: Vec<i32> = (0..3)
+            .map(|_| receiver.recv_timeout(Duration::from_secs(5)).unwrap())
+            .collect();
+        done.sort();
+        assert_eq!(done, [0, 1, 2]);
+    }
+
+    #[test]
+    fn test_queue_is_bounded() {
+        let pool = WorkerPool::new(1, 1);
+        let (release, blocked) = mpsc::channel::<()>();
+        let (started, running) = mpsc::channel();
+        pool.execute(move || {
+            started.send(()).unwrap();
+            blocked.recv().unwrap();
+        })
+        .unwrap();
+        running.recv_timeout(Duration::from_secs(5)).unwrap();
+        // fills the queue, the worker is still busy
+        let (ran, done) = mpsc::channel();
+        pool.execute(move || ran.send(()).unwrap()).unwrap();
+
+        assert_eq!(pool.execute(|| {}), Err(QueueFull));
+
+        release.send(()).unwrap();
+        done.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(pool.execute(|| {}), Ok(()));
+    }
+}
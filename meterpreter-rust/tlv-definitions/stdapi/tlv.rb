@@ -0,0 +1,139 @@
+# -*- coding: binary -*-
+#
+# TLV constants from metasploit-framework
+# lib/rex/post/meterpreter/extensions/stdapi/tlv.rb, limited to the groups this
+# crate implements or parses.
+#
+# Keep the upstream names and layout when updating, build.rs prefixes the
+# generated TlvKey constants with "Stdapi".
+#
+module Rex
+module Post
+module Meterpreter
+module Extensions
+module Stdapi
+
+##
+#
+# General
+#
+##
+TLV_TYPE_HANDLE                = TLV_META_TYPE_QWORD   |  600
+TLV_TYPE_PROCESS_HANDLE        = TLV_META_TYPE_QWORD   |  630
+
+##
+#
+# Sys
+#
+##
+
+# Config
+TLV_TYPE_COMPUTER_NAME         = TLV_META_TYPE_STRING  | 1040
+TLV_TYPE_OS_NAME               = TLV_META_TYPE_STRING  | 1041
+TLV_TYPE_USER_NAME             = TLV_META_TYPE_STRING  | 1042
+TLV_TYPE_ARCHITECTURE          = TLV_META_TYPE_STRING  | 1043
+TLV_TYPE_LANG_SYSTEM           = TLV_META_TYPE_STRING  | 1044
+TLV_TYPE_SID                   = TLV_META_TYPE_STRING  | 1045
+TLV_TYPE_DOMAIN                = TLV_META_TYPE_STRING  | 1046
+TLV_TYPE_LOGGED_ON_USER_COUNT  = TLV_META_TYPE_UINT    | 1047
+TLV_TYPE_LOCAL_DATETIME        = TLV_META_TYPE_STRING  | 1048
+
+# Environment
+TLV_TYPE_ENV_VARIABLE          = TLV_META_TYPE_STRING  | 1100
+TLV_TYPE_ENV_VALUE             = TLV_META_TYPE_STRING  | 1101
+TLV_TYPE_ENV_GROUP             = TLV_META_TYPE_GROUP   | 1102
+
+##
+#
+# Fs
+#
+##
+TLV_TYPE_DIRECTORY_PATH        = TLV_META_TYPE_STRING  | 1200
+TLV_TYPE_FILE_NAME             = TLV_META_TYPE_STRING  | 1201
+TLV_TYPE_FILE_PATH             = TLV_META_TYPE_STRING  | 1202
+TLV_TYPE_FILE_MODE             = TLV_META_TYPE_STRING  | 1203
+TLV_TYPE_FILE_SIZE             = TLV_META_TYPE_UINT    | 1204
+TLV_TYPE_FILE_SHORT_NAME       = TLV_META_TYPE_STRING  | 1205
+TLV_TYPE_FILE_HASH             = TLV_META_TYPE_RAW     | 1206
+
+TLV_TYPE_MOUNT_GROUP           = TLV_META_TYPE_GROUP   | 1207
+TLV_TYPE_MOUNT_NAME            = TLV_META_TYPE_STRING  | 1208
+TLV_TYPE_MOUNT_TYPE            = TLV_META_TYPE_UINT    | 1209
+TLV_TYPE_MOUNT_SPACE_USER      = TLV_META_TYPE_QWORD   | 1210
+TLV_TYPE_MOUNT_SPACE_TOTAL     = TLV_META_TYPE_QWORD   | 1211
+TLV_TYPE_MOUNT_SPACE_FREE      = TLV_META_TYPE_QWORD   | 1212
+TLV_TYPE_MOUNT_UNCPATH         = TLV_META_TYPE_STRING  | 1213
+
+TLV_TYPE_STAT_BUF32            = TLV_META_TYPE_COMPLEX | 1220
+TLV_TYPE_STAT_BUF              = TLV_META_TYPE_COMPLEX | 1221
+
+##
+#
+# Net
+#
+##
+TLV_TYPE_INTERFACE_MTU         = TLV_META_TYPE_UINT    | 1402
+TLV_TYPE_INTERFACE_FLAGS       = TLV_META_TYPE_STRING  | 1403
+TLV_TYPE_INTERFACE_INDEX       = TLV_META_TYPE_UINT    | 1404
+
+TLV_TYPE_SUBNET                = TLV_META_TYPE_RAW     | 1420
+TLV_TYPE_NETMASK               = TLV_META_TYPE_RAW     | 1421
+TLV_TYPE_GATEWAY               = TLV_META_TYPE_RAW     | 1422
+TLV_TYPE_NETWORK_ROUTE         = TLV_META_TYPE_GROUP   | 1423
+TLV_TYPE_IP_PREFIX             = TLV_META_TYPE_UINT    | 1424
+TLV_TYPE_ARP_ENTRY             = TLV_META_TYPE_GROUP   | 1425
+
+TLV_TYPE_IP                    = TLV_META_TYPE_RAW     | 1430
+TLV_TYPE_MAC_ADDRESS           = TLV_META_TYPE_RAW     | 1431
+TLV_TYPE_MAC_NAME              = TLV_META_TYPE_STRING  | 1432
+TLV_TYPE_NETWORK_INTERFACE     = TLV_META_TYPE_GROUP   | 1433
+TLV_TYPE_IP6_SCOPE             = TLV_META_TYPE_RAW     | 1434
+
+TLV_TYPE_SUBNET_STRING         = TLV_META_TYPE_STRING  | 1440
+TLV_TYPE_NETMASK_STRING        = TLV_META_TYPE_STRING  | 1441
+TLV_TYPE_GATEWAY_STRING        = TLV_META_TYPE_STRING  | 1442
+TLV_TYPE_ROUTE_METRIC          = TLV_META_TYPE_UINT    | 1443
+TLV_TYPE_ADDR_TYPE             = TLV_META_TYPE_UINT    | 1444
+
+# Proxy configuration
+TLV_TYPE_PROXY_CFG_AUTODETECT    = TLV_META_TYPE_BOOL   | 1445
+TLV_TYPE_PROXY_CFG_AUTOCONFIGURL = TLV_META_TYPE_STRING | 1446
+TLV_TYPE_PROXY_CFG_PROXY         = TLV_META_TYPE_STRING | 1447
+TLV_TYPE_PROXY_CFG_PROXYBYPASS   = TLV_META_TYPE_STRING | 1448
+
+# Socket
+TLV_TYPE_PEER_HOST             = TLV_META_TYPE_STRING  | 1500
+TLV_TYPE_PEER_PORT             = TLV_META_TYPE_UINT    | 1501
+TLV_TYPE_LOCAL_HOST            = TLV_META_TYPE_STRING  | 1502
+TLV_TYPE_LOCAL_PORT            = TLV_META_TYPE_UINT    | 1503
+TLV_TYPE_CONNECT_RETRIES       = TLV_META_TYPE_UINT    | 1504
+TLV_TYPE_NETSTAT_ENTRY         = TLV_META_TYPE_GROUP   | 1505
+TLV_TYPE_PEER_HOST_RAW         = TLV_META_TYPE_RAW     | 1506
+TLV_TYPE_LOCAL_HOST_RAW        = TLV_META_TYPE_RAW     | 1507
+
+TLV_TYPE_SHUTDOWN_HOW          = TLV_META_TYPE_UINT    | 1530
+
+##
+#
+# Process
+#
+##
+TLV_TYPE_PID                   = TLV_META_TYPE_UINT    | 2300
+TLV_TYPE_PROCESS_NAME          = TLV_META_TYPE_STRING  | 2301
+TLV_TYPE_PROCESS_PATH          = TLV_META_TYPE_STRING  | 2302
+TLV_TYPE_PROCESS_GROUP         = TLV_META_TYPE_GROUP   | 2303
+TLV_TYPE_PROCESS_FLAGS         = TLV_META_TYPE_UINT    | 2304
+TLV_TYPE_PROCESS_ARGUMENTS     = TLV_META_TYPE_STRING  | 2305
+TLV_TYPE_PROCESS_ARCH          = TLV_META_TYPE_UINT    | 2306
+TLV_TYPE_PARENT_PID            = TLV_META_TYPE_UINT    | 2307
+TLV_TYPE_PROCESS_SESSION       = TLV_META_TYPE_UINT    | 2308
+
+##
+#
+# Power
+#
+##
+TLV_TYPE_POWER_FLAGS           = TLV_META_TYPE_UINT    | 4100
+TLV_TYPE_POWER_REASON          = TLV_META_TYPE_UINT    | 4101
+
+end; end; end; end; end
@@ -0,0 +1,310 @@
+use std::fs::{self, File};
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::request::{percent_decode, Method, Request};
+use crate::response::Response;
+
+// Serves the files below a document root. Paths leaving the root, through .. or a symlink
+// pointing outside of it, are forbidden
+pub struct StaticFiles {
+    root: PathBuf,
+}
+
+impl StaticFiles {
+    pub fn new(root: impl AsRef<Path>) -> io::Result<StaticFiles> {
+        let root = root.as_ref().canonicalize()?;
+        if !root.is_dir() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "document root is not a directory",
+            ));
+        }
+        Ok(StaticFiles { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn serve(&self, request: &Request) -> Response {
+        // the body of a response to HEAD is left out when it's written
+        if !matches!(request.method, Method::Get | Method::Head) {
+            return Response::error(405).with_header("Allow", "GET, HEAD");
+        }
+        match self.open(request) {
+            Ok(response) => response,
+            Err(404) => self.not_found(),
+            Err(status) => Response::error(status),
+        }
+    }
+
+    // The page for paths without a file, the root's 404.html when there is one
+    pub fn not_found(&self) -> Response {
+        match fs::read(self.root.join("404.html")) {
+            Ok(page) => Response::html(404, page),
+            Err(_) => Response::error(404),
+        }
+    }
+
+    // Err is the status to answer with instead
+    fn open(&self, request: &Request) -> Result<Response, u16> {
+        let decoded = percent_decode(request.path())
+            .and_then(|path| String::from_utf8(path).ok())
+            .ok_or(400u16)?;
+        let mut path = self.root.clone();
+        for segment in decoded.split('/') {
+            match segment {
+                "" | "." => {}
+                ".." => return Err(403),
+                // a separator or NUL hidden in an escape could make the path mean something else
+                segment if segment.contains(['\\', '\0']) => return Err(403),
+                segment => path.push(segment),
+            }
+        }
+
+        let mut path = self.contained(&path)?;
+        if path.is_dir() {
+            // relative links in the index only work with the trailing slash
+            if !decoded.ends_with('/') {
+                let location = match request.query() {
+                    Some(query) => format!("{}/?{}", request.path(), query),
+                    None => format!("{}/", request.path()),
+                };
+                return Ok(Response::redirect(301, &location));
+            }
+            path = self.contained(&path.join("index.html"))?;
+        } else if decoded.ends_with('/') {
+            return Err(404);
+        }
+
+        let file = File::open(&path).map_err(|err| status_of(&err))?;
+        let metadata = file.metadata().map_err(|err| status_of(&err))?;
+        if !metadata.is_file() {
+            return Err(404);
+        }
+        Ok(Response::file(file, metadata.len(), content_type(&path)))
+    }
+
+    // The path with every symlink resolved, as long as it stays below the root
+    fn contained(&self, path: &Path) -> Result<PathBuf, u16> {
+        let path = path.canonicalize().map_err(|err| status_of(&err))?;
+        if !path.starts_with(&self.root) {
+            return Err(403);
+        }
+        Ok(path)
+    }
+}
+
+fn status_of(err: &io::Error) -> u16 {
+    match err.kind() {
+        ErrorKind::PermissionDenied => 403,
+        _ => 404,
+    }
+}
+
+pub fn content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match &extension[..] {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "md" => "text/markdown; charset=utf-8",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "mp4" => "video/mp4",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use std::env;
+    use std::fs;
+    use std::io::Cursor;
+    use std::path::{Path, PathBuf};
+
+    use super::{content_type, StaticFiles};
+    use crate::request::{Method, Request, Version};
+
+    // A document root with a page, a stylesheet, a binary file and a directory with its own
+    // index, next to a file outside of the root
+    pub fn document_root(name: &str) -> PathBuf {
+        let base = env::temp_dir().join(format!("webserver-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&base);
+        let root = base.join("public");
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::create_dir_all(root.join("empty")).unwrap();
+        fs::write(root.join("index.html"), "<h1>home</h1>").unwrap();
+        fs::write(root.join("404.html"), "<h1>nothing here</h1>").unwrap();
+        fs::write(root.join("style.css"), "h1 {}").unwrap();
+        fs::write(root.join("image.PNG"), b"\x89PNG\r\n\x1a\n\xff\x00").unwrap();
+        fs::write(root.join("docs").join("index.html"), "<h1>docs</h1>").unwrap();
+        fs::write(root.join("docs").join("a b.txt"), "spaced").unwrap();
+        fs::write(base.join("secret.txt"), "secret").unwrap();
+        root
+    }
+
+    pub fn request(method: &str, target: &str) -> Request {
+        let raw = format!("{method} {target} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        Request::read(&mut Cursor::new(raw)).unwrap().unwrap()
+    }
+
+    fn get(files: &StaticFiles, target: &str) -> (u16, Option<String>, Vec<u8>) {
+        let response = files.serve(&request("GET", target));
+        let status = response.status;
+        let content_type = response.headers.get("Content-Type").map(String::from);
+        let mut raw = vec![];
+        response
+            .write_to(&mut raw, Version::Http11, Method::Get)
+            .unwrap();
+        let body_start = raw
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .unwrap()
+            + 4;
+        (status, content_type, raw[body_start..].to_vec())
+    }
+
+    #[test]
+    fn test_serve() {
+        let root = document_root("serve");
+        let files = StaticFiles::new(&root).unwrap();
+
+        let (status, content_type, body) = get(&files, "/");
+        assert_eq!(status, 200);
+        assert_eq!(content_type.as_deref(), Some("text/html; charset=utf-8"));
+        assert_eq!(body, b"<h1>home</h1>");
+
+        assert_eq!(get(&files, "/docs/").2, b"<h1>docs</h1>");
+        assert_eq!(get(&files, "/docs/a%20b.txt?download=1").2, b"spaced");
+        assert_eq!(get(&files, "/./docs//index.html").2, b"<h1>docs</h1>");
+
+        let (status, content_type, body) = get(&files, "/image.PNG");
+        assert_eq!(status, 200);
+        assert_eq!(content_type.as_deref(), Some("image/png"));
+        assert_eq!(body, b"\x89PNG\r\n\x1a\n\xff\x00");
+
+        let response = files.serve(&request("GET", "/docs?page=2"));
+        assert_eq!(response.status, 301);
+        assert_eq!(response.headers.get("Location"), Some("/docs/?page=2"));
+
+        fs::remove_dir_all(root.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_head() {
+        let root = document_root("head");
+        let files = StaticFiles::new(&root).unwrap();
+
+        let mut raw = vec![];
+        files
+            .serve(&request("HEAD", "/style.css"))
+            .write_to(&mut raw, Version::Http11, Method::Head)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(raw).unwrap(),
+            "HTTP/1.1 200 OK\r\nContent-Type: text/css; charset=utf-8\r\n\
+             Content-Length: 5\r\n\r\n"
+        );
+        assert_eq!(files.serve(&request("HEAD", "/missing.html")).status, 404);
+
+        fs::remove_dir_all(root.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_not_found() {
+        let root = document_root("not-found");
+        let files = StaticFiles::new(&root).unwrap();
+
+        for target in ["/missing.html", "/empty/", "/style.css/"] {
+            let (status, _, body) = get(&files, target);
+            assert_eq!((status, &body[..]), (404, &b"<h1>nothing here</h1>"[..]));
+        }
+        fs::remove_file(root.join("404.html")).unwrap();
+        assert_eq!(get(&files, "/missing.html").0, 404);
+
+        assert_eq!(get(&files, "/%zz").0, 400);
+        let response = files.serve(&request("POST", "/"));
+        assert_eq!(response.status, 405);
+        assert_eq!(response.headers.get("Allow"), Some("GET, HEAD"));
+
+        fs::remove_dir_all(root.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_traversal() {
+        let root = document_root("traversal");
+        let files = StaticFiles::new(&root).unwrap();
+
+        for target in [
+            "/../secret.txt",
+            "/docs/../../secret.txt",
+            "/%2e%2e/secret.txt",
+            "/docs%2f..%2f..%2fsecret.txt",
+            "/..%5csecret.txt",
+        ] {
+            assert_eq!(get(&files, target).0, 403, "{}", target);
+        }
+        fs::remove_dir_all(root.parent().unwrap()).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let root = document_root("symlinks");
+        let files = StaticFiles::new(&root).unwrap();
+        let outside = root.parent().unwrap();
+        symlink(outside.join("secret.txt"), root.join("leak.txt")).unwrap();
+        symlink(outside, root.join("up")).unwrap();
+        symlink(root.join("style.css"), root.join("theme.css")).unwrap();
+        fs::create_dir(root.join("linked")).unwrap();
+        symlink(
+            outside.join("secret.txt"),
+            root.join("linked").join("index.html"),
+        )
+        .unwrap();
+
+        assert_eq!(get(&files, "/leak.txt").0, 403);
+        assert_eq!(get(&files, "/up/secret.txt").0, 403);
+        assert_eq!(get(&files, "/linked/").0, 403);
+        // links that stay inside the root are fine
+        assert_eq!(get(&files, "/theme.css").2, b"h1 {}");
+
+        fs::remove_dir_all(outside).unwrap();
+    }
+
+    #[test]
+    fn test_content_type() {
+        assert_eq!(
+            content_type(Path::new("a/b.JS")),
+            "text/javascript; charset=utf-8"
+        );
+        assert_eq!(content_type(Path::new("font.woff2")), "font/woff2");
+        assert_eq!(
+            content_type(Path::new("Makefile")),
+            "application/octet-stream"
+        );
+        assert_eq!(
+            content_type(Path::new("archive.tar.gz")),
+            "application/octet-stream"
+        );
+    }
+}
@@ -1,128 +1,127 @@
 use std::{
-    fs,
-    io::{prelude::*, BufReader},
-    net::{TcpListener, TcpStream},
-    sync::{mpsc, Arc, Mutex},
-    thread,
-    time::Duration,
+    fmt::Display, net::TcpListener, path::PathBuf, process, sync::Arc, thread, time::Duration,
 };
 
-pub struct ThreadPool {
-    workers: Vec<Worker>,
-    sender: Option<mpsc::Sender<Job>>,
+use clap::Parser;
+use webserver::files::StaticFiles;
+use webserver::request::{Method, Request};
+use webserver::router::Router;
+use webserver::server::{self, Handler, KeepAlive};
+use webserver::ThreadPool;
+
+#[derive(Parser)]
+#[clap(about = "Serves the files of a directory")]
+struct Args {
+    /// directory the files are served from
+    #[clap(default_value = "public")]
+    root: PathBuf,
+    /// address plain HTTP is served on
+    #[clap(long, default_value = "127.0.0.1:7878")]
+    address: String,
+    /// seconds a connection waits for the next request, 0 for no limit
+    #[clap(long, default_value_t = 5)]
+    idle_timeout: u64,
+    /// seconds a request may take to arrive once it started, 0 for no limit
+    #[clap(long, default_value_t = 20)]
+    request_timeout: u64,
+    /// requests answered on one connection before it is closed
+    #[clap(long, default_value_t = 100)]
+    max_requests: usize,
+    /// threads serving connections, HTTP and HTTPS together. Each keeps its connection
+    /// while the client may send more requests
+    #[clap(long, default_value_t = 32)]
+    workers: usize,
+    /// address HTTPS is served on
+    #[cfg(feature = "tls")]
+    #[clap(long, requires_all = &["cert", "key"])]
+    tls_address: Option<String>,
+    /// PEM file with the certificate chain, the server's certificate first
+    #[cfg(feature = "tls")]
+    #[clap(long)]
+    cert: Option<PathBuf>,
+    /// PEM file with the private key of the certificate
+    #[cfg(feature = "tls")]
+    #[clap(long)]
+    key: Option<PathBuf>,
+    /// answer plain HTTP with a redirect to HTTPS instead of the files
+    #[cfg(feature = "tls")]
+    #[clap(long, requires = "tls-address")]
+    redirect_http: bool,
 }
 
-type Job = Box<dyn FnOnce() + Send + 'static>;
-
-impl ThreadPool {
-    pub fn new(size: usize) -> ThreadPool {
-        assert!(size > 0);
-
-        let (sender, receiver) = mpsc::channel();
-
-        let receiver = Arc::new(Mutex::new(receiver));
-
-        let mut workers = Vec::with_capacity(size);
-
-        for id in 0..size {
-            workers.push(Worker::new(id, Arc::clone(&receiver)));
-        }
-
-        ThreadPool {
-            workers,
-            sender: Some(sender),
-        }
-    }
-
-    pub fn execute<F>(&self, f: F)
-    where
-        F: FnOnce() + Send + 'static,
-    {
-        let job = Box::new(f);
-        self.sender.as_ref().unwrap().send(job).unwrap();
+fn main() {
+    let args = Args::parse();
+    let files = StaticFiles::new(&args.root)
+        .map(Arc::new)
+        .unwrap_or_else(|err| exit(format!("Can't serve {}: {err}", args.root.display())));
+    println!("Serving {}", files.root().display());
+
+    let keep_alive = KeepAlive {
+        idle_timeout: Duration::from_secs(args.idle_timeout),
+        request_timeout: Duration::from_secs(args.request_timeout),
+        max_requests: args.max_requests,
+    };
+    if args.workers == 0 {
+        exit("--workers needs at least 1");
     }
-}
-
-impl Drop for ThreadPool {
-    fn drop(&mut self) {
-        drop(self.sender.take());
-
-        for worker in &mut self.workers {
-            println!("Shutting down worker {}", worker.id);
-
-            if let Some(thread) = worker.thread.take() {
-                thread.join().unwrap();
-            }
-        }
+    let listener = bind(&args.address);
+    let pool = Arc::new(ThreadPool::new(args.workers));
+    let router = routes(files);
+    let handler: Arc<Handler> = Arc::new(move |request: &Request| router.handle(request));
+
+    #[cfg(feature = "tls")]
+    if let (Some(tls_address), Some(cert), Some(key)) = (&args.tls_address, &args.cert, &args.key) {
+        let config = webserver::tls::load_config(cert, key)
+            .unwrap_or_else(|err| exit(format!("Can't load the certificate: {err}")));
+        let tls_listener = bind(tls_address);
+        let plain_handler: Arc<Handler> = if args.redirect_http {
+            let port = tls_listener
+                .local_addr()
+                .map_or(443, |address| address.port());
+            Arc::new(server::https_redirect(port))
+        } else {
+            Arc::clone(&handler)
+        };
+
+        let plain_pool = Arc::clone(&pool);
+        thread::spawn(move || server::serve(listener, &plain_pool, plain_handler, keep_alive));
+        webserver::tls::serve(tls_listener, &pool, config, handler, keep_alive);
+        return;
     }
-}
 
-struct Worker {
-    id: usize,
-    thread: Option<thread::JoinHandle<()>>,
+    server::serve(listener, &pool, handler, keep_alive);
+    println!("Shutting dowm");
 }
 
-impl Worker {
-    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Job>>>) -> Worker {
-        let thread = thread::spawn(move || loop {
-            match receiver.lock().unwrap().recv() {
-                Ok(job) => {
-                    println!("Worker {id} got a job; executing.");
-                    job();
-                }
-                Err(_) => {
-                    println!("Worker {id} disconnected; shutting down.");
-                    break;
-                }
-            }
-        });
-
-        Worker {
-            id,
-            thread: Some(thread),
-        }
+fn bind(address: &str) -> TcpListener {
+    let listener = TcpListener::bind(address)
+        .unwrap_or_else(|err| exit(format!("Can't listen on {address}: {err}")));
+    if let Ok(address) = listener.local_addr() {
+        println!("Listening on {address}");
     }
+    listener
 }
 
-fn main() {
-    let listener = TcpListener::bind("127.0.0.1:7878").unwrap();
-    let pool = ThreadPool::new(4);
-
-    for stream in listener.incoming() {
-        let stream = stream.unwrap();
-
-        pool.execute(|| {
-            handle_connection(stream);
-        });
-    }
-
-    println!("Shutting dowm");
+fn exit(message: impl Display) -> ! {
+    eprintln!("{message}");
+    process::exit(1);
 }
 
-fn handle_connection(mut stream: TcpStream) {
-    let buf_reader = BufReader::new(&mut stream);
-    let http_request: Vec<_> = buf_reader
-        .lines()
-        .map(|res| res.unwrap())
-        .take_while(|line| !line.is_empty())
-        .collect();
-
-    println!("Request: {:#?}", http_request);
-
-    let request_line = &http_request[0];
-
-    let (status_line, filename) = match &request_line[..] {
-        "GET / HTTP/1.1" => ("HTTP/1.1 200 OK", "hello.html"),
-        "GET /sleep HTTP/1.1" => {
+fn routes(files: Arc<StaticFiles>) -> Router {
+    let index = Arc::clone(&files);
+    let head_files = Arc::clone(&files);
+    Router::new()
+        .route(Method::Get, "/sleep", move |request, _| {
             thread::sleep(Duration::from_secs(5));
-            ("HTTP/1.1 200 OK", "hello.html")
-        }
-        _ => ("HTTP/1.1 404 NOT FOUND", "404.html"),
-    };
-
-    let contents = fs::read_to_string(filename).unwrap();
-    let length = contents.len();
-    let response = format!("{status_line}\r\nContent-Length: {length}\r\n\r\n{contents}");
-
-    stream.write_all(response.as_bytes()).unwrap();
+            index.serve(&Request {
+                target: String::from("/"),
+                ..request.clone()
+            })
+        })
+        .route(Method::Get, "/*path", move |request, _| {
+            files.serve(request)
+        })
+        .route(Method::Head, "/*path", move |request, _| {
+            head_files.serve(request)
+        })
 }
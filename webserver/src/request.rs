@@ -0,0 +1,669 @@
+use std::fmt;
+use std::io::{self, BufRead, Read};
+use std::str::FromStr;
+
+use thiserror::Error;
+
+// Request line and headers together, anything longer is refused
+pub const MAX_HEAD_SIZE: usize = 8 * 1024;
+pub const MAX_BODY_SIZE: usize = 1024 * 1024;
+// Size and extensions of a chunk of a chunked body
+const MAX_CHUNK_LINE: usize = 1024;
+
+#[derive(Error, Debug)]
+pub enum ParseError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+
+    #[error("Bad request: {0}")]
+    BadRequest(&'static str),
+
+    #[error("Request line too long")]
+    UriTooLong,
+
+    #[error("Request headers too large")]
+    HeadersTooLarge,
+
+    #[error("Request body too large")]
+    PayloadTooLarge,
+
+    #[error("Method or transfer encoding not implemented")]
+    NotImplemented,
+
+    #[error("HTTP version not supported")]
+    VersionNotSupported,
+}
+
+impl ParseError {
+    // The status to answer with, None when the connection itself failed
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            ParseError::Io(_) => None,
+            ParseError::BadRequest(_) => Some(400),
+            ParseError::UriTooLong => Some(414),
+            ParseError::HeadersTooLarge => Some(431),
+            ParseError::PayloadTooLarge => Some(413),
+            ParseError::NotImplemented => Some(501),
+            ParseError::VersionNotSupported => Some(505),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Method {
+    Get,
+    Head,
+    Post,
+    Put,
+    Delete,
+    Patch,
+    Options,
+}
+
+impl Method {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Method::Get => "GET",
+            Method::Head => "HEAD",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Delete => "DELETE",
+            Method::Patch => "PATCH",
+            Method::Options => "OPTIONS",
+        }
+    }
+}
+
+impl FromStr for Method {
+    type Err = ParseError;
+
+    // Methods are case-sensitive, "get" is some other method nobody implements
+    fn from_str(method: &str) -> Result<Self, Self::Err> {
+        match method {
+            "GET" => Ok(Method::Get),
+            "HEAD" => Ok(Method::Head),
+            "POST" => Ok(Method::Post),
+            "PUT" => Ok(Method::Put),
+            "DELETE" => Ok(Method::Delete),
+            "PATCH" => Ok(Method::Patch),
+            "OPTIONS" => Ok(Method::Options),
+            method if is_token(method) => Err(ParseError::NotImplemented),
+            _ => Err(ParseError::BadRequest("invalid method")),
+        }
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Version {
+    Http10,
+    Http11,
+}
+
+impl FromStr for Version {
+    type Err = ParseError;
+
+    fn from_str(version: &str) -> Result<Self, Self::Err> {
+        match version {
+            "HTTP/1.0" => Ok(Version::Http10),
+            "HTTP/1.1" => Ok(Version::Http11),
+            _ => {
+                // well formed but some other version, like HTTP/2.0 or HTTP/0.9
+                let digits = version.strip_prefix("HTTP/").map(str::as_bytes);
+                match digits {
+                    Some([major, b'.', minor])
+                        if major.is_ascii_digit() && minor.is_ascii_digit() =>
+                    {
+                        Err(ParseError::VersionNotSupported)
+                    }
+                    _ => Err(ParseError::BadRequest("invalid version")),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Version::Http10 => f.write_str("HTTP/1.0"),
+            Version::Http11 => f.write_str("HTTP/1.1"),
+        }
+    }
+}
+
+// Header fields in the order they came, names compared without case
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Headers(Vec<(String, String)>);
+
+impl Headers {
+    pub fn new() -> Headers {
+        Headers(vec![])
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(field, _)| field.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.0
+            .iter()
+            .filter(move |(field, _)| field.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    pub fn add(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.0.push((name.into(), value.into()));
+    }
+
+    // Replaces every field of that name
+    pub fn set(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        let name = name.into();
+        self.remove(&name);
+        self.add(name, value);
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.0
+            .retain(|(field, _)| !field.eq_ignore_ascii_case(name));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: Method,
+    pub target: String,
+    pub version: Version,
+    pub headers: Headers,
+    pub body: Vec<u8>,
+    // what came after a chunked body
+    pub trailers: Headers,
+}
+
+impl Request {
+    // The next request on the connection, None when the client closed it before sending one
+    pub fn read(reader: &mut impl BufRead) -> Result<Option<Request>, ParseError> {
+        let mut head_left = MAX_HEAD_SIZE;
+
+        // empty lines before the request line are allowed and skipped
+        let request_line = loop {
+            match read_line(reader, &mut head_left, ParseError::UriTooLong)? {
+                None => return Ok(None),
+                Some(line) if line.is_empty() => continue,
+                Some(line) => break line,
+            }
+        };
+        let mut parts = request_line.split(' ');
+        let (method, target, version) =
+            match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                (Some(method), Some(target), Some(version), None) => (method, target, version),
+                _ => return Err(ParseError::BadRequest("malformed request line")),
+            };
+        let method: Method = method.parse()?;
+        let version: Version = version.parse()?;
+        if !is_valid_target(target) {
+            return Err(ParseError::BadRequest("invalid request target"));
+        }
+
+        let mut headers = Headers::new();
+        loop {
+            let line = read_line(reader, &mut head_left, ParseError::HeadersTooLarge)?.ok_or(
+                ParseError::BadRequest("connection closed within the headers"),
+            )?;
+            if line.is_empty() {
+                break;
+            }
+            let (name, value) = parse_header(&line)?;
+            headers.add(name, value);
+        }
+        if version == Version::Http11 && headers.get_all("Host").count() != 1 {
+            return Err(ParseError::BadRequest(
+                "exactly one Host header is required",
+            ));
+        }
+
+        let (body, trailers) = read_body(reader, &headers, version)?;
+        Ok(Some(Request {
+            method,
+            target: target.to_string(),
+            version,
+            headers,
+            body,
+            trailers,
+        }))
+    }
+
+    // The target without its query string
+    pub fn path(&self) -> &str {
+        self.target
+            .split_once('?')
+            .map_or(&self.target[..], |(path, _)| path)
+    }
+
+    pub fn query(&self) -> Option<&str> {
+        self.target.split_once('?').map(|(_, query)| query)
+    }
+}
+
+// %XX escapes turned back into the bytes they stand for, None when an escape is broken
+pub fn percent_decode(text: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(text.len());
+    let mut bytes = text.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte != b'%' {
+            decoded.push(byte);
+            continue;
+        }
+        let high = (bytes.next()? as char).to_digit(16)?;
+        let low = (bytes.next()? as char).to_digit(16)?;
+        decoded.push((high * 16 + low) as u8);
+    }
+    Some(decoded)
+}
+
+// A line without its line ending. The line counts against what is left of the head, too long
+// and it's the given error
+fn read_line(
+    reader: &mut impl BufRead,
+    left: &mut usize,
+    too_long: ParseError,
+) -> Result<Option<String>, ParseError> {
+    let mut line = vec![];
+    // one more than allowed to tell a line of exactly the limit from a longer one
+    reader
+        .by_ref()
+        .take(*left as u64 + 1)
+        .read_until(b'\n', &mut line)?;
+    if line.is_empty() {
+        return Ok(None);
+    }
+    if line.len() > *left {
+        return Err(too_long);
+    }
+    *left -= line.len();
+
+    match line.pop() {
+        Some(b'\n') => {}
+        _ => return Err(ParseError::BadRequest("connection closed within a line")),
+    }
+    // a bare LF is tolerated, a CR anywhere else is not
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    if line.contains(&b'\r') {
+        return Err(ParseError::BadRequest("stray carriage return"));
+    }
+    String::from_utf8(line)
+        .map(Some)
+        .map_err(|_| ParseError::BadRequest("request head is not UTF-8"))
+}
+
+fn parse_header(line: &str) -> Result<(&str, &str), ParseError> {
+    // folded lines were deprecated long ago, they are rejected rather than guessed at
+    if line.starts_with([' ', '\t']) {
+        return Err(ParseError::BadRequest("obsolete line folding"));
+    }
+    let (name, value) = line
+        .split_once(':')
+        .ok_or(ParseError::BadRequest("header without a colon"))?;
+    if !is_token(name) {
+        return Err(ParseError::BadRequest("invalid header name"));
+    }
+    let value = value.trim_matches([' ', '\t']);
+    if value.chars().any(|c| c.is_ascii_control() && c != '\t') {
+        return Err(ParseError::BadRequest("invalid header value"));
+    }
+    Ok((name, value))
+}
+
+fn read_body(
+    reader: &mut impl BufRead,
+    headers: &Headers,
+    version: Version,
+) -> Result<(Vec<u8>, Headers), ParseError> {
+    if headers.contains("Transfer-Encoding") {
+        // whoever is in front of the server could go by the length instead, and see another
+        // request in the body
+        if headers.contains("Content-Length") {
+            return Err(ParseError::BadRequest(
+                "both Transfer-Encoding and Content-Length",
+            ));
+        }
+        if version == Version::Http10 {
+            return Err(ParseError::BadRequest("Transfer-Encoding in HTTP/1.0"));
+        }
+        let codings: Vec<&str> = headers
+            .get_all("Transfer-Encoding")
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|coding| !coding.is_empty())
+            .collect();
+        return match &codings[..] {
+            [coding] if coding.eq_ignore_ascii_case("chunked") => read_chunked(reader),
+            // only the final chunked tells where the body ends
+            [.., last] if last.eq_ignore_ascii_case("chunked") => Err(ParseError::NotImplemented),
+            _ => Err(ParseError::BadRequest(
+                "chunked isn't the final transfer coding",
+            )),
+        };
+    }
+
+    let mut lengths = headers.get_all("Content-Length");
+    let length = match lengths.next() {
+        None => return Ok((vec![], Headers::new())),
+        Some(length) => length,
+    };
+    // repeated lengths are only fine when they all agree
+    if lengths.any(|other| other != length) {
+        return Err(ParseError::BadRequest("conflicting Content-Length headers"));
+    }
+    if length.is_empty() || !length.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(ParseError::BadRequest("invalid Content-Length"));
+    }
+    let length: usize = match length.parse() {
+        Ok(length) if length <= MAX_BODY_SIZE => length,
+        _ => return Err(ParseError::PayloadTooLarge),
+    };
+
+    let mut body = vec![0; length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::UnexpectedEof => {
+                ParseError::BadRequest("body shorter than Content-Length")
+            }
+            _ => ParseError::Io(err),
+        })?;
+    Ok((body, Headers::new()))
+}
+
+// The chunks joined up, and the trailer fields after the last one
+fn read_chunked(reader: &mut impl BufRead) -> Result<(Vec<u8>, Headers), ParseError> {
+    let closed = || ParseError::BadRequest("connection closed within the body");
+    let mut body = vec![];
+    loop {
+        let mut line_left = MAX_CHUNK_LINE;
+        let line = read_line(
+            reader,
+            &mut line_left,
+            ParseError::BadRequest("chunk size line too long"),
+        )?
+        .ok_or_else(closed)?;
+        // extensions after the size mean nothing to the server
+        let size = line
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches([' ', '\t']);
+        if size.is_empty() || !size.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(ParseError::BadRequest("invalid chunk size"));
+        }
+        let size = usize::from_str_radix(size, 16)
+            .ok()
+            .filter(|size| *size <= MAX_BODY_SIZE - body.len())
+            .ok_or(ParseError::PayloadTooLarge)?;
+        if size == 0 {
+            break;
+        }
+
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader
+            .read_exact(&mut body[start..])
+            .map_err(|err| match err.kind() {
+                io::ErrorKind::UnexpectedEof => closed(),
+                _ => ParseError::Io(err),
+            })?;
+        // the data has to end right where its size says
+        let too_long = || ParseError::BadRequest("chunk longer than its size");
+        match read_line(reader, &mut 2, too_long())? {
+            Some(end) if end.is_empty() => {}
+            Some(_) => return Err(too_long()),
+            None => return Err(closed()),
+        }
+    }
+
+    let mut trailers = Headers::new();
+    let mut left = MAX_HEAD_SIZE;
+    loop {
+        let line = read_line(reader, &mut left, ParseError::HeadersTooLarge)?.ok_or_else(closed)?;
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = parse_header(&line)?;
+        trailers.add(name, value);
+    }
+    Ok((body, trailers))
+}
+
+// Origin form like /path?query, or * for OPTIONS. Absolute URLs only come through proxies
+fn is_valid_target(target: &str) -> bool {
+    target == "*" || target.starts_with('/') && target.bytes().all(|byte| byte.is_ascii_graphic())
+}
+
+fn is_token(text: &str) -> bool {
+    !text.is_empty()
+        && text
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte))
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{BufReader, Cursor};
+
+    use super::{percent_decode, Method, ParseError, Request, Version, MAX_HEAD_SIZE};
+
+    fn parse(raw: &str) -> Result<Option<Request>, ParseError> {
+        Request::read(&mut Cursor::new(raw.as_bytes()))
+    }
+
+    fn status(raw: &str) -> Option<u16> {
+        parse(raw).unwrap_err().status()
+    }
+
+    #[test]
+    fn test_request() {
+        let request = parse(
+            "\r\nPOST /users?page=2 HTTP/1.1\r\nHost: localhost\r\ncontent-LENGTH: 5\r\n\
+             X-Empty:\r\nX-Twice: a\r\nX-Twice:  b \r\n\r\nhelloGET",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(request.method, Method::Post);
+        assert_eq!(request.target, "/users?page=2");
+        assert_eq!(request.version, Version::Http11);
+        assert_eq!(request.headers.get("Content-Length"), Some("5"));
+        assert_eq!(request.headers.get("host"), Some("localhost"));
+        assert_eq!(request.headers.get("x-empty"), Some(""));
+        assert_eq!(
+            request.headers.get_all("x-twice").collect::<Vec<_>>(),
+            ["a", "b"]
+        );
+        assert_eq!(request.body, b"hello");
+        assert_eq!(request.path(), "/users");
+        assert_eq!(request.query(), Some("page=2"));
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("a%20b%2fc").unwrap(), b"a b/c");
+        assert_eq!(percent_decode("%e2%9C%93").unwrap(), "\u{2713}".as_bytes());
+        assert!(percent_decode("%2").is_none());
+        assert!(percent_decode("%zz").is_none());
+    }
+
+    #[test]
+    fn test_requests_on_one_stream() {
+        let mut reader = BufReader::new(Cursor::new(
+            "GET / HTTP/1.0\n\nPUT /a HTTP/1.0\nContent-Length: 2\n\nhi".as_bytes(),
+        ));
+        let first = Request::read(&mut reader).unwrap().unwrap();
+        assert_eq!(
+            (first.method, first.version),
+            (Method::Get, Version::Http10)
+        );
+        let second = Request::read(&mut reader).unwrap().unwrap();
+        assert_eq!((second.method, &second.body[..]), (Method::Put, &b"hi"[..]));
+        assert!(Request::read(&mut reader).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_empty_connection() {
+        assert!(parse("").unwrap().is_none());
+        assert_eq!(status("GET / HTTP/1.1"), Some(400));
+    }
+
+    #[test]
+    fn test_bad_requests() {
+        for raw in [
+            "GET /\r\n\r\n",
+            "GET  / HTTP/1.1\r\nHost: a\r\n\r\n",
+            "GET / HTTP/1.1 extra\r\nHost: a\r\n\r\n",
+            "G(T / HTTP/1.1\r\nHost: a\r\n\r\n",
+            "GET index.html HTTP/1.1\r\nHost: a\r\n\r\n",
+            "GET / HTTP/one\r\nHost: a\r\n\r\n",
+            "GET / HTTP/1.1\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a\r\nHost: b\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a\r\nNo colon\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a\r\nBad Name: x\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a\r\nX-Folded: a\r\n b\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a\rX: b\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a\r\nContent-Length: -1\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a\r\nContent-Length: 1\r\nContent-Length: 2\r\n\r\nab",
+            "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 10\r\n\r\nshort",
+            "GET / HTTP/1.1\r\nHost: a\r\n",
+        ] {
+            assert_eq!(status(raw), Some(400), "{:?}", raw);
+        }
+        assert!(matches!(
+            Request::read(&mut Cursor::new(&b"GET /\xff HTTP/1.1\r\n\r\n"[..])),
+            Err(ParseError::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_unsupported() {
+        assert_eq!(status("GET / HTTP/2.0\r\n\r\n"), Some(505));
+        assert_eq!(status("GET / HTTP/0.9\r\n\r\n"), Some(505));
+        assert_eq!(status("BREW /pot HTTP/1.1\r\nHost: a\r\n\r\n"), Some(501));
+        assert_eq!(
+            status("POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: gzip, chunked\r\n\r\n"),
+            Some(501)
+        );
+        assert_eq!(
+            status("POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 99999999999999999999\r\n\r\n"),
+            Some(413)
+        );
+    }
+
+    #[test]
+    fn test_size_limits() {
+        let long_target = format!(
+            "GET /{} HTTP/1.1\r\nHost: a\r\n\r\n",
+            "a".repeat(MAX_HEAD_SIZE)
+        );
+        assert_eq!(status(&long_target), Some(414));
+
+        let big_header = format!(
+            "GET / HTTP/1.1\r\nHost: a\r\nX: {}\r\n\r\n",
+            "a".repeat(MAX_HEAD_SIZE)
+        );
+        assert_eq!(status(&big_header), Some(431));
+        let many_headers = format!(
+            "GET / HTTP/1.1\r\nHost: a\r\n{}\r\n",
+            "X: aaaa\r\n".repeat(1000)
+        );
+        assert_eq!(status(&many_headers), Some(431));
+
+        // right at the limit is still fine
+        let head = "GET / HTTP/1.1\r\nHost: a\r\nX: \r\n\r\n";
+        let exact = head.replace(
+            "X: ",
+            &format!("X: {}", "a".repeat(MAX_HEAD_SIZE - head.len())),
+        );
+        assert!(parse(&exact).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_chunked_body() {
+        let mut reader = Cursor::new(
+            "POST /upload HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n\
+             5\r\nhello\r\n1;name=value\r\n \r\nA \r\n0123456789\n0\r\n\
+             Checksum: abc\r\nExpires: never\r\n\r\nGET / HTTP/1.1\r\nHost: a\r\n\r\n"
+                .as_bytes(),
+        );
+        let request = Request::read(&mut reader).unwrap().unwrap();
+        assert_eq!(request.body, b"hello 0123456789");
+        assert_eq!(request.trailers.get("checksum"), Some("abc"));
+        assert_eq!(request.trailers.get("Expires"), Some("never"));
+        // the next request starts right after the trailers
+        let next = Request::read(&mut reader).unwrap().unwrap();
+        assert_eq!((next.method, next.body.len()), (Method::Get, 0));
+
+        let request =
+            parse("POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: Chunked\r\n\r\n0\r\n\r\n")
+                .unwrap()
+                .unwrap();
+        assert!(request.body.is_empty());
+        assert!(request.trailers.iter().next().is_none());
+    }
+
+    #[test]
+    fn test_bad_chunked_bodies() {
+        let chunked = |body: &str| {
+            status(&format!(
+                "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n{body}"
+            ))
+        };
+        for body in [
+            "",
+            "5\r\nhel",
+            "5\r\nhello",
+            "5\r\nhello world\r\n0\r\n\r\n",
+            "x\r\nhello\r\n0\r\n\r\n",
+            "\r\nhello\r\n0\r\n\r\n",
+            "-5\r\nhello\r\n0\r\n\r\n",
+            "0\r\nBad Trailer\r\n\r\n",
+            "0\r\n",
+        ] {
+            assert_eq!(chunked(body), Some(400), "{:?}", body);
+        }
+        assert_eq!(chunked(&format!("1{}\r\n", "0".repeat(2000))), Some(400));
+        assert_eq!(chunked("ffffffffffffffffffff\r\n"), Some(413));
+        assert_eq!(chunked("100001\r\n"), Some(413));
+        let trailers = format!("0\r\n{}\r\n", "X: aaaa\r\n".repeat(1000));
+        assert_eq!(chunked(&trailers), Some(431));
+
+        for headers in [
+            "Transfer-Encoding: chunked\r\nContent-Length: 5",
+            "Transfer-Encoding: chunked, gzip",
+            "Transfer-Encoding: gzip",
+        ] {
+            let raw = format!("POST / HTTP/1.1\r\nHost: a\r\n{headers}\r\n\r\n0\r\n\r\n");
+            assert_eq!(status(&raw), Some(400), "{:?}", headers);
+        }
+        assert_eq!(
+            status("POST / HTTP/1.0\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n"),
+            Some(400)
+        );
+    }
+}
@@ -0,0 +1,338 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+
+use crate::request::{Headers, Method, Version};
+
+// Bodies of unknown length are sent chunked to HTTP/1.1 clients. HTTP/1.0 ones get them as
+// they are, the end of the connection is the end of the body
+pub enum Body {
+    Bytes(Vec<u8>),
+    // streamed from the file as it is written, the length is taken when the file is opened
+    File(File, u64),
+    Reader(Box<dyn Read + Send>),
+    // one chunk each, empty ones are skipped
+    Chunks(Box<dyn Iterator<Item = Vec<u8>> + Send>),
+}
+
+impl Body {
+    // None when it's only known once everything was sent
+    pub fn length(&self) -> Option<u64> {
+        match self {
+            Body::Bytes(bytes) => Some(bytes.len() as u64),
+            Body::File(_, length) => Some(*length),
+            Body::Reader(_) | Body::Chunks(_) => None,
+        }
+    }
+}
+
+pub struct Response {
+    pub status: u16,
+    pub headers: Headers,
+    pub body: Body,
+}
+
+impl Response {
+    pub fn new(status: u16, body: impl Into<Vec<u8>>) -> Response {
+        Response {
+            status,
+            headers: Headers::new(),
+            body: Body::Bytes(body.into()),
+        }
+    }
+
+    pub fn file(file: File, length: u64, content_type: &str) -> Response {
+        Response {
+            status: 200,
+            headers: Headers::new(),
+            body: Body::File(file, length),
+        }
+        .with_header("Content-Type", content_type)
+    }
+
+    pub fn stream(status: u16, reader: impl Read + Send + 'static) -> Response {
+        Response {
+            status,
+            headers: Headers::new(),
+            body: Body::Reader(Box::new(reader)),
+        }
+    }
+
+    pub fn chunks<I>(status: u16, chunks: I) -> Response
+    where
+        I: IntoIterator<Item = Vec<u8>>,
+        I::IntoIter: Send + 'static,
+    {
+        Response {
+            status,
+            headers: Headers::new(),
+            body: Body::Chunks(Box::new(chunks.into_iter())),
+        }
+    }
+
+    pub fn redirect(status: u16, location: &str) -> Response {
+        Response::new(status, "").with_header("Location", location)
+    }
+
+    pub fn html(status: u16, body: impl Into<Vec<u8>>) -> Response {
+        Response::new(status, body).with_header("Content-Type", "text/html; charset=utf-8")
+    }
+
+    // A short page saying what went wrong
+    pub fn error(status: u16) -> Response {
+        let title = format!("{} {}", status, reason(status));
+        Response::html(
+            status,
+            format!(
+                "<!DOCTYPE html>\n<html><head><title>{title}</title></head>\
+                 <body><h1>{title}</h1></body></html>\n"
+            ),
+        )
+    }
+
+    pub fn with_header(mut self, name: &str, value: impl Into<String>) -> Response {
+        self.headers.set(name, value);
+        self
+    }
+
+    // Whether the body goes out at all, a response to HEAD only has the headers the one to
+    // GET would have
+    pub fn sends_body(&self, method: Method) -> bool {
+        method != Method::Head && has_body(self.status)
+    }
+
+    // The response to a request of the given version and method
+    pub fn write_to(
+        self,
+        writer: &mut impl Write,
+        version: Version,
+        method: Method,
+    ) -> io::Result<()> {
+        let framed = has_body(self.status);
+        let length = self.body.length();
+        let chunked = framed && length.is_none() && version == Version::Http11;
+        let mut head = format!("HTTP/1.1 {} {}\r\n", self.status, reason(self.status));
+        for (name, value) in self.headers.iter() {
+            head += &format!("{name}: {value}\r\n");
+        }
+        match length {
+            Some(length) if framed => head += &format!("Content-Length: {length}\r\n"),
+            None if chunked => head += "Transfer-Encoding: chunked\r\n",
+            _ => {}
+        }
+        head += "\r\n";
+        writer.write_all(head.as_bytes())?;
+        if !self.sends_body(method) {
+            return writer.flush();
+        }
+
+        match self.body {
+            Body::Bytes(bytes) => writer.write_all(&bytes)?,
+            Body::File(file, length) => {
+                // a file that shrank in the meantime would leave the client waiting
+                let copied = io::copy(&mut file.take(length), writer)?;
+                if copied < length {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "file shorter than when it was opened",
+                    ));
+                }
+            }
+            Body::Reader(mut reader) => {
+                let mut buffer = vec![0; 8 * 1024];
+                loop {
+                    let read = match reader.read(&mut buffer) {
+                        Ok(0) => break,
+                        Ok(read) => read,
+                        Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                        Err(err) => return Err(err),
+                    };
+                    write_chunk(writer, &buffer[..read], chunked)?;
+                }
+            }
+            Body::Chunks(chunks) => {
+                for chunk in chunks {
+                    write_chunk(writer, &chunk, chunked)?;
+                }
+            }
+        }
+        if chunked {
+            writer.write_all(b"0\r\n\r\n")?;
+        }
+        writer.flush()
+    }
+}
+
+// Sent right away, whoever streams the body wants it to arrive as it's produced
+fn write_chunk(writer: &mut impl Write, data: &[u8], chunked: bool) -> io::Result<()> {
+    if !chunked {
+        writer.write_all(data)?;
+        return writer.flush();
+    }
+    // an empty chunk would end the body
+    if data.is_empty() {
+        return Ok(());
+    }
+    let mut chunk = format!("{:X}\r\n", data.len()).into_bytes();
+    chunk.extend_from_slice(data);
+    chunk.extend_from_slice(b"\r\n");
+    writer.write_all(&chunk)?;
+    writer.flush()
+}
+
+// Informational responses, 204 and 304 end with their headers, whatever their body is
+fn has_body(status: u16) -> bool {
+    !matches!(status, 100..=199 | 204 | 304)
+}
+
+pub fn reason(status: u16) -> &'static str {
+    match status {
+        100 => "Continue",
+        101 => "Switching Protocols",
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        204 => "No Content",
+        206 => "Partial Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        303 => "See Other",
+        304 => "Not Modified",
+        307 => "Temporary Redirect",
+        308 => "Permanent Redirect",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        406 => "Not Acceptable",
+        408 => "Request Timeout",
+        409 => "Conflict",
+        410 => "Gone",
+        411 => "Length Required",
+        412 => "Precondition Failed",
+        413 => "Payload Too Large",
+        414 => "URI Too Long",
+        415 => "Unsupported Media Type",
+        416 => "Range Not Satisfiable",
+        422 => "Unprocessable Content",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        505 => "HTTP Version Not Supported",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::Response;
+    use crate::request::{Method, Version};
+
+    fn written(response: Response, version: Version) -> String {
+        let mut raw = vec![];
+        response.write_to(&mut raw, version, Method::Get).unwrap();
+        String::from_utf8(raw).unwrap()
+    }
+
+    #[test]
+    fn test_write_to() {
+        let mut raw = vec![];
+        Response::new(404, "gone")
+            .with_header("X-Test", "a")
+            .with_header("x-test", "b")
+            .write_to(&mut raw, Version::Http11, Method::Get)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(raw).unwrap(),
+            "HTTP/1.1 404 Not Found\r\nx-test: b\r\nContent-Length: 4\r\n\r\ngone"
+        );
+    }
+
+    #[test]
+    fn test_chunked() {
+        let chunks = ["Hello", "", ", world", "!".repeat(20).as_str()]
+            .map(|chunk| chunk.as_bytes().to_vec());
+        assert_eq!(
+            written(Response::chunks(200, chunks.clone()), Version::Http11),
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+             5\r\nHello\r\n7\r\n, world\r\n14\r\n!!!!!!!!!!!!!!!!!!!!\r\n0\r\n\r\n"
+        );
+        // HTTP/1.0 has no chunks, the body goes as it is
+        assert_eq!(
+            written(Response::chunks(200, chunks), Version::Http10),
+            format!("HTTP/1.1 200 OK\r\n\r\nHello, world{}", "!".repeat(20))
+        );
+    }
+
+    #[test]
+    fn test_no_body_statuses() {
+        // no framing and no body, the next response starts right after the headers
+        assert_eq!(
+            written(Response::chunks(204, vec![]), Version::Http11),
+            "HTTP/1.1 204 No Content\r\n\r\n"
+        );
+        assert_eq!(
+            written(
+                Response::new(304, "stale").with_header("ETag", "\"1\""),
+                Version::Http11
+            ),
+            "HTTP/1.1 304 Not Modified\r\nETag: \"1\"\r\n\r\n"
+        );
+        assert_eq!(
+            written(Response::new(101, ""), Version::Http10),
+            "HTTP/1.1 101 Switching Protocols\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_head() {
+        let mut raw = vec![];
+        Response::new(200, "body")
+            .write_to(&mut raw, Version::Http11, Method::Head)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(raw).unwrap(),
+            "HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\n"
+        );
+
+        let mut raw = vec![];
+        Response::chunks(200, [b"never read".to_vec()])
+            .write_to(&mut raw, Version::Http11, Method::Head)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(raw).unwrap(),
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_streamed_reader() {
+        let data = vec![b'x'; 20 * 1024];
+        let raw = written(
+            Response::stream(200, Cursor::new(data.clone())),
+            Version::Http11,
+        );
+        let (head, mut body) = raw.split_once("\r\n\r\n").unwrap();
+        assert!(head.ends_with("Transfer-Encoding: chunked"));
+
+        // decoded, it's the data again
+        let mut decoded = String::new();
+        loop {
+            let (size, rest) = body.split_once("\r\n").unwrap();
+            let size = usize::from_str_radix(size, 16).unwrap();
+            if size == 0 {
+                assert_eq!(rest, "\r\n");
+                break;
+            }
+            decoded += &rest[..size];
+            body = rest[size..].strip_prefix("\r\n").unwrap();
+        }
+        assert_eq!(decoded.as_bytes(), data);
+    }
+}
@@ -0,0 +1,324 @@
+use std::str::FromStr;
+
+use crate::request::{percent_decode, Method, Request};
+use crate::response::Response;
+
+pub type RouteHandler = dyn Fn(&Request, &Params) -> Response + Send + Sync;
+
+// Sends each request to the first route matching its method and path. A path matching only
+// routes of other methods is answered with 405 and the methods it has, any other with 404
+pub struct Router {
+    routes: Vec<Route>,
+}
+
+struct Route {
+    method: Method,
+    segments: Vec<Segment>,
+    handler: Box<RouteHandler>,
+}
+
+enum Segment {
+    Literal(String),
+    // :name, one segment
+    Param(String),
+    // *name, whatever is left of the path, only at the end
+    Rest(String),
+}
+
+impl Router {
+    pub fn new() -> Router {
+        Router { routes: vec![] }
+    }
+
+    // Patterns are paths like /users/:id or /files/*path, they panic when malformed
+    pub fn route(
+        mut self,
+        method: Method,
+        pattern: &str,
+        handler: impl Fn(&Request, &Params) -> Response + Send + Sync + 'static,
+    ) -> Router {
+        self.routes.push(Route {
+            method,
+            segments: parse_pattern(pattern),
+            handler: Box::new(handler),
+        });
+        self
+    }
+
+    pub fn handle(&self, request: &Request) -> Response {
+        let segments = match request.path().strip_prefix('/').map(|path| {
+            path.split('/')
+                .map(|segment| percent_decode(segment).and_then(|s| String::from_utf8(s).ok()))
+                .collect::<Option<Vec<String>>>()
+        }) {
+            Some(Some(segments)) => segments,
+            Some(None) => return Response::error(400),
+            // the asterisk form of OPTIONS
+            None => return Response::error(404),
+        };
+        let query = match parse_query(request.query().unwrap_or_default()) {
+            Some(query) => query,
+            None => return Response::error(400),
+        };
+
+        let mut allowed: Vec<Method> = vec![];
+        for route in &self.routes {
+            let path = match route.matches(&segments) {
+                Some(path) => path,
+                None => continue,
+            };
+            if route.method == request.method {
+                return (route.handler)(request, &Params { path, query });
+            }
+            if !allowed.contains(&route.method) {
+                allowed.push(route.method);
+            }
+        }
+
+        if allowed.is_empty() {
+            return Response::error(404);
+        }
+        let allow: Vec<&str> = allowed.iter().map(Method::as_str).collect();
+        Response::error(405).with_header("Allow", allow.join(", "))
+    }
+}
+
+impl Default for Router {
+    fn default() -> Self {
+        Router::new()
+    }
+}
+
+impl Route {
+    // The parameters when the path matches
+    fn matches(&self, path: &[String]) -> Option<Vec<(String, String)>> {
+        let mut params = vec![];
+        let mut path = path.iter();
+        for segment in &self.segments {
+            match segment {
+                Segment::Rest(name) => {
+                    let rest: Vec<&str> = path.by_ref().map(String::as_str).collect();
+                    params.push((name.clone(), rest.join("/")));
+                }
+                Segment::Literal(literal) => {
+                    if path.next() != Some(literal) {
+                        return None;
+                    }
+                }
+                Segment::Param(name) => match path.next() {
+                    Some(value) if !value.is_empty() => params.push((name.clone(), value.clone())),
+                    _ => return None,
+                },
+            }
+        }
+        path.next().is_none().then_some(params)
+    }
+}
+
+fn parse_pattern(pattern: &str) -> Vec<Segment> {
+    let path = pattern
+        .strip_prefix('/')
+        .unwrap_or_else(|| panic!("route pattern '{pattern}' doesn't start with /"));
+    let segments: Vec<Segment> = path
+        .split('/')
+        .map(|segment| {
+            let segment = match (segment.strip_prefix(':'), segment.strip_prefix('*')) {
+                (Some(name), _) => Segment::Param(String::from(name)),
+                (_, Some(name)) => Segment::Rest(String::from(name)),
+                _ => return Segment::Literal(String::from(segment)),
+            };
+            if let Segment::Param(name) | Segment::Rest(name) = &segment {
+                assert!(
+                    !name.is_empty(),
+                    "unnamed parameter in route pattern '{pattern}'"
+                );
+            }
+            segment
+        })
+        .collect();
+    if let Some(index) = segments
+        .iter()
+        .position(|segment| matches!(segment, Segment::Rest(_)))
+    {
+        assert!(
+            index == segments.len() - 1,
+            "*parameter before the end of route pattern '{pattern}'"
+        );
+    }
+    segments
+}
+
+// Pairs of a form encoded query, + standing for a space. None when an escape is broken
+fn parse_query(query: &str) -> Option<Vec<(String, String)>> {
+    let decode = |text: &str| {
+        percent_decode(&text.replace('+', " ")).and_then(|text| String::from_utf8(text).ok())
+    };
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Some((decode(name)?, decode(value)?))
+        })
+        .collect()
+}
+
+// The path parameters of the route and the query string, decoded
+#[derive(Debug, Default)]
+pub struct Params {
+    path: Vec<(String, String)>,
+    query: Vec<(String, String)>,
+}
+
+impl Params {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        first(&self.path, name)
+    }
+
+    // The path parameter as T, None when it's missing or doesn't parse
+    pub fn parse<T: FromStr>(&self, name: &str) -> Option<T> {
+        self.get(name)?.parse().ok()
+    }
+
+    pub fn query(&self, name: &str) -> Option<&str> {
+        first(&self.query, name)
+    }
+
+    pub fn query_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        lookup(&self.query, name)
+    }
+
+    pub fn parse_query<T: FromStr>(&self, name: &str) -> Option<T> {
+        self.query(name)?.parse().ok()
+    }
+}
+
+fn first<'a>(pairs: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    pairs
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.as_str())
+}
+
+fn lookup<'a>(pairs: &'a [(String, String)], name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    pairs
+        .iter()
+        .filter(move |(key, _)| key == name)
+        .map(|(_, value)| value.as_str())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Params, Router};
+    use crate::files::test::request;
+    use crate::request::Method;
+    use crate::response::{Body, Response};
+
+    fn body(response: &Response) -> &str {
+        match &response.body {
+            Body::Bytes(bytes) => std::str::from_utf8(bytes).unwrap(),
+            _ => panic!("not a buffered body"),
+        }
+    }
+
+    fn router() -> Router {
+        Router::new()
+            .route(Method::Get, "/", |_, _| Response::new(200, "home"))
+            .route(Method::Get, "/users/new", |_, _| Response::new(200, "form"))
+            .route(
+                Method::Get,
+                "/users/:id",
+                |_, params: &Params| match params.parse::<u32>("id") {
+                    Some(id) => Response::new(200, format!("user {id}")),
+                    None => Response::error(400),
+                },
+            )
+            .route(Method::Delete, "/users/:id", |_, params| {
+                Response::new(200, format!("deleted {}", params.get("id").unwrap()))
+            })
+            .route(Method::Get, "/users/:id/posts/:post", |_, params| {
+                let page: u32 = params.parse_query("page").unwrap_or(1);
+                Response::new(
+                    200,
+                    format!(
+                        "{} {} {page}",
+                        params.get("id").unwrap(),
+                        params.get("post").unwrap()
+                    ),
+                )
+            })
+            .route(Method::Post, "/search", |_, params| {
+                let tags: Vec<&str> = params.query_all("tag").collect();
+                Response::new(200, format!("{:?} {:?}", params.query("q"), tags))
+            })
+            .route(Method::Get, "/files/*path", |_, params| {
+                Response::new(200, format!("file {}", params.get("path").unwrap()))
+            })
+    }
+
+    fn handle(method: &str, target: &str) -> Response {
+        router().handle(&request(method, target))
+    }
+
+    #[test]
+    fn test_routes() {
+        assert_eq!(body(&handle("GET", "/")), "home");
+        // the first route wins
+        assert_eq!(body(&handle("GET", "/users/new")), "form");
+        assert_eq!(body(&handle("GET", "/users/42")), "user 42");
+        assert_eq!(handle("GET", "/users/abc").status, 400);
+        assert_eq!(body(&handle("DELETE", "/users/a%20b")), "deleted a b");
+        assert_eq!(
+            body(&handle("GET", "/users/7/posts/hello?page=3")),
+            "7 hello 3"
+        );
+        assert_eq!(body(&handle("GET", "/users/7/posts/hello")), "7 hello 1");
+        assert_eq!(
+            body(&handle("POST", "/search?q=rust+web%21&tag=a&tag=b&empty")),
+            "Some(\"rust web!\") [\"a\", \"b\"]"
+        );
+        assert_eq!(body(&handle("GET", "/files/")), "file ");
+        assert_eq!(
+            body(&handle("GET", "/files/docs/a%2Fb.txt")),
+            "file docs/a/b.txt"
+        );
+    }
+
+    #[test]
+    fn test_not_matched() {
+        for target in [
+            "/nope",
+            "/users",
+            "/users/",
+            "/users/7/",
+            "/users/7/posts",
+            "*",
+        ] {
+            assert_eq!(handle("GET", target).status, 404, "{}", target);
+        }
+        assert_eq!(handle("GET", "/users/%zz").status, 400);
+        assert_eq!(handle("GET", "/?q=%zz").status, 400);
+    }
+
+    #[test]
+    fn test_method_not_allowed() {
+        let response = handle("PUT", "/users/42");
+        assert_eq!(response.status, 405);
+        assert_eq!(response.headers.get("Allow"), Some("GET, DELETE"));
+
+        let response = handle("GET", "/search");
+        assert_eq!(response.headers.get("Allow"), Some("POST"));
+    }
+
+    #[test]
+    #[should_panic(expected = "doesn't start with /")]
+    fn test_relative_pattern() {
+        Router::new().route(Method::Get, "users", |_, _| Response::new(200, ""));
+    }
+
+    #[test]
+    #[should_panic(expected = "before the end")]
+    fn test_rest_before_the_end() {
+        Router::new().route(Method::Get, "/*path/edit", |_, _| Response::new(200, ""));
+    }
+}
@@ -0,0 +1,607 @@
+use std::cell::Cell;
+use std::io::{self, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::request::{Headers, Method, ParseError, Request, Version};
+use crate::response::Response;
+use crate::ThreadPool;
+
+// Answers the requests, the same one for every listener
+pub type Handler = dyn Fn(&Request) -> Response + Send + Sync;
+
+// How long a connection stays open for more requests, and how long a request may take
+#[derive(Debug, Clone, Copy)]
+pub struct KeepAlive {
+    // waited for the next request, and for each read while one is coming in. Zero waits
+    // forever
+    pub idle_timeout: Duration,
+    // from the first byte of a request to its last, however they trickle in. Zero has no
+    // limit
+    pub request_timeout: Duration,
+    // after that many the connection is closed, the first request is always answered
+    pub max_requests: usize,
+}
+
+impl Default for KeepAlive {
+    fn default() -> Self {
+        KeepAlive {
+            idle_timeout: Duration::from_secs(5),
+            request_timeout: Duration::from_secs(20),
+            max_requests: 100,
+        }
+    }
+}
+
+// When the request being read has to be complete. Shared by a connection and the reads
+// of its socket, which start the clock once the first byte of a request arrived
+#[derive(Debug, Clone)]
+pub struct Deadline {
+    timeout: Duration,
+    clock: Rc<Cell<Clock>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Clock {
+    Stopped,
+    // waiting for a request
+    Armed,
+    Running(Instant),
+}
+
+impl Deadline {
+    pub fn new(timeout: Duration) -> Deadline {
+        Deadline {
+            timeout,
+            clock: Rc::new(Cell::new(Clock::Stopped)),
+        }
+    }
+
+    // A request that is already buffered has started arriving
+    fn wait_for_request(&self, buffered: bool) {
+        let clock = if self.timeout.is_zero() {
+            Clock::Stopped
+        } else if buffered {
+            Clock::Running(Instant::now() + self.timeout)
+        } else {
+            Clock::Armed
+        };
+        self.clock.set(clock);
+    }
+
+    fn stop(&self) {
+        self.clock.set(Clock::Stopped);
+    }
+
+    fn received(&self) {
+        if let Clock::Armed = self.clock.get() {
+            self.clock
+                .set(Clock::Running(Instant::now() + self.timeout));
+        }
+    }
+
+    fn left(&self) -> Option<Duration> {
+        match self.clock.get() {
+            Clock::Running(at) => Some(at.saturating_duration_since(Instant::now())),
+            Clock::Stopped | Clock::Armed => None,
+        }
+    }
+}
+
+// A socket whose reads wait at most the idle timeout, and fail once the request being read
+// is past its deadline
+pub struct TimedStream {
+    stream: TcpStream,
+    idle_timeout: Option<Duration>,
+    deadline: Deadline,
+}
+
+impl TimedStream {
+    pub fn new(stream: TcpStream, keep_alive: &KeepAlive) -> (TimedStream, Deadline) {
+        let deadline = Deadline::new(keep_alive.request_timeout);
+        let stream = TimedStream {
+            stream,
+            idle_timeout: (!keep_alive.idle_timeout.is_zero()).then_some(keep_alive.idle_timeout),
+            deadline: deadline.clone(),
+        };
+        (stream, deadline)
+    }
+}
+
+impl Read for TimedStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let timeout = match self.deadline.left() {
+            Some(left) if left.is_zero() => {
+                return Err(io::Error::new(ErrorKind::TimedOut, "request took too long"))
+            }
+            Some(left) => Some(self.idle_timeout.map_or(left, |idle| idle.min(left))),
+            None => self.idle_timeout,
+        };
+        self.stream.set_read_timeout(timeout)?;
+        let read = self.stream.read(buf)?;
+        if read > 0 {
+            self.deadline.received();
+        }
+        Ok(read)
+    }
+}
+
+impl Write for TimedStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+pub fn serve(
+    listener: TcpListener,
+    pool: &ThreadPool,
+    handler: Arc<Handler>,
+    keep_alive: KeepAlive,
+) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                println!("Accepting a connection failed: {err}");
+                continue;
+            }
+        };
+
+        let handler = Arc::clone(&handler);
+        pool.execute(move || {
+            let (mut stream, deadline) = TimedStream::new(stream, &keep_alive);
+            handle_connection(&mut stream, &*handler, &keep_alive, &deadline);
+        });
+    }
+}
+
+// Answers requests in the order they come until the client or the server wants the
+// connection closed. Requests sent before their predecessors were answered are read from
+// the buffer they ended up in
+pub fn handle_connection(
+    stream: &mut (impl Read + Write),
+    handler: &Handler,
+    keep_alive: &KeepAlive,
+    deadline: &Deadline,
+) {
+    let mut reader = BufReader::new(stream);
+    for count in 1.. {
+        deadline.wait_for_request(!reader.buffer().is_empty());
+        let read = Request::read(&mut reader);
+        deadline.stop();
+        let (response, version, method, close) = match read {
+            Ok(Some(request)) => {
+                println!(
+                    "Request: {} {} {}",
+                    request.method, request.target, request.version
+                );
+                let mut response = handler(&request);
+                // without chunks, only closing the connection tells HTTP/1.0 clients where
+                // a body of unknown length ends
+                let close = !wants_keep_alive(&request)
+                    || count >= keep_alive.max_requests
+                    || has_token(&response.headers, "Connection", "close")
+                    || request.version == Version::Http10
+                        && response.body.length().is_none()
+                        && response.sends_body(request.method);
+                if close {
+                    response.headers.set("Connection", "close");
+                } else if request.version == Version::Http10 {
+                    response.headers.set("Connection", "keep-alive");
+                }
+                (response, request.version, request.method, close)
+            }
+            Ok(None) => return,
+            Err(err) => match err.status() {
+                // whatever follows can't be trusted to be a request, the connection is closed
+                Some(status) => {
+                    println!("Rejected request: {err}");
+                    let response = Response::error(status).with_header("Connection", "close");
+                    (response, Version::Http11, Method::Get, true)
+                }
+                None if is_timeout(&err) => return,
+                None => {
+                    println!("Reading request failed: {err}");
+                    return;
+                }
+            },
+        };
+
+        if let Err(err) = response.write_to(reader.get_mut(), version, method) {
+            println!("Writing response failed: {err}");
+            return;
+        }
+        if close {
+            return;
+        }
+    }
+}
+
+// HTTP/1.1 connections stay open unless closed, HTTP/1.0 ones only when asked to
+fn wants_keep_alive(request: &Request) -> bool {
+    match request.version {
+        Version::Http11 => !has_token(&request.headers, "Connection", "close"),
+        Version::Http10 => has_token(&request.headers, "Connection", "keep-alive"),
+    }
+}
+
+// Whether a comma separated header like Connection lists the token
+fn has_token(headers: &Headers, name: &str, token: &str) -> bool {
+    headers
+        .get_all(name)
+        .flat_map(|value| value.split(','))
+        .any(|item| item.trim().eq_ignore_ascii_case(token))
+}
+
+fn is_timeout(err: &ParseError) -> bool {
+    match err {
+        ParseError::Io(err) => matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut),
+        _ => false,
+    }
+}
+
+// Sends every request to the same host and target over HTTPS on the given port
+pub fn https_redirect(https_port: u16) -> impl Fn(&Request) -> Response + Send + Sync {
+    move |request| {
+        let host = match request.headers.get("Host") {
+            Some(host) if !host.is_empty() => host_name(host),
+            _ => return Response::error(400),
+        };
+        let location = match https_port {
+            443 => format!("https://{}{}", host, request.target),
+            port => format!("https://{}:{}{}", host, port, request.target),
+        };
+        // 308 keeps the method and body, browsers only do that for GET and HEAD with 301
+        let status = match request.method {
+            Method::Get | Method::Head => 301,
+            _ => 308,
+        };
+        Response::redirect(status, &location)
+    }
+}
+
+// The Host header without its port, brackets of IPv6 addresses kept
+fn host_name(host: &str) -> &str {
+    match host.rfind([':', ']']) {
+        Some(index) if host.as_bytes()[index] == b':' => &host[..index],
+        _ => host,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::{handle_connection, host_name, https_redirect, serve, Deadline, KeepAlive};
+    use crate::files::test::request;
+    use crate::response::Response;
+    use crate::ThreadPool;
+
+    // Input and output of a connection, kept apart
+    struct Connection {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for Connection {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Connection {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn exchange(input: &str) -> String {
+        exchange_with(input, &KeepAlive::default())
+    }
+
+    fn exchange_with(input: &str, keep_alive: &KeepAlive) -> String {
+        let mut connection = Connection {
+            input: Cursor::new(input.as_bytes().to_vec()),
+            output: vec![],
+        };
+        let handler = |request: &crate::request::Request| match request.path() {
+            "/bye" => Response::new(200, "bye").with_header("Connection", "close"),
+            "/stream" => Response::chunks(200, [b"ab".to_vec(), b"c".to_vec()]),
+            "/empty" => Response::chunks(204, vec![]),
+            "/echo" => Response::new(200, request.body.clone()),
+            _ => Response::new(200, request.target.clone()),
+        };
+        let deadline = Deadline::new(keep_alive.request_timeout);
+        handle_connection(&mut connection, &handler, keep_alive, &deadline);
+        String::from_utf8(connection.output).unwrap()
+    }
+
+    #[test]
+    fn test_handle_connection() {
+        assert_eq!(
+            exchange("GET /a HTTP/1.1\r\nHost: localhost\r\n\r\n"),
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n/a"
+        );
+        let rejected = exchange("GET /a HTTP/1.1\r\n\r\n");
+        assert!(rejected.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        assert!(rejected.contains("\r\nConnection: close\r\n"));
+        assert_eq!(exchange(""), "");
+    }
+
+    #[test]
+    fn test_pipelined_requests() {
+        let output = exchange(
+            "GET /a HTTP/1.1\r\nHost: a\r\n\r\nPOST /b HTTP/1.1\r\nHost: a\r\n\
+             Content-Length: 3\r\n\r\nxyzGET /c HTTP/1.1\r\nHost: a\r\n\r\n",
+        );
+        assert_eq!(
+            output,
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n/a\
+             HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n/b\
+             HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n/c"
+        );
+
+        // a broken request ends the connection, the ones after it are left alone
+        let output =
+            exchange("GET /a HTTP/1.1\r\nHost: a\r\n\r\nbroken\r\n\r\nGET /c HTTP/1.1\r\n\r\n");
+        assert_eq!(output.matches("HTTP/1.1 ").count(), 2);
+        assert!(output.ends_with("400 Bad Request</h1></body></html>\n"));
+    }
+
+    #[test]
+    fn test_connection_close() {
+        let answered = |output: &str| output.matches("HTTP/1.1 200").count();
+        let requests = "GET /a HTTP/1.1\r\nHost: a\r\nConnection: Keep-Alive, close\r\n\r\n\
+                        GET /b HTTP/1.1\r\nHost: a\r\n\r\n";
+        let output = exchange(requests);
+        assert_eq!(answered(&output), 1);
+        assert!(output.contains("\r\nConnection: close\r\n"));
+
+        // the handler can close it as well
+        let output =
+            exchange("GET /bye HTTP/1.1\r\nHost: a\r\n\r\nGET /b HTTP/1.1\r\nHost: a\r\n\r\n");
+        assert_eq!(answered(&output), 1);
+
+        let keep_alive = KeepAlive {
+            max_requests: 2,
+            ..KeepAlive::default()
+        };
+        let requests = "GET /a HTTP/1.1\r\nHost: a\r\n\r\n".repeat(3);
+        let output = exchange_with(&requests, &keep_alive);
+        assert_eq!(answered(&output), 2);
+        assert_eq!(output.matches("Connection: close").count(), 1);
+        assert!(output.ends_with("Connection: close\r\nContent-Length: 2\r\n\r\n/a"));
+    }
+
+    #[test]
+    fn test_pipelined_head() {
+        let output = exchange(
+            "HEAD /a HTTP/1.1\r\nHost: a\r\n\r\nHEAD /stream HTTP/1.1\r\nHost: a\r\n\r\n\
+             GET /b HTTP/1.1\r\nHost: a\r\n\r\n",
+        );
+        assert_eq!(
+            output,
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n\
+             HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+             HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n/b"
+        );
+
+        // with no body to end, the connection of an HTTP/1.0 client stays open
+        let output = exchange(
+            "HEAD /stream HTTP/1.0\r\nConnection: keep-alive\r\n\r\nGET /b HTTP/1.0\r\n\r\n",
+        );
+        assert_eq!(
+            output,
+            "HTTP/1.1 200 OK\r\nConnection: keep-alive\r\n\r\n\
+             HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 2\r\n\r\n/b"
+        );
+    }
+
+    #[test]
+    fn test_http_1_0() {
+        let output = exchange("GET /a HTTP/1.0\r\n\r\nGET /b HTTP/1.0\r\n\r\n");
+        assert_eq!(
+            output,
+            "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 2\r\n\r\n/a"
+        );
+
+        let output =
+            exchange("GET /a HTTP/1.0\r\nConnection: keep-alive\r\n\r\nGET /b HTTP/1.0\r\n\r\n");
+        assert_eq!(
+            output,
+            "HTTP/1.1 200 OK\r\nConnection: keep-alive\r\nContent-Length: 2\r\n\r\n/a\
+             HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 2\r\n\r\n/b"
+        );
+    }
+
+    #[test]
+    fn test_chunked_on_a_connection() {
+        let output = exchange(
+            "POST /echo HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n\
+             3\r\nabc\r\n0\r\nTrailer: x\r\n\r\nGET /stream HTTP/1.1\r\nHost: a\r\n\r\n\
+             GET /a HTTP/1.1\r\nHost: a\r\n\r\n",
+        );
+        assert_eq!(
+            output,
+            "HTTP/1.1 200 OK\r\nContent-Length: 3\r\n\r\nabc\
+             HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nab\r\n1\r\nc\r\n0\r\n\r\n\
+             HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n/a"
+        );
+
+        let output =
+            exchange("DELETE /empty HTTP/1.1\r\nHost: a\r\n\r\nGET /a HTTP/1.1\r\nHost: a\r\n\r\n");
+        assert_eq!(
+            output,
+            "HTTP/1.1 204 No Content\r\n\r\nHTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n/a"
+        );
+
+        // an HTTP/1.0 client only knows the body ended when the connection does
+        let output = exchange(
+            "GET /stream HTTP/1.0\r\nConnection: keep-alive\r\n\r\nGET /a HTTP/1.0\r\n\r\n",
+        );
+        assert_eq!(output, "HTTP/1.1 200 OK\r\nConnection: close\r\n\r\nabc");
+    }
+
+    #[test]
+    fn test_idle_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let keep_alive = KeepAlive {
+            idle_timeout: Duration::from_millis(200),
+            ..KeepAlive::default()
+        };
+        thread::spawn(move || {
+            let handler = Arc::new(|_: &crate::request::Request| Response::new(200, "ok"));
+            serve(listener, &ThreadPool::new(1), handler, keep_alive);
+        });
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        for _ in 0..2 {
+            stream
+                .write_all(b"GET / HTTP/1.1\r\nHost: a\r\n\r\n")
+                .unwrap();
+            let mut buf = vec![0; response.len()];
+            stream.read_exact(&mut buf).unwrap();
+            assert_eq!(buf, response);
+        }
+
+        // nothing more comes, the server hangs up
+        let started = Instant::now();
+        assert_eq!(stream.read(&mut [0; 16]).unwrap(), 0);
+        assert!(started.elapsed() >= Duration::from_millis(150));
+
+        // the single worker is free again for the next connection
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        stream.write_all(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+        let mut buf = vec![];
+        stream.read_to_end(&mut buf).unwrap();
+        assert!(buf.ends_with(b"\r\n\r\nok"));
+    }
+
+    #[test]
+    fn test_connections_served_in_parallel() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let handler = Arc::new(|_: &crate::request::Request| Response::new(200, "ok"));
+            serve(listener, &ThreadPool::new(2), handler, KeepAlive::default());
+        });
+
+        // the first connection stays open and keeps its worker, the second one gets the other
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        let mut streams = vec![];
+        for _ in 0..2 {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            stream
+                .write_all(b"GET / HTTP/1.1\r\nHost: a\r\n\r\n")
+                .unwrap();
+            let mut buf = vec![0; response.len()];
+            stream.read_exact(&mut buf).unwrap();
+            assert_eq!(buf, response);
+            streams.push(stream);
+        }
+
+        for stream in &mut streams {
+            stream
+                .write_all(b"GET / HTTP/1.1\r\nHost: a\r\n\r\n")
+                .unwrap();
+            let mut buf = vec![0; response.len()];
+            stream.read_exact(&mut buf).unwrap();
+            assert_eq!(buf, response);
+        }
+    }
+
+    #[test]
+    fn test_request_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let keep_alive = KeepAlive {
+            idle_timeout: Duration::from_millis(1000),
+            request_timeout: Duration::from_millis(400),
+            ..KeepAlive::default()
+        };
+        thread::spawn(move || {
+            let handler = Arc::new(|_: &crate::request::Request| Response::new(200, "ok"));
+            serve(listener, &ThreadPool::new(1), handler, keep_alive);
+        });
+
+        // waiting for the request doesn't count, only how long it takes once it started
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        thread::sleep(Duration::from_millis(600));
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: a\r\n\r\n")
+            .unwrap();
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        let mut buf = vec![0; response.len()];
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, response);
+
+        // a byte now and then stays under the idle timeout, the request still gets cut off
+        let started = Instant::now();
+        for byte in b"GET / HTTP/1.1\r\nHost: a\r\n".iter() {
+            if stream.write_all(&[*byte]).is_err() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        let mut rest = vec![];
+        let _ = stream.read_to_end(&mut rest);
+        assert!(rest.is_empty());
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(350), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(1500), "{:?}", elapsed);
+    }
+
+    #[test]
+    fn test_https_redirect() {
+        let redirect = https_redirect(8443);
+        let response = redirect(&request("GET", "/docs/?page=2"));
+        assert_eq!(response.status, 301);
+        assert_eq!(
+            response.headers.get("Location"),
+            Some("https://localhost:8443/docs/?page=2")
+        );
+        assert_eq!(redirect(&request("POST", "/form")).status, 308);
+        assert_eq!(
+            https_redirect(443)(&request("GET", "/"))
+                .headers
+                .get("Location"),
+            Some("https://localhost/")
+        );
+    }
+
+    #[test]
+    fn test_host_name() {
+        assert_eq!(host_name("example.com:8080"), "example.com");
+        assert_eq!(host_name("example.com"), "example.com");
+        assert_eq!(host_name("[::1]:8080"), "[::1]");
+        assert_eq!(host_name("[::1]"), "[::1]");
+    }
+}
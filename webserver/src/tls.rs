@@ -0,0 +1,189 @@
+use std::io::{self, ErrorKind};
+use std::net::TcpListener;
+use std::path::Path;
+use std::sync::Arc;
+
+use rustls::crypto::ring;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+
+use crate::server::{handle_connection, Handler, KeepAlive, TimedStream};
+use crate::ThreadPool;
+
+// The server side of TLS from a PEM certificate chain, leaf first, and the PEM private key
+pub fn load_config(cert_path: &Path, key_path: &Path) -> io::Result<Arc<ServerConfig>> {
+    let pem_error = |path: &Path, err| {
+        io::Error::new(
+            ErrorKind::InvalidData,
+            format!("{}: {}", path.display(), err),
+        )
+    };
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|err| pem_error(cert_path, err))?;
+    if certs.is_empty() {
+        return Err(pem_error(
+            cert_path,
+            rustls::pki_types::pem::Error::NoItemsFound,
+        ));
+    }
+    let key = PrivateKeyDer::from_pem_file(key_path).map_err(|err| pem_error(key_path, err))?;
+
+    let config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+        .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?;
+    Ok(Arc::new(config))
+}
+
+// Like server::serve, with the handshake and the encryption done on the worker thread
+pub fn serve(
+    listener: TcpListener,
+    pool: &ThreadPool,
+    config: Arc<ServerConfig>,
+    handler: Arc<Handler>,
+    keep_alive: KeepAlive,
+) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                println!("Accepting a connection failed: {err}");
+                continue;
+            }
+        };
+        let connection = match ServerConnection::new(Arc::clone(&config)) {
+            Ok(connection) => connection,
+            Err(err) => {
+                println!("Setting up TLS failed: {err}");
+                continue;
+            }
+        };
+
+        let handler = Arc::clone(&handler);
+        pool.execute(move || {
+            // the handshake is read like the first request, under its deadline
+            let (stream, deadline) = TimedStream::new(stream, &keep_alive);
+            let mut stream = StreamOwned::new(connection, stream);
+            handle_connection(&mut stream, &*handler, &keep_alive, &deadline);
+
+            // tells the client the response is complete rather than cut off. Written
+            // directly, flushing the stream would wait for a failed handshake to go on
+            let StreamOwned { mut conn, mut sock } = stream;
+            if !conn.is_handshaking() {
+                conn.send_close_notify();
+            }
+            while conn.wants_write() {
+                if let Err(err) = conn.write_tls(&mut sock) {
+                    println!("Closing TLS connection failed: {err}");
+                    break;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+    use std::fs;
+    use std::io::{ErrorKind, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::thread;
+
+    use rcgen::CertifiedKey;
+    use rustls::crypto::ring;
+    use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+
+    use super::load_config;
+    use crate::response::Response;
+    use crate::server::KeepAlive;
+    use crate::ThreadPool;
+
+    // A self-signed certificate for localhost and the paths of its PEM files
+    fn certificate(name: &str) -> (CertifiedKey, PathBuf, PathBuf) {
+        let certified =
+            rcgen::generate_simple_self_signed(vec![String::from("localhost")]).unwrap();
+        let base = env::temp_dir().join(format!("webserver-{}-{}", std::process::id(), name));
+        let (cert_path, key_path) = (base.with_extension("crt"), base.with_extension("key"));
+        fs::write(&cert_path, certified.cert.pem()).unwrap();
+        fs::write(&key_path, certified.key_pair.serialize_pem()).unwrap();
+        (certified, cert_path, key_path)
+    }
+
+    fn client(certified: &CertifiedKey, port: u16) -> StreamOwned<ClientConnection, TcpStream> {
+        let mut roots = RootCertStore::empty();
+        roots.add(certified.cert.der().clone()).unwrap();
+        let config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let connection =
+            ClientConnection::new(Arc::new(config), "localhost".try_into().unwrap()).unwrap();
+        StreamOwned::new(connection, TcpStream::connect(("127.0.0.1", port)).unwrap())
+    }
+
+    #[test]
+    fn test_https() {
+        let (certified, cert_path, key_path) = certificate("https");
+        let config = load_config(&cert_path, &key_path).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let pool = ThreadPool::new(2);
+            super::serve(
+                listener,
+                &pool,
+                config,
+                Arc::new(|request| Response::new(200, format!("secure {}", request.target))),
+                KeepAlive::default(),
+            );
+        });
+
+        let mut stream = client(&certified, port);
+        stream
+            .write_all(b"GET /page HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        stream
+            .write_all(b"GET /last HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert_eq!(
+            response,
+            "HTTP/1.1 200 OK\r\nContent-Length: 12\r\n\r\nsecure /page\
+             HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 12\r\n\r\nsecure /last"
+        );
+
+        // plain HTTP on the TLS port gets nowhere
+        let mut plain = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        plain
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let mut response = vec![];
+        let _ = plain.read_to_end(&mut response);
+        assert!(!response.starts_with(b"HTTP/1.1"));
+
+        fs::remove_file(cert_path).unwrap();
+        fs::remove_file(key_path).unwrap();
+    }
+
+    #[test]
+    fn test_load_config_errors() {
+        let (_, cert_path, key_path) = certificate("load");
+
+        let missing = cert_path.with_extension("missing");
+        assert!(load_config(&missing, &key_path).is_err());
+        // the files the wrong way around
+        let err = load_config(&key_path, &cert_path).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(err.to_string().contains(&*key_path.to_string_lossy()));
+
+        fs::remove_file(cert_path).unwrap();
+        fs::remove_file(key_path).unwrap();
+    }
+}